@@ -0,0 +1,98 @@
+use serde::Deserialize;
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IssuesData {
+    pub actor: IssuesActor,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IssuesActor {
+    pub account: IssuesAccount,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IssuesAccount {
+    pub ai_issues: AiIssues,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AiIssues {
+    pub issues: IssueResults,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IssueResults {
+    pub issues: Vec<Issue>,
+}
+
+#[derive(Default, Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Issue {
+    pub issue_id: String,
+    #[serde(default)]
+    pub title: Vec<String>,
+    pub priority: String,
+    pub state: String,
+    #[serde(default)]
+    pub condition_name: Vec<String>,
+    #[serde(default)]
+    pub condition_family_id: Vec<i64>,
+    #[serde(default)]
+    pub entity_names: Vec<String>,
+    // Epoch milliseconds
+    pub created_at: Option<i64>,
+    pub acknowledged_at: Option<i64>,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AckData {
+    pub ai_issues_ack_issue: AckResult,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AckResult {
+    pub error: Option<String>,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConditionData {
+    pub actor: ConditionActor,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConditionActor {
+    pub account: ConditionAccount,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConditionAccount {
+    pub alerts: ConditionAlerts,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConditionAlerts {
+    pub nrql_condition: Option<NrqlCondition>,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NrqlCondition {
+    pub nrql: ConditionNrql,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConditionNrql {
+    pub query: String,
+}
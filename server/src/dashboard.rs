@@ -0,0 +1,86 @@
+use serde::Deserialize;
+use serde_json::Value;
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DashboardData {
+    pub actor: DashboardActor,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DashboardActor {
+    pub entity: Option<Dashboard>,
+}
+
+#[derive(Default, Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Dashboard {
+    pub name: String,
+    #[serde(default)]
+    pub pages: Vec<Page>,
+}
+
+#[derive(Default, Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Page {
+    pub name: String,
+    #[serde(default)]
+    pub widgets: Vec<Widget>,
+}
+
+#[derive(Default, Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Widget {
+    pub title: Option<String>,
+    pub raw_configuration: Option<Value>,
+}
+
+impl Widget {
+    // NRQL queries from the widget's raw configuration, whatever its visualization
+    pub fn queries(&self) -> Vec<String> {
+        self.raw_configuration
+            .as_ref()
+            .and_then(|config| config.get("nrqlQueries"))
+            .and_then(|queries| queries.as_array())
+            .map(|queries| {
+                queries
+                    .iter()
+                    .filter_map(|query| query.get("query")?.as_str())
+                    .map(|query| query.to_owned())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DashboardCreateData {
+    pub dashboard_create: DashboardResult,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DashboardUpdateData {
+    pub dashboard_update: DashboardResult,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DashboardResult {
+    pub entity_result: Option<DashboardGuid>,
+    pub errors: Option<Vec<DashboardError>>,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DashboardGuid {
+    pub guid: String,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DashboardError {
+    pub description: String,
+}
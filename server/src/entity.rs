@@ -0,0 +1,42 @@
+use serde::Deserialize;
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntityData {
+    pub actor: EntityActor,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntityActor {
+    pub entity_search: EntitySearch,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntitySearch {
+    pub results: EntityResults,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntityResults {
+    pub entities: Vec<Entity>,
+}
+
+#[derive(Default, Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Entity {
+    pub guid: String,
+    pub name: String,
+    pub apm_summary: Option<ApmSummary>,
+}
+
+#[derive(Default, Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ApmSummary {
+    pub throughput: Option<f64>,
+    pub error_rate: Option<f64>,
+    pub apdex_score: Option<f64>,
+    pub response_time_average: Option<f64>,
+}
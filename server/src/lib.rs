@@ -8,20 +8,51 @@
     - Get Trace data for found traces ...
 */
 
-use anyhow::anyhow;
+use anyhow::{anyhow, Result};
 use reqwest::{
     header::{HeaderMap, HeaderValue},
-    Client, ClientBuilder, Method,
+    Client, ClientBuilder, Method, Response,
 };
 
+pub mod alert;
 pub mod application;
+pub mod dashboard;
+pub mod entity;
 pub mod newrelic;
+pub mod preflight;
+pub mod service_level;
 pub mod timeseries;
+pub mod trace;
+pub mod workload;
 use serde::de::DeserializeOwned;
+use serde_json::json;
 
-use newrelic::QueryResponse;
+use alert::{AckData, ConditionData, Issue, IssuesData};
+use dashboard::{
+    Dashboard, DashboardCreateData, DashboardData, DashboardResult, DashboardUpdateData,
+};
+use entity::{Entity, EntityData};
+use newrelic::{GraphQLResponse, NrqlError, QueryResponse};
+use preflight::{PreflightData, PreflightFailure};
+use service_level::{Indicator, ServiceLevelData};
+use workload::{Member, MemberData, Workload, WorkloadData};
 
-static QUERY_BASE: &str = r#"{ "query":  "{ actor { account(id: $account) { nrql(query: \"$query\") { results } } } }" }"#;
+static QUERY_BASE: &str =
+    "query($account: Int!, $nrql: Nrql!) { actor { account(id: $account) { nrql(query: $nrql) { results } } } }";
+static OPEN_ISSUES: &str = r#"{ "query": "{ actor { account(id: $account) { aiIssues { issues(filter: {states: [CREATED, ACTIVATED]}) { issues { issueId title priority state conditionName conditionFamilyId entityNames createdAt acknowledgedAt } } } } } }" }"#;
+static ACK_ISSUE: &str = r#"{ "query": "mutation { aiIssuesAckIssue(accountId: $account, issueId: \"$issue\") { error } }" }"#;
+static NRQL_CONDITION: &str = r#"{ "query": "{ actor { account(id: $account) { alerts { nrqlCondition(id: \"$condition\") { nrql { query } } } } } }" }"#;
+static DASHBOARD_SEARCH: &str = r#"{ "query": "{ actor { entitySearch(queryBuilder: {type: DASHBOARD}) { results { entities { guid name } } } } }" }"#;
+static DASHBOARD: &str = r#"{ "query": "{ actor { entity(guid: \"$guid\") { ... on DashboardEntity { name pages { name widgets { title rawConfiguration } } } } } }" }"#;
+static DASHBOARD_CREATE: &str = "mutation($account: Int!, $dashboard: DashboardInput!) { dashboardCreate(accountId: $account, dashboard: $dashboard) { entityResult { guid } errors { description } } }";
+static DASHBOARD_UPDATE: &str = "mutation($guid: EntityGuid!, $dashboard: DashboardInput!) { dashboardUpdate(guid: $guid, dashboard: $dashboard) { entityResult { guid } errors { description } } }";
+static PREFLIGHT: &str =
+    r#"{ "query": "{ actor { user { email } account(id: $account) { id name } } }" }"#;
+static ENTITY_SEARCH: &str = r#"{ "query": "{ actor { entitySearch(queryBuilder: {domain: APM, type: APPLICATION}) { results { entities { guid name ... on ApmApplicationEntityOutline { apmSummary { throughput errorRate apdexScore responseTimeAverage } } } } } } }" }"#;
+static APP_SEARCH: &str = r#"{ "query": "{ actor { entitySearch(queryBuilder: {domain: $domain, type: APPLICATION}) { results { entities { guid name } } } } }" }"#;
+static SERVICE_LEVELS: &str = r#"{ "query": "{ actor { entitySearch(queryBuilder: {type: SERVICE_LEVEL}) { results { entities { guid name serviceLevel { indicators { guid name objectives { target timeWindow { rolling { count unit } } } } } } } } } }" }"#;
+static WORKLOADS: &str = r#"{ "query": "{ actor { entitySearch(queryBuilder: {type: WORKLOAD}) { results { entities { guid name ... on WorkloadEntityOutline { workloadStatus { statusValue } } } } } } }" }"#;
+static WORKLOAD_MEMBERS: &str = r#"{ "query": "{ actor { entity(guid: \"$guid\") { relatedEntities(filter: {relationshipTypes: {include: CONTAINS}}) { results { target { entity { guid name domain type } } } } } } }" }"#;
 
 #[derive(Clone)]
 pub struct NewRelicClient {
@@ -81,37 +112,316 @@ impl NewRelicClient {
         &self,
         query_str: impl AsRef<str>,
     ) -> Option<Vec<T>> {
+        self.try_query(query_str).await.ok()
+    }
+
+    pub async fn try_query<T: DeserializeOwned + std::fmt::Debug + Default>(
+        &self,
+        query_str: impl AsRef<str>,
+    ) -> Result<Vec<T>> {
+        self.try_query_in(None, query_str).await
+    }
+
+    // Runs the query against another account, or the client's own when None
+    pub async fn try_query_in<T: DeserializeOwned + std::fmt::Debug + Default>(
+        &self,
+        account: Option<i64>,
+        query_str: impl AsRef<str>,
+    ) -> Result<Vec<T>> {
         // dbg!(&query_str);
+        let account = account
+            .or(self.account)
+            .ok_or(anyhow!("ERROR: No account number linked to client!"))?;
 
+        let json = self
+            .post(query_body(account, query_str.as_ref()).to_string())
+            .await?
+            .json::<QueryResponse<T>>()
+            .await?;
+
+        // dbg!(&json);
+        if let Some(error) = json.errors.and_then(|errors| errors.into_iter().next()) {
+            return Err(NrqlError {
+                message: error.message,
+            }
+            .into());
+        }
+
+        json.data
+            .and_then(|data| data.actor.account.nrql)
+            .map(|nrql| nrql.results)
+            .ok_or(anyhow!("ERROR: No results in response!"))
+    }
+
+    // Checks the endpoint is reachable, the key is accepted and the account is visible to it
+    pub async fn preflight(&self) -> std::result::Result<(), PreflightFailure> {
+        let account = self.account_id();
         let response = self
-            .client
-            .clone()?
-            .request(Method::POST, self.url.clone()?)
-            .body(
-                QUERY_BASE
-                    .replace(
-                        "$account",
-                        &self
-                            .account
-                            .expect("ERROR: No account number linked to client!")
-                            .to_string(),
-                    )
-                    .replace("$query", query_str.as_ref()),
+            .post(PREFLIGHT.replace("$account", &account))
+            .await
+            .map_err(|e| PreflightFailure::Network(e.to_string()))?;
+
+        let status = response.status();
+        if status.as_u16() == 401 || status.as_u16() == 403 {
+            return Err(PreflightFailure::Auth(format!("HTTP {}", status)));
+        }
+        let json = response
+            .json::<GraphQLResponse<PreflightData>>()
+            .await
+            .map_err(|e| PreflightFailure::Network(format!("HTTP {}: {}", status, e)))?;
+
+        let error = json
+            .errors
+            .and_then(|errors| errors.into_iter().next())
+            .map(|error| error.message);
+        let actor = json.data.and_then(|data| data.actor);
+        match actor {
+            Some(actor) if actor.user.is_some() => match actor.account {
+                Some(_) => Ok(()),
+                None => Err(PreflightFailure::Account(error.unwrap_or(format!(
+                    "Account {} is not visible to this API key",
+                    account
+                )))),
+            },
+            _ => Err(PreflightFailure::Auth(
+                error.unwrap_or("The API key was not accepted".to_owned()),
+            )),
+        }
+    }
+
+    // APM applications visible to the API key, with their summary metrics
+    pub async fn entities(&self) -> Result<Vec<Entity>> {
+        let data = self.graphql::<EntityData>(ENTITY_SEARCH.to_owned()).await?;
+        Ok(data.actor.entity_search.results.entities)
+    }
+
+    // Applications in a domain other than APM, e.g. "BROWSER" or "MOBILE"
+    pub async fn apps(&self, domain: &str) -> Result<Vec<Entity>> {
+        let data = self
+            .graphql::<EntityData>(APP_SEARCH.replace("$domain", domain))
+            .await?;
+        Ok(data.actor.entity_search.results.entities)
+    }
+
+    // Every SLI defined in the account, as each service level entity lists them
+    pub async fn service_levels(&self) -> Result<Vec<Indicator>> {
+        let data = self
+            .graphql::<ServiceLevelData>(SERVICE_LEVELS.to_owned())
+            .await?;
+        Ok(data
+            .actor
+            .entity_search
+            .results
+            .entities
+            .into_iter()
+            .filter_map(|entity| entity.service_level)
+            .flat_map(|service_level| service_level.indicators)
+            .collect())
+    }
+
+    pub async fn workloads(&self) -> Result<Vec<Workload>> {
+        let data = self.graphql::<WorkloadData>(WORKLOADS.to_owned()).await?;
+        Ok(data.actor.entity_search.results.entities)
+    }
+
+    // Entities the workload contains
+    pub async fn workload_members(&self, guid: &str) -> Result<Vec<Member>> {
+        let data = self
+            .graphql::<MemberData>(WORKLOAD_MEMBERS.replace("$guid", guid))
+            .await?;
+        let entity = data
+            .actor
+            .entity
+            .ok_or(anyhow!("ERROR: No workload found for {}!", guid))?;
+        Ok(entity
+            .related_entities
+            .results
+            .into_iter()
+            .filter_map(|relationship| relationship.target.entity)
+            .collect())
+    }
+
+    pub async fn dashboards(&self) -> Result<Vec<Entity>> {
+        let data = self
+            .graphql::<EntityData>(DASHBOARD_SEARCH.to_owned())
+            .await?;
+        Ok(data.actor.entity_search.results.entities)
+    }
+
+    pub async fn dashboard(&self, guid: &str) -> Result<Dashboard> {
+        let data = self
+            .graphql::<DashboardData>(DASHBOARD.replace("$guid", guid))
+            .await?;
+        data.actor
+            .entity
+            .ok_or(anyhow!("ERROR: No dashboard found for {}!", guid))
+    }
+
+    // Creates a dashboard of line widgets, one per (title, query, account), or replaces an
+    // earlier export. Widgets without an account use the client's.
+    pub async fn export_dashboard(
+        &self,
+        guid: Option<&str>,
+        name: &str,
+        widgets: &[(String, String, Option<i64>)],
+    ) -> Result<String> {
+        let account = self
+            .account
+            .ok_or(anyhow!("ERROR: No account number linked to client!"))?;
+        let widgets = widgets
+            .iter()
+            .enumerate()
+            .map(|(i, (title, query, widget_account))| {
+                json!({
+                    "title": title,
+                    "layout": { "column": (i % 3) * 4 + 1, "row": (i / 3) * 3 + 1, "width": 4, "height": 3 },
+                    "visualization": { "id": "viz.line" },
+                    "rawConfiguration": { "nrqlQueries": [{ "accountIds": [widget_account.unwrap_or(account)], "query": query }] },
+                })
+            })
+            .collect::<Vec<_>>();
+        let dashboard = json!({
+            "name": name,
+            "permissions": "PUBLIC_READ_WRITE",
+            "pages": [{ "name": name, "widgets": widgets }],
+        });
+
+        let result: DashboardResult = match guid {
+            Some(guid) => {
+                let body = json!({
+                    "query": DASHBOARD_UPDATE,
+                    "variables": { "guid": guid, "dashboard": dashboard },
+                });
+                self.graphql::<DashboardUpdateData>(body.to_string())
+                    .await?
+                    .dashboard_update
+            }
+            None => {
+                let body = json!({
+                    "query": DASHBOARD_CREATE,
+                    "variables": { "account": account, "dashboard": dashboard },
+                });
+                self.graphql::<DashboardCreateData>(body.to_string())
+                    .await?
+                    .dashboard_create
+            }
+        };
+
+        if let Some(error) = result.errors.into_iter().flatten().next() {
+            return Err(NrqlError {
+                message: error.description,
+            }
+            .into());
+        }
+        result
+            .entity_result
+            .map(|entity| entity.guid)
+            .ok_or(anyhow!("ERROR: No dashboard returned!"))
+    }
+
+    // Open (created or activated) alert issues for the account
+    pub async fn issues(&self) -> Result<Vec<Issue>> {
+        let data = self
+            .graphql::<IssuesData>(OPEN_ISSUES.replace("$account", &self.account_id()))
+            .await?;
+        Ok(data.actor.account.ai_issues.issues.issues)
+    }
+
+    pub async fn acknowledge_issue(&self, issue_id: &str) -> Result<()> {
+        let data = self
+            .graphql::<AckData>(
+                ACK_ISSUE
+                    .replace("$account", &self.account_id())
+                    .replace("$issue", issue_id),
+            )
+            .await?;
+        match data.ai_issues_ack_issue.error {
+            Some(message) => Err(NrqlError { message }.into()),
+            None => Ok(()),
+        }
+    }
+
+    // The NRQL behind an alert condition
+    pub async fn condition_query(&self, condition_id: i64) -> Result<String> {
+        let data = self
+            .graphql::<ConditionData>(
+                NRQL_CONDITION
+                    .replace("$account", &self.account_id())
+                    .replace("$condition", &condition_id.to_string()),
             )
-            .send()
-            .await;
-
-        if let Ok(data) = response {
-            let json = data
-                .json::<QueryResponse<T>>()
-                .await
-                .map_err(|e| anyhow!(e))
-                .expect("ERROR: Error in response deserialization schema");
-
-            // dbg!(&json);
-            return Some(json.data.actor.account.nrql.results);
+            .await?;
+        data.actor
+            .account
+            .alerts
+            .nrql_condition
+            .map(|condition| condition.nrql.query)
+            .ok_or(anyhow!("ERROR: No NRQL condition found!"))
+    }
+
+    // Any GraphQL query, answered with the whole response so errors can be read alongside data
+    pub async fn raw_graphql(&self, query: &str) -> Result<serde_json::Value> {
+        let body = json!({ "query": query });
+        Ok(self
+            .post(body.to_string())
+            .await?
+            .json::<serde_json::Value>()
+            .await?)
+    }
+
+    async fn graphql<D: DeserializeOwned>(&self, body: String) -> Result<D> {
+        let json = self.post(body).await?.json::<GraphQLResponse<D>>().await?;
+
+        if let Some(error) = json.errors.and_then(|errors| errors.into_iter().next()) {
+            return Err(NrqlError {
+                message: error.message,
+            }
+            .into());
         }
 
-        None
+        json.data.ok_or(anyhow!("ERROR: No data in response!"))
+    }
+
+    pub fn account_id(&self) -> String {
+        self.account
+            .expect("ERROR: No account number linked to client!")
+            .to_string()
+    }
+
+    async fn post(&self, body: String) -> Result<Response> {
+        let client = self
+            .client
+            .clone()
+            .ok_or(anyhow!("ERROR: HTTP client must be built first!"))?;
+        let url = self
+            .url
+            .clone()
+            .ok_or(anyhow!("ERROR: No URL linked to client!"))?;
+
+        Ok(client.request(Method::POST, url).body(body).send().await?)
+    }
+}
+
+// The NRQL travels as a variable, so quotes and backslashes in it reach New Relic untouched
+fn query_body(account: i64, nrql: &str) -> serde_json::Value {
+    json!({
+        "query": QUERY_BASE,
+        "variables": { "account": account, "nrql": nrql },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nrql_is_sent_as_a_variable() {
+        let nrql = r#"SELECT count(*) FROM Transaction WHERE name = 'a\'b "c"' SINCE 1 hour ago"#;
+        let body = query_body(42, nrql).to_string();
+        let parsed: serde_json::Value = serde_json::from_str(&body).unwrap();
+
+        assert_eq!(parsed["query"], QUERY_BASE);
+        assert_eq!(parsed["variables"]["account"], 42);
+        assert_eq!(parsed["variables"]["nrql"], nrql);
+        assert!(!parsed["query"].as_str().unwrap().contains("Transaction"));
     }
 }
@@ -1,9 +1,25 @@
 use serde::Deserialize;
+use std::fmt;
 
 #[derive(Default, Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct QueryResponse<T> {
-    pub data: Data<T>,
+    pub data: Option<Data<T>>,
+    pub errors: Option<Vec<GraphQLError>>,
+}
+
+// Envelope for NerdGraph requests outside of NRQL queries
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphQLResponse<D> {
+    pub data: Option<D>,
+    pub errors: Option<Vec<GraphQLError>>,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphQLError {
+    pub message: String,
 }
 
 #[derive(Default, Debug, Deserialize)]
@@ -21,7 +37,7 @@ pub struct Actor<T> {
 #[derive(Default, Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Account<T> {
-    pub nrql: Nrql<T>,
+    pub nrql: Option<Nrql<T>>,
 }
 
 #[derive(Default, Debug, Deserialize)]
@@ -29,3 +45,31 @@ pub struct Account<T> {
 pub struct Nrql<T> {
     pub results: Vec<T>,
 }
+
+#[derive(Debug)]
+pub struct NrqlError {
+    pub message: String,
+}
+
+impl NrqlError {
+    pub fn is_result_too_large(&self) -> bool {
+        let message = self.message.to_lowercase();
+        [
+            "too many",
+            "too large",
+            "too much data",
+            "maximum number",
+            "exceeds the maximum",
+        ]
+        .iter()
+        .any(|pattern| message.contains(pattern))
+    }
+}
+
+impl fmt::Display for NrqlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "NRQL Error! : {}", self.message)
+    }
+}
+
+impl std::error::Error for NrqlError {}
@@ -0,0 +1,46 @@
+use serde::Deserialize;
+use std::fmt;
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreflightData {
+    pub actor: Option<PreflightActor>,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreflightActor {
+    pub user: Option<User>,
+    pub account: Option<Account>,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct User {
+    pub email: Option<String>,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Account {
+    pub id: i64,
+    pub name: Option<String>,
+}
+
+// The first startup check to fail; later checks depend on it so go unchecked
+#[derive(Debug, Clone)]
+pub enum PreflightFailure {
+    Network(String),
+    Auth(String),
+    Account(String),
+}
+
+impl fmt::Display for PreflightFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PreflightFailure::Network(message) => write!(f, "Network: {}", message),
+            PreflightFailure::Auth(message) => write!(f, "Authentication: {}", message),
+            PreflightFailure::Account(message) => write!(f, "Account access: {}", message),
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use serde::Deserialize;
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceLevelData {
+    pub actor: ServiceLevelActor,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceLevelActor {
+    pub entity_search: ServiceLevelSearch,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceLevelSearch {
+    pub results: ServiceLevelResults,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceLevelResults {
+    pub entities: Vec<ServiceLevelEntity>,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceLevelEntity {
+    pub guid: String,
+    pub name: String,
+    pub service_level: Option<ServiceLevel>,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceLevel {
+    #[serde(default)]
+    pub indicators: Vec<Indicator>,
+}
+
+#[derive(Default, Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Indicator {
+    // The SLI's own entity, which its newrelic.sli.* metrics are reported against
+    pub guid: String,
+    pub name: String,
+    #[serde(default)]
+    pub objectives: Vec<Objective>,
+}
+
+#[derive(Default, Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Objective {
+    // Percentage of good events, e.g. 99.5
+    pub target: f64,
+    pub time_window: TimeWindow,
+}
+
+#[derive(Default, Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeWindow {
+    pub rolling: Option<Rolling>,
+}
+
+#[derive(Default, Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Rolling {
+    pub count: i64,
+    // Only DAY is offered today
+    pub unit: String,
+}
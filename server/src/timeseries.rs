@@ -1,29 +1,85 @@
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+use std::collections::BTreeMap;
 
-#[derive(Default, Debug, Deserialize, PartialEq, PartialOrd)]
+#[derive(Default, Debug, Clone, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct TimeseriesResult {
+    // Absent from the single row of a query without TIMESERIES
+    #[serde(default)]
     pub begin_time_seconds: f64,
+    #[serde(default)]
     pub end_time_seconds: f64,
-    pub facet: Option<String>,
-    pub value: f64,
+    // One value per FACET attribute, in the order they were listed
+    #[serde(default, deserialize_with = "facet_values")]
+    pub facet: Vec<String>,
+    pub comparison: Option<String>,
+    // The selected columns as NerdGraph names them, e.g. "value" or "average.duration"
+    #[serde(flatten)]
+    pub columns: BTreeMap<String, Value>,
+}
+
+impl TimeseriesResult {
+    // Numeric columns, with nested ones such as percentiles keyed like "percentile.duration.95"
+    pub fn values(&self) -> Vec<(String, f64)> {
+        let mut values = vec![];
+        for (name, value) in &self.columns {
+            flatten(name, value, &mut values);
+        }
+        values
+    }
+
+    // Array columns, e.g. the bucket counts under "histogram.duration"
+    pub fn histograms(&self) -> Vec<(String, Vec<f64>)> {
+        self.columns
+            .iter()
+            .filter_map(|(name, value)| match value {
+                Value::Array(buckets) => Some((
+                    name.to_owned(),
+                    buckets.iter().filter_map(Value::as_f64).collect(),
+                )),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+// A single facet comes back as a value, several as an array of them
+fn facet_values<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    let label = |value: &Value| match value {
+        Value::String(value) => value.to_owned(),
+        value => value.to_string(),
+    };
+    Ok(match Option::<Value>::deserialize(deserializer)? {
+        None | Some(Value::Null) => vec![],
+        Some(Value::Array(values)) => values.iter().map(label).collect(),
+        Some(value) => vec![label(&value)],
+    })
+}
+
+fn flatten(name: &str, value: &Value, values: &mut Vec<(String, f64)>) {
+    match value {
+        Value::Number(number) => {
+            if let Some(number) = number.as_f64() {
+                values.push((name.to_owned(), number));
+            }
+        }
+        Value::Object(fields) => {
+            for (key, value) in fields {
+                flatten(&format!("{}.{}", name, key), value, values);
+            }
+        }
+        _ => {}
+    }
 }
 
 #[derive(Debug)]
 pub struct Timeseries {
     pub begin_time_seconds: f64,
     pub end_time_seconds: f64,
-    pub facet: Option<String>,
-    pub value: f64,
-}
-
-impl Timeseries {
-    pub fn plot(&self) -> ((f64, f64), (f64, f64)) {
-        (
-            (self.begin_time_seconds, self.value),
-            (self.end_time_seconds, self.value),
-        )
-    }
+    pub facet: Vec<String>,
+    pub values: Vec<(String, f64)>,
+    pub comparison: Option<String>,
 }
 
 impl From<TimeseriesResult> for Timeseries {
@@ -31,8 +87,78 @@ impl From<TimeseriesResult> for Timeseries {
         Timeseries {
             begin_time_seconds: val.begin_time_seconds,
             end_time_seconds: val.end_time_seconds,
-            facet: val.facet.clone(),
-            value: val.value,
+            values: val.values(),
+            facet: val.facet,
+            comparison: val.comparison,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn result(value: Value) -> TimeseriesResult {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn facets_are_read_from_a_value_an_array_or_null() {
+        let facet = |facet: Value| result(json!({ "facet": facet, "count": 1 })).facet;
+        assert_eq!(facet(json!("api")), ["api"]);
+        assert_eq!(facet(json!(404)), ["404"]);
+        assert_eq!(facet(json!(["api", 200])), ["api", "200"]);
+        assert!(facet(Value::Null).is_empty());
+        assert!(result(json!({ "count": 1 })).facet.is_empty());
+    }
+
+    #[test]
+    fn times_and_comparison_are_not_columns() {
+        let result = result(json!({
+            "beginTimeSeconds": 60,
+            "endTimeSeconds": 120,
+            "comparison": "previous",
+            "count": 3,
+        }));
+        assert_eq!(result.begin_time_seconds, 60.0);
+        assert_eq!(result.end_time_seconds, 120.0);
+        assert_eq!(result.comparison.as_deref(), Some("previous"));
+        assert_eq!(result.values(), [("count".to_owned(), 3.0)]);
+    }
+
+    #[test]
+    fn nested_columns_are_flattened() {
+        let result = result(json!({
+            "average.duration": 0.5,
+            "percentile.duration": { "95": 1.5, "99": 2.5 },
+            "latest.host": "web-1",
+        }));
+        assert_eq!(
+            result.values(),
+            [
+                ("average.duration".to_owned(), 0.5),
+                ("percentile.duration.95".to_owned(), 1.5),
+                ("percentile.duration.99".to_owned(), 2.5),
+            ]
+        );
+        assert!(result.histograms().is_empty());
+    }
+
+    #[test]
+    fn histograms_are_the_array_columns() {
+        let result = result(json!({
+            "histogram.duration": [1, 4, 2],
+            "count": 7,
+        }));
+        assert_eq!(
+            result.histograms(),
+            [("histogram.duration".to_owned(), vec![1.0, 4.0, 2.0])]
+        );
+        assert_eq!(result.values(), [("count".to_owned(), 7.0)]);
+
+        let timeseries = Timeseries::from(result);
+        assert_eq!(timeseries.values, [("count".to_owned(), 7.0)]);
+    }
+}
@@ -0,0 +1,41 @@
+use serde::Deserialize;
+
+#[derive(Default, Debug, Deserialize, Clone)]
+pub struct TraceSummary {
+    #[serde(rename = "trace.id")]
+    pub trace_id: String,
+    #[serde(rename = "root.entity.name")]
+    pub root_entity_name: Option<String>,
+    #[serde(rename = "root.span.name")]
+    pub root_span_name: Option<String>,
+    #[serde(rename = "duration.ms")]
+    pub duration_ms: Option<f64>,
+    #[serde(rename = "errorCount")]
+    pub error_count: Option<u64>,
+    #[serde(rename = "spanCount")]
+    pub span_count: Option<u64>,
+    pub timestamp: f64,
+}
+
+#[derive(Default, Debug, Deserialize, Clone)]
+pub struct Span {
+    pub id: String,
+    #[serde(rename = "parent.id")]
+    pub parent_id: Option<String>,
+    pub name: Option<String>,
+    #[serde(rename = "service.name")]
+    pub service_name: Option<String>,
+    #[serde(rename = "duration.ms")]
+    pub duration_ms: Option<f64>,
+    // Epoch milliseconds
+    pub timestamp: f64,
+    pub error: Option<bool>,
+    #[serde(rename = "error.message")]
+    pub error_message: Option<String>,
+}
+
+impl Span {
+    pub fn is_error(&self) -> bool {
+        self.error.unwrap_or(false) || self.error_message.is_some()
+    }
+}
@@ -0,0 +1,86 @@
+use serde::Deserialize;
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkloadData {
+    pub actor: WorkloadActor,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkloadActor {
+    pub entity_search: WorkloadSearch,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkloadSearch {
+    pub results: WorkloadResults,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkloadResults {
+    pub entities: Vec<Workload>,
+}
+
+#[derive(Default, Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Workload {
+    pub guid: String,
+    pub name: String,
+    pub workload_status: Option<WorkloadStatus>,
+}
+
+#[derive(Default, Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkloadStatus {
+    // OPERATIONAL, DEGRADED, DISRUPTED or UNKNOWN
+    pub status_value: String,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemberData {
+    pub actor: MemberActor,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemberActor {
+    pub entity: Option<MemberEntity>,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemberEntity {
+    pub related_entities: MemberResults,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemberResults {
+    pub results: Vec<MemberRelationship>,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemberRelationship {
+    pub target: MemberTarget,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemberTarget {
+    pub entity: Option<Member>,
+}
+
+#[derive(Default, Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Member {
+    pub guid: String,
+    pub name: String,
+    pub domain: String,
+    #[serde(rename = "type")]
+    pub entity_type: String,
+}
@@ -0,0 +1,374 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::app::{Focus, InputMode};
+
+// Everything a key press can ask of the app, so state changes can be driven and tested
+// without a terminal. Applied by App::handle_action.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Quit,
+    // Ctrl-Z, stops the process until resumed from the shell
+    Suspend,
+    EditQuery,
+    // The selected query into the query box, to submit as a new query once changed
+    Duplicate,
+    SelectNext,
+    SelectPrevious,
+    // A digit typed ahead of a motion, repeating it, as in vim
+    Count(u32),
+    // g, which selects the first query when typed twice
+    Go,
+    Bottom,
+    HalfPageDown,
+    HalfPageUp,
+    ShiftDown,
+    ShiftUp,
+    TogglePin,
+    Delete,
+    RenameQuery,
+    EditNote,
+    AddLink,
+    OpenLinks,
+    OpenQueryBuilder,
+    EditTimeRange,
+    EditVariables,
+    Import,
+    Export,
+    NextLayout,
+    Help,
+    Diagnostics,
+    Palette,
+    NextTab,
+    PreviousTab,
+    NewTab,
+    RenameTab,
+    CloseTab,
+    MoveToNextTab,
+    Alerts,
+    Entities,
+    Browser,
+    Mobile,
+    Console,
+    Hosts,
+    Kubernetes,
+    ServiceLevels,
+    Workloads,
+    Templates,
+    Traces,
+    ToggleDashboard,
+    // Grows the selected window leftwards and pans it, in steps, see App::scrub_chart
+    Scrub { grow: f64, pan: f64 },
+    ClearScrub,
+    ToggleExpanded,
+    ZoomIn,
+    ZoomOut,
+    SaveSession,
+    ToggleColourBlind,
+    NextFacet,
+    ToggleSnapshot,
+    NextChartStyle,
+    NextTimezone,
+    EditAxis,
+    EditThresholds,
+    // Text inputs
+    Submit,
+    Cancel,
+    InsertChar(char),
+    DeleteChar,
+    CursorLeft,
+    CursorRight,
+    OpenDatePicker,
+}
+
+// Names for the keybindings table of the config, e.g. traces = "t"
+pub const NAMED: &[(&str, Action)] = &[
+    ("quit", Action::Quit),
+    ("suspend", Action::Suspend),
+    ("edit_query", Action::EditQuery),
+    ("duplicate_query", Action::Duplicate),
+    ("select_next", Action::SelectNext),
+    ("select_previous", Action::SelectPrevious),
+    ("bottom", Action::Bottom),
+    ("half_page_down", Action::HalfPageDown),
+    ("half_page_up", Action::HalfPageUp),
+    ("shift_down", Action::ShiftDown),
+    ("shift_up", Action::ShiftUp),
+    ("toggle_pin", Action::TogglePin),
+    ("delete", Action::Delete),
+    ("rename_query", Action::RenameQuery),
+    ("edit_note", Action::EditNote),
+    ("add_link", Action::AddLink),
+    ("open_links", Action::OpenLinks),
+    ("open_query_builder", Action::OpenQueryBuilder),
+    ("edit_time_range", Action::EditTimeRange),
+    ("edit_variables", Action::EditVariables),
+    ("import", Action::Import),
+    ("export", Action::Export),
+    ("next_layout", Action::NextLayout),
+    ("help", Action::Help),
+    ("diagnostics", Action::Diagnostics),
+    ("palette", Action::Palette),
+    ("next_tab", Action::NextTab),
+    ("previous_tab", Action::PreviousTab),
+    ("new_tab", Action::NewTab),
+    ("rename_tab", Action::RenameTab),
+    ("close_tab", Action::CloseTab),
+    ("move_to_next_tab", Action::MoveToNextTab),
+    ("alerts", Action::Alerts),
+    ("entities", Action::Entities),
+    ("browser", Action::Browser),
+    ("mobile", Action::Mobile),
+    ("console", Action::Console),
+    ("hosts", Action::Hosts),
+    ("kubernetes", Action::Kubernetes),
+    ("service_levels", Action::ServiceLevels),
+    ("workloads", Action::Workloads),
+    ("templates", Action::Templates),
+    ("traces", Action::Traces),
+    ("toggle_dashboard", Action::ToggleDashboard),
+    ("save_session", Action::SaveSession),
+    ("toggle_colour_blind", Action::ToggleColourBlind),
+    ("next_facet", Action::NextFacet),
+    ("toggle_snapshot", Action::ToggleSnapshot),
+    ("next_chart_style", Action::NextChartStyle),
+    ("next_timezone", Action::NextTimezone),
+    ("edit_axis", Action::EditAxis),
+    ("edit_thresholds", Action::EditThresholds),
+];
+
+pub fn named(name: &str) -> Option<Action> {
+    NAMED
+        .iter()
+        .find(|(action, _)| *action == name)
+        .map(|(_, action)| *action)
+}
+
+// Maps a key pressed in Normal or Input mode, the overlays and list views handle their own keys
+pub fn from_key(key: KeyEvent, mode: &InputMode, focus: Focus, scrubbing: bool) -> Option<Action> {
+    let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    // Raw mode delivers Ctrl-Z as a key rather than the shell's SIGTSTP, whatever the mode
+    if key.code == KeyCode::Char('z') && key.modifiers.contains(KeyModifiers::CONTROL) {
+        return Some(Action::Suspend);
+    }
+    let action = match mode {
+        InputMode::Normal => match key.code {
+            KeyCode::Char('q') => Action::Quit,
+            KeyCode::Char('e') => Action::EditQuery,
+            KeyCode::Char('D') => Action::Duplicate,
+            KeyCode::Char('j') => Action::SelectNext,
+            KeyCode::Char('k') => Action::SelectPrevious,
+            KeyCode::Char('d') if ctrl => Action::HalfPageDown,
+            KeyCode::Char('u') if ctrl => Action::HalfPageUp,
+            KeyCode::Char('g') => Action::Go,
+            KeyCode::Char('G') => Action::Bottom,
+            KeyCode::Char(c) if c.is_ascii_digit() => Action::Count(c.to_digit(10).unwrap_or(0)),
+            KeyCode::Char('J') => Action::ShiftDown,
+            KeyCode::Char('K') => Action::ShiftUp,
+            KeyCode::Char('p') => Action::TogglePin,
+            KeyCode::Char('x') => Action::Delete,
+            KeyCode::Char('r') => Action::RenameQuery,
+            KeyCode::Char('n') => Action::EditNote,
+            KeyCode::Char('u') => Action::AddLink,
+            KeyCode::Char('b') => Action::OpenLinks,
+            KeyCode::Char('o') => Action::OpenQueryBuilder,
+            KeyCode::Char('t') => Action::EditTimeRange,
+            KeyCode::Char('V') => Action::EditVariables,
+            KeyCode::Char('I') => Action::Import,
+            KeyCode::Char('X') => Action::Export,
+            KeyCode::Char('L') => Action::NextLayout,
+            KeyCode::Char('?') => Action::Help,
+            KeyCode::F(12) => Action::Diagnostics,
+            KeyCode::Char(':') => Action::Palette,
+            KeyCode::Tab => Action::NextTab,
+            KeyCode::BackTab => Action::PreviousTab,
+            KeyCode::Char('N') => Action::NewTab,
+            KeyCode::Char('R') => Action::RenameTab,
+            KeyCode::Char('W') => Action::CloseTab,
+            KeyCode::Char('M') => Action::MoveToNextTab,
+            KeyCode::Char('A') => Action::Alerts,
+            KeyCode::Char('E') => Action::Entities,
+            KeyCode::Char('B') => Action::Browser,
+            KeyCode::Char('O') => Action::Mobile,
+            KeyCode::Char('C') => Action::Console,
+            KeyCode::Char('H') => Action::Hosts,
+            KeyCode::Char('P') => Action::Kubernetes,
+            KeyCode::Char('S') => Action::ServiceLevels,
+            KeyCode::Char('U') => Action::Workloads,
+            KeyCode::Char('Y') => Action::Templates,
+            KeyCode::Char('T') => Action::Traces,
+            KeyCode::Char('d') => Action::ToggleDashboard,
+            KeyCode::Char('c') => Action::NextChartStyle,
+            KeyCode::Char('f') => Action::NextFacet,
+            KeyCode::Char('s') => Action::ToggleSnapshot,
+            KeyCode::Char('y') => Action::EditAxis,
+            KeyCode::Char('a') => Action::EditThresholds,
+            KeyCode::Char('Z') => Action::NextTimezone,
+            KeyCode::Left if shift => Action::Scrub {
+                grow: 1.0,
+                pan: 0.0,
+            },
+            KeyCode::Right if shift => Action::Scrub {
+                grow: -1.0,
+                pan: 0.0,
+            },
+            KeyCode::Left if scrubbing => Action::Scrub {
+                grow: 0.0,
+                pan: -1.0,
+            },
+            KeyCode::Right if scrubbing => Action::Scrub {
+                grow: 0.0,
+                pan: 1.0,
+            },
+            KeyCode::Enter if focus == Focus::Dashboard && !scrubbing => Action::ToggleExpanded,
+            KeyCode::Enter => Action::ZoomIn,
+            KeyCode::Backspace => Action::ZoomOut,
+            KeyCode::Esc => Action::ClearScrub,
+            _ => return None,
+        },
+        InputMode::Input => match key.code {
+            KeyCode::Enter => Action::Submit,
+            KeyCode::F(12) => Action::Diagnostics,
+            KeyCode::Char('t') if focus == Focus::QueryInput && ctrl => Action::OpenDatePicker,
+            KeyCode::Char(c) => Action::InsertChar(c),
+            KeyCode::Backspace => Action::DeleteChar,
+            KeyCode::Left => Action::CursorLeft,
+            KeyCode::Right => Action::CursorRight,
+            KeyCode::Esc => Action::Cancel,
+            _ => return None,
+        },
+    };
+    Some(action)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn normal_keys_map_to_actions() {
+        let action = |code| {
+            from_key(
+                key(code, KeyModifiers::NONE),
+                &InputMode::Normal,
+                Focus::Default,
+                false,
+            )
+        };
+        assert_eq!(action(KeyCode::Char('q')), Some(Action::Quit));
+        assert_eq!(action(KeyCode::Char('j')), Some(Action::SelectNext));
+        assert_eq!(action(KeyCode::Tab), Some(Action::NextTab));
+        assert_eq!(action(KeyCode::Char('z')), None);
+        let ctrl_z = key(KeyCode::Char('z'), KeyModifiers::CONTROL);
+        for mode in [InputMode::Normal, InputMode::Input] {
+            assert_eq!(
+                from_key(ctrl_z, &mode, Focus::QueryInput, false),
+                Some(Action::Suspend)
+            );
+        }
+    }
+
+    #[test]
+    fn vim_motions_map_to_actions() {
+        let action = |code, modifiers| {
+            from_key(
+                key(code, modifiers),
+                &InputMode::Normal,
+                Focus::Default,
+                false,
+            )
+        };
+        let none = KeyModifiers::NONE;
+        assert_eq!(action(KeyCode::Char('7'), none), Some(Action::Count(7)));
+        assert_eq!(action(KeyCode::Char('g'), none), Some(Action::Go));
+        assert_eq!(action(KeyCode::Char('G'), none), Some(Action::Bottom));
+        assert_eq!(action(KeyCode::Char('U'), none), Some(Action::Workloads));
+        let ctrl = KeyModifiers::CONTROL;
+        assert_eq!(action(KeyCode::Char('d'), ctrl), Some(Action::HalfPageDown));
+        assert_eq!(action(KeyCode::Char('u'), ctrl), Some(Action::HalfPageUp));
+        assert_eq!(
+            action(KeyCode::Char('d'), none),
+            Some(Action::ToggleDashboard)
+        );
+    }
+
+    #[test]
+    fn enter_depends_on_focus_and_scrub() {
+        let enter = key(KeyCode::Enter, KeyModifiers::NONE);
+        let normal = &InputMode::Normal;
+        assert_eq!(
+            from_key(enter, normal, Focus::Dashboard, false),
+            Some(Action::ToggleExpanded)
+        );
+        assert_eq!(
+            from_key(enter, normal, Focus::Dashboard, true),
+            Some(Action::ZoomIn)
+        );
+        assert_eq!(
+            from_key(enter, normal, Focus::Default, false),
+            Some(Action::ZoomIn)
+        );
+    }
+
+    #[test]
+    fn arrows_only_pan_while_scrubbing() {
+        let right = key(KeyCode::Right, KeyModifiers::NONE);
+        let normal = &InputMode::Normal;
+        assert_eq!(from_key(right, normal, Focus::Default, false), None);
+        assert_eq!(
+            from_key(right, normal, Focus::Default, true),
+            Some(Action::Scrub {
+                grow: 0.0,
+                pan: 1.0
+            })
+        );
+        assert_eq!(
+            from_key(
+                key(KeyCode::Left, KeyModifiers::SHIFT),
+                normal,
+                Focus::Default,
+                false
+            ),
+            Some(Action::Scrub {
+                grow: 1.0,
+                pan: 0.0
+            })
+        );
+    }
+
+    #[test]
+    fn input_mode_types_characters() {
+        let input = &InputMode::Input;
+        assert_eq!(
+            from_key(
+                key(KeyCode::Char('q'), KeyModifiers::NONE),
+                input,
+                Focus::QueryInput,
+                false
+            ),
+            Some(Action::InsertChar('q'))
+        );
+        assert_eq!(
+            from_key(
+                key(KeyCode::Char('t'), KeyModifiers::CONTROL),
+                input,
+                Focus::QueryInput,
+                false
+            ),
+            Some(Action::OpenDatePicker)
+        );
+        assert_eq!(
+            from_key(
+                key(KeyCode::Char('t'), KeyModifiers::CONTROL),
+                input,
+                Focus::Rename,
+                false
+            ),
+            Some(Action::InsertChar('t'))
+        );
+    }
+}
@@ -1,16 +1,43 @@
 use crate::{
-    backend::{Backend as AppBackend, Bounds},
-    query::{NRQLQuery, NRQL},
+    action::{self, Action},
+    axis::{Unit, YAxis},
+    backend::{
+        channel, AlertPayload, Backend as AppBackend, CrashGroup, DataSource, Host, ImportPayload,
+        KubeRow, Payload, Slo, TracePayload, UIEvent, Wakeup, WorkloadPayload, FILE_PREFIX,
+    },
+    config::{Config, ConfigWatch, QuitBehaviour, View},
+    dataset::{Dataset, Datasets, Snapshot},
+    format::{NumberFormat, Timezone},
+    i18n::{Catalog, Msg},
+    query::{
+        nrql_string, parse_variables, reorder_nrql, set_clause, variables_spec, NRQLQuery,
+        TimeRange, Variables, NRQL,
+    },
+    script::Script,
+    session::{upgrade_query, Session, SessionQuery},
+    template::{QueryTemplate, RecentValues},
+    threshold::Threshold,
     ui::{
-        render_dashboard, render_graph, render_load_session, render_loading, render_query_box,
-        render_query_list, render_rename_dialog,
+        anomaly_lines, render_alerts, render_anomalies, render_console, render_crashes,
+        render_dashboard, render_date_picker, render_details, render_diagnostics, render_entities,
+        render_error, render_export_dialog, render_graph, render_help, render_hosts, render_import,
+        render_kube, render_load_session, render_loading, render_note_dialog, render_palette,
+        render_preflight, render_query_box, render_query_list, render_rename_dialog,
+        render_save_session, render_slos, render_status_bar, render_tabs, render_templates,
+        render_time_range_dialog, render_too_small, render_traces, render_workloads,
     },
 };
 
-use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Datelike, Duration as Delta, FixedOffset, Months, Timelike, Utc};
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    ExecutableCommand,
+};
 use ratatui::{
     backend::Backend,
-    layout::{Constraint, Layout},
+    layout::{Constraint, Layout, Rect},
     style::{
         palette::tailwind::{self, Palette},
         Color,
@@ -18,13 +45,26 @@ use ratatui::{
     widgets::ListState,
     Frame, Terminal,
 };
+use server::{
+    alert::Issue,
+    dashboard::Dashboard,
+    entity::Entity,
+    preflight::PreflightFailure,
+    trace::{Span, TraceSummary},
+    workload::Workload,
+};
 use std::{
-    collections::{btree_map::Entry, BTreeMap},
-    fs::File,
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    fs::{self, File},
     io::Write,
-    time::Duration,
+    path::PathBuf,
+    process::{Command, Stdio},
+    sync::mpsc::Receiver,
+    thread,
+    time::{Duration, Instant},
 };
 use tokio::io;
+use tracing::{error, warn};
 
 pub const QUERY: isize = 0;
 pub const RENAME: isize = 1;
@@ -32,6 +72,29 @@ pub const SESSION_LOAD: isize = 2;
 pub const DEFAULT: isize = 3;
 pub const DASHBOARD: isize = 4;
 pub const LOADING: isize = 5;
+pub const SESSION_SAVE: isize = 6;
+pub const NOTE: isize = 7;
+pub const LINK: isize = 8;
+pub const TIME_RANGE: isize = 9;
+pub const DATE_PICKER: isize = 10;
+pub const TRACES: isize = 11;
+pub const ENTITIES: isize = 12;
+pub const ALERTS: isize = 13;
+pub const IMPORT: isize = 14;
+pub const EXPORT: isize = 15;
+pub const PALETTE: isize = 16;
+pub const TAB_NAME: isize = 17;
+pub const AXIS: isize = 18;
+pub const THRESHOLDS: isize = 19;
+pub const CONSOLE: isize = 20;
+pub const HOSTS: isize = 21;
+pub const KUBERNETES: isize = 22;
+pub const CRASHES: isize = 23;
+pub const SERVICE_LEVELS: isize = 24;
+pub const WORKLOADS: isize = 25;
+pub const TEMPLATES: isize = 26;
+pub const TEMPLATE_VARIABLE: isize = 27;
+pub const VARIABLES: isize = 28;
 
 #[derive(Clone, Copy, PartialEq)]
 pub enum Focus {
@@ -39,25 +102,384 @@ pub enum Focus {
     Rename = RENAME,
     Dashboard = DASHBOARD,
     SessionLoad = SESSION_LOAD,
+    SessionSave = SESSION_SAVE,
+    Note = NOTE,
+    Link = LINK,
+    TimeRange = TIME_RANGE,
+    DatePicker = DATE_PICKER,
+    Traces = TRACES,
+    Entities = ENTITIES,
+    Alerts = ALERTS,
+    Import = IMPORT,
+    Export = EXPORT,
+    Palette = PALETTE,
+    TabName = TAB_NAME,
+    Axis = AXIS,
+    Thresholds = THRESHOLDS,
+    Console = CONSOLE,
+    Hosts = HOSTS,
+    Kubernetes = KUBERNETES,
+    Crashes = CRASHES,
+    ServiceLevels = SERVICE_LEVELS,
+    Workloads = WORKLOADS,
+    Templates = TEMPLATES,
+    TemplateVariable = TEMPLATE_VARIABLE,
+    Variables = VARIABLES,
     Loading = LOADING,
     Default = DEFAULT,
 }
 
+// Smallest terminal the views can be laid out in
+pub const MIN_WIDTH: u16 = 40;
+pub const MIN_HEIGHT: u16 = 10;
+// Below this width the side panels are dropped and the graph takes the full width
+const COMPACT_WIDTH: u16 = 90;
+
+// Counts are capped so a long run of digits can't overflow
+const MAX_COUNT: usize = 9999;
+// Longest the UI loop sleeps without input or payloads, for countdowns, flashes and the idle timer
+const TICK: Duration = Duration::from_millis(250);
+// Countdowns, elapsed times and the time axis only move on by the second, so nothing else
+// needs drawing while idle
+const REDRAW_INTERVAL: Duration = Duration::from_secs(1);
+// Gap between dispatching queued session queries, so loading doesn't burst the API
+const LOAD_INTERVAL: Duration = Duration::from_millis(250);
+const RECENT_VALUES: &str = "template_values.yaml";
+// Session variables are kept beside the session, which is a plain map of queries
+const VARIABLES_FILE: &str = "variables.yaml";
+const QUERY_BUILDER: &str = "https://one.newrelic.com/data-exploration/query-builder";
+
+// Everything reachable from the command palette
+pub const COMMANDS: &[(Msg, Action)] = &[
+    (Msg::KeyEdit, Action::EditQuery),
+    (Msg::KeyDuplicate, Action::Duplicate),
+    (Msg::KeyRename, Action::RenameQuery),
+    (Msg::KeyNote, Action::EditNote),
+    (Msg::KeyLink, Action::AddLink),
+    (Msg::KeyOpenLinks, Action::OpenLinks),
+    (Msg::KeyQueryBuilder, Action::OpenQueryBuilder),
+    (Msg::KeyPin, Action::TogglePin),
+    (Msg::KeyDelete, Action::Delete),
+    (Msg::KeyTimeRange, Action::EditTimeRange),
+    (Msg::KeyVariables, Action::EditVariables),
+    (Msg::KeyDashboard, Action::ToggleDashboard),
+    (Msg::KeyLayout, Action::NextLayout),
+    (Msg::KeyTraces, Action::Traces),
+    (Msg::KeyEntities, Action::Entities),
+    (Msg::KeyBrowser, Action::Browser),
+    (Msg::KeyMobile, Action::Mobile),
+    (Msg::KeyConsole, Action::Console),
+    (Msg::KeyHosts, Action::Hosts),
+    (Msg::KeyKubernetes, Action::Kubernetes),
+    (Msg::KeyServiceLevels, Action::ServiceLevels),
+    (Msg::KeyWorkloads, Action::Workloads),
+    (Msg::KeyTemplates, Action::Templates),
+    (Msg::KeyAlerts, Action::Alerts),
+    (Msg::KeyImport, Action::Import),
+    (Msg::KeyExport, Action::Export),
+    (Msg::KeyNextTab, Action::NextTab),
+    (Msg::KeyNewTab, Action::NewTab),
+    (Msg::KeyRenameTab, Action::RenameTab),
+    (Msg::KeyMoveTab, Action::MoveToNextTab),
+    (Msg::KeyCloseTab, Action::CloseTab),
+    (Msg::KeyHelp, Action::Help),
+    (Msg::KeyDiagnostics, Action::Diagnostics),
+    (Msg::KeyQuit, Action::Quit),
+    (Msg::KeySuspend, Action::Suspend),
+    (Msg::KeySave, Action::SaveSession),
+    (Msg::KeyColourBlind, Action::ToggleColourBlind),
+    (Msg::KeyFacet, Action::NextFacet),
+    (Msg::KeySnapshot, Action::ToggleSnapshot),
+    (Msg::KeyChartStyle, Action::NextChartStyle),
+    (Msg::KeyAxis, Action::EditAxis),
+    (Msg::KeyThresholds, Action::EditThresholds),
+    (Msg::KeyTimezone, Action::NextTimezone),
+];
+
+// Keybindings by context, as listed in the help overlay
+pub const KEYMAP: &[(Msg, &[(&str, Msg)])] = &[
+    (
+        Msg::HelpGraph,
+        &[
+            ("e", Msg::KeyEdit),
+            ("D", Msg::KeyDuplicate),
+            ("j/k", Msg::KeySelect),
+            ("gg/G", Msg::KeyTopBottom),
+            ("Ctrl+D/U", Msg::KeyHalfPage),
+            ("5j", Msg::KeyCount),
+            ("J/K", Msg::KeyReorder),
+            ("p", Msg::KeyPin),
+            ("x", Msg::KeyDelete),
+            ("r", Msg::KeyRename),
+            ("n", Msg::KeyNote),
+            ("f", Msg::KeyFacet),
+            ("s", Msg::KeySnapshot),
+            ("u", Msg::KeyLink),
+            ("b", Msg::KeyOpenLinks),
+            ("o", Msg::KeyQueryBuilder),
+            ("t", Msg::KeyTimeRange),
+            ("V", Msg::KeyVariables),
+            ("d", Msg::KeyDashboard),
+            ("c", Msg::KeyChartStyle),
+            ("y", Msg::KeyAxis),
+            ("a", Msg::KeyThresholds),
+            ("Z", Msg::KeyTimezone),
+            ("L", Msg::KeyLayout),
+            ("Tab/Shift+Tab", Msg::KeyNextTab),
+            ("N", Msg::KeyNewTab),
+            ("R", Msg::KeyRenameTab),
+            ("M", Msg::KeyMoveTab),
+            ("W", Msg::KeyCloseTab),
+            ("T", Msg::KeyTraces),
+            ("E", Msg::KeyEntities),
+            ("B", Msg::KeyBrowser),
+            ("O", Msg::KeyMobile),
+            ("C", Msg::KeyConsole),
+            ("H", Msg::KeyHosts),
+            ("P", Msg::KeyKubernetes),
+            ("S", Msg::KeyServiceLevels),
+            ("U", Msg::KeyWorkloads),
+            ("Y", Msg::KeyTemplates),
+            ("A", Msg::KeyAlerts),
+            ("I", Msg::KeyImport),
+            ("X", Msg::KeyExport),
+            ("Shift+Left/Right", Msg::KeyScrubStart),
+            (":", Msg::KeyPalette),
+            ("?", Msg::KeyHelp),
+            ("F12", Msg::KeyDiagnostics),
+            ("Ctrl+Z", Msg::KeySuspend),
+            ("q", Msg::KeyQuit),
+        ],
+    ),
+    (
+        Msg::HelpZoom,
+        &[
+            ("Left/Right", Msg::KeyScrubMove),
+            ("Enter", Msg::KeyZoomIn),
+            ("Backspace", Msg::KeyZoomOut),
+            ("Esc", Msg::KeyClearScrub),
+        ],
+    ),
+    (
+        Msg::HelpDashboard,
+        &[
+            ("j/k", Msg::KeySelect),
+            ("Enter", Msg::KeyExpand),
+            ("L", Msg::KeyLayout),
+            ("d", Msg::KeyBack),
+        ],
+    ),
+    (
+        Msg::HelpInputs,
+        &[
+            ("Enter", Msg::KeyConfirm),
+            ("Esc", Msg::KeyCancel),
+            ("Left/Right", Msg::KeyCursor),
+            ("Backspace", Msg::KeyErase),
+            ("Ctrl+t", Msg::KeyDatePicker),
+        ],
+    ),
+    (
+        Msg::HelpDatePicker,
+        &[
+            ("h/l", Msg::KeyField),
+            ("j/k", Msg::KeyAdjust),
+            ("Tab", Msg::KeyBound),
+            ("Enter", Msg::KeyApply),
+            ("Esc", Msg::KeyCancel),
+        ],
+    ),
+    (
+        Msg::HelpViews,
+        &[
+            ("j/k", Msg::KeyMove),
+            ("gg/G", Msg::KeyTopBottom),
+            ("Ctrl+D/U", Msg::KeyHalfPage),
+            ("5j", Msg::KeyCount),
+            ("Enter", Msg::KeyOpen),
+            ("a", Msg::KeyAcknowledge),
+            ("s", Msg::KeySort),
+            ("Backspace", Msg::KeyParent),
+            ("r", Msg::KeyRefresh),
+            ("Esc/q", Msg::KeyBack),
+        ],
+    ),
+    (
+        Msg::HelpConsole,
+        &[
+            ("e", Msg::KeyConsoleEdit),
+            ("Enter", Msg::KeyConsoleRun),
+            ("j/k", Msg::KeyScroll),
+            ("Esc/q", Msg::KeyBack),
+        ],
+    ),
+];
+
 pub enum InputMode {
     Normal,
     Input,
 }
 
+// A count and g typed ahead of a motion, kept for the next key only
+#[derive(Default)]
+pub struct Motion {
+    pub count: Option<usize>,
+    pub g: bool,
+}
+
+#[derive(Default)]
 pub struct Input {
     pub buffer: String,
     pub cursor_position: usize,
 }
 
-pub struct Dataset {
-    pub query_alias: Option<String>,
-    pub facets: BTreeMap<String, Vec<(f64, f64)>>,
-    pub bounds: Bounds,
-    pub selection: String,
+pub struct DatePicker {
+    pub until: bool,
+    pub time: DateTime<FixedOffset>,
+    // Index into year, month, day, hour, minute
+    pub field: usize,
+}
+
+#[derive(Default)]
+pub struct TraceView {
+    pub summaries: Vec<TraceSummary>,
+    pub list_state: ListState,
+    // Spans of the trace being viewed, keyed by trace.id
+    pub spans: Option<(String, Vec<Span>)>,
+    pub loading: bool,
+}
+
+#[derive(Default, Clone, Copy, PartialEq)]
+pub enum EntityKind {
+    #[default]
+    Apm,
+    Browser,
+    Mobile,
+}
+
+#[derive(Default)]
+pub struct EntityView {
+    pub entities: Vec<Entity>,
+    pub list_state: ListState,
+    pub loading: bool,
+    pub kind: EntityKind,
+}
+
+#[derive(Default)]
+pub struct CrashView {
+    // Mobile app the crash groups are from
+    pub app: String,
+    pub groups: Vec<CrashGroup>,
+    pub list_state: ListState,
+    pub loading: bool,
+}
+
+#[derive(Default)]
+pub struct AlertView {
+    pub issues: Vec<Issue>,
+    pub list_state: ListState,
+    pub loading: bool,
+    // Outcome of the last action, e.g. an acknowledgement
+    pub message: Option<String>,
+}
+
+#[derive(Default)]
+pub struct HostView {
+    pub hosts: Vec<Host>,
+    pub list_state: ListState,
+    pub loading: bool,
+    // Column the table is sorted by: name, then CPU, memory and disk, highest first
+    pub sort: usize,
+}
+
+impl HostView {
+    fn sort(&mut self) {
+        let latest = |series: &[(f64, f64)]| series.last().map_or(f64::MIN, |(_, y)| *y);
+        match self.sort {
+            0 => self.hosts.sort_by(|a, b| a.name.cmp(&b.name)),
+            column => self.hosts.sort_by(|a, b| {
+                let (a, b) = (a.metrics()[column - 1], b.metrics()[column - 1]);
+                latest(b).total_cmp(&latest(a))
+            }),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct KubeView {
+    // Cluster then namespace drilled into, empty when listing clusters
+    pub path: Vec<String>,
+    pub rows: Vec<KubeRow>,
+    pub list_state: ListState,
+    pub loading: bool,
+}
+
+#[derive(Default)]
+pub struct SloView {
+    pub slos: Vec<Slo>,
+    pub list_state: ListState,
+    pub loading: bool,
+}
+
+#[derive(Default)]
+pub struct WorkloadView {
+    pub workloads: Vec<Workload>,
+    pub list_state: ListState,
+    pub loading: bool,
+    pub message: Option<String>,
+}
+
+#[derive(Default)]
+pub struct TemplateView {
+    pub list_state: ListState,
+    // Template being filled in and the values given so far
+    pub filling: Option<(QueryTemplate, BTreeMap<String, String>)>,
+    // Position in the recent values of the variable being asked for
+    pub recent: usize,
+}
+
+#[derive(Default)]
+pub struct ConsoleView {
+    // Pretty-printed JSON of the last response, or why it failed
+    pub response: String,
+    pub scroll: u16,
+    pub loading: bool,
+}
+
+#[derive(Default)]
+pub struct ImportView {
+    pub dashboards: Vec<Entity>,
+    pub list_state: ListState,
+    pub loading: bool,
+    pub message: Option<String>,
+}
+
+impl DatePicker {
+    pub fn at(now: DateTime<FixedOffset>) -> Self {
+        DatePicker {
+            until: false,
+            time: now.with_second(0).unwrap_or(now),
+            field: 3,
+        }
+    }
+
+    fn adjust(&mut self, step: i32) {
+        let time = self.time;
+        self.time = match self.field {
+            0 => time.with_year(time.year() + step),
+            1 if step > 0 => time.checked_add_months(Months::new(step as u32)),
+            1 => time.checked_sub_months(Months::new(step.unsigned_abs())),
+            2 => time.checked_add_signed(Delta::days(step as i64)),
+            3 => time.checked_add_signed(Delta::hours(step as i64)),
+            _ => time.checked_add_signed(Delta::minutes(step as i64)),
+        }
+        .unwrap_or(time);
+    }
+
+    // NRQL datetime literal, e.g. '2024-03-01 09:30:00+0100'
+    pub fn literal(&self) -> String {
+        format!("'{}'", self.time.format("%Y-%m-%d %H:%M:%S%z"))
+    }
 }
 
 pub struct Theme {
@@ -69,239 +491,2507 @@ pub struct Theme {
     pub value_fg: Color,
 }
 
+impl Theme {
+    pub fn new(palette: &Palette) -> Self {
+        Theme {
+            focus_fg: palette.c500,
+            chart_fg: palette.c900,
+            elastic_fg: palette.c400,
+            net_fg: palette.c400,
+            webex_fg: tailwind::AMBER.c400,
+            value_fg: palette.c400,
+        }
+    }
+}
+
 pub struct App {
-    pub session: Option<BTreeMap<String, String>>,
+    pub session: Option<Session>,
+    pub session_path: PathBuf,
+    pub pending: BTreeMap<String, (String, SessionQuery)>,
+    pub dirty: bool,
+    // Viewing only: no adding, deleting or renaming, and the session is never saved
+    pub read_only: bool,
+    pub config: Config,
+    pub numbers: NumberFormat,
+    pub timezone: Timezone,
+    pub text: Catalog,
     pub theme: Theme,
-    pub inputs: [Input; 4],
+    pub inputs: [Input; 29],
     pub input_mode: InputMode,
     pub focus: Focus,
     pub backend: AppBackend,
     pub selected_query: String,
     pub list_state: ListState,
-    pub datasets: BTreeMap<String, Dataset>,
+    // Rows the query list showed when last drawn, which half a page is of
+    pub list_height: u16,
+    // Rows the open list view showed when last drawn, for its half-page moves
+    pub view_height: u16,
+    pub motion: Motion,
+    pub datasets: Datasets,
+    pub time_range: Option<TimeRange>,
+    pub variables: Variables,
+    pub last_input: Instant,
+    pub idle: bool,
+    pub scrub: Option<(f64, f64)>,
+    // Facet summarised in the details panel, the whole dataset when None
+    pub stats_facet: Option<String>,
+    pub expanded: bool,
+    pub date_picker: Option<DatePicker>,
+    pub traces: TraceView,
+    // Session queries waiting to be dispatched to the backend
+    pub loading: VecDeque<DataSource>,
+    pub load_total: usize,
+    pub last_load: Instant,
+    pub entities: EntityView,
+    pub alerts: AlertView,
+    pub import: ImportView,
+    pub console: ConsoleView,
+    pub hosts: HostView,
+    pub kube: KubeView,
+    pub crashes: CrashView,
+    pub slos: SloView,
+    pub workloads: WorkloadView,
+    pub templates: TemplateView,
+    pub recent_values: RecentValues,
+    // Name and guid of the dashboard last exported to, updated in place on the next export
+    pub exported: Option<(String, String)>,
+    pub export_message: Option<String>,
+    // Index into the configured layout presets, once one has been applied
+    pub layout: Option<usize>,
+    pub side_width: u16,
+    // Why the startup credential check failed, shown until retried or skipped
+    pub preflight: Option<PreflightFailure>,
+    // Whether the most recent query response succeeded, None until one arrives
+    pub api_ok: Option<bool>,
+    // Scroll offset of the help overlay while it is open
+    pub help: Option<u16>,
+    // Highlighted row of the command palette
+    pub palette: usize,
+    // Set once an action asks the app to exit
+    pub quit: bool,
+    pub show_diagnostics: bool,
+    // Last failure worth telling the user about, dismissed by any key
+    pub error: Option<String>,
+    // Why the submitted query was rejected, shown until it is fixed or abandoned
+    pub query_error: Option<String>,
+    // Keys played back in place of the terminal until the script runs out
+    pub script: Option<Script>,
+    // Reloaded whenever the file changes
+    pub config_watch: Option<ConfigWatch>,
+    // When each query last notified of a threshold crossing
+    pub notified: BTreeMap<String, Instant>,
+    // Latest crossing or config reload, shown in the status bar for a few seconds
+    pub flash: Option<(String, Color, Instant)>,
+    // Set when the terminal bell is due on the next frame
+    pub bell: bool,
+    // Set by Ctrl-Z, the terminal is handed back once the key has been handled
+    pub suspend: bool,
+    // Set when the screen is out of date, otherwise it's only drawn again once the clocks move on
+    pub redraw: bool,
 }
 
 impl App {
     pub fn new(
         palette: &Palette,
         backend: AppBackend,
-        session: Option<BTreeMap<String, String>>,
+        config: Config,
+        text: Catalog,
+        session: Option<Session>,
+        session_path: PathBuf,
+        read_only: bool,
     ) -> Self {
+        let mut backend = backend;
+        let variables: Variables = session_path
+            .parent()
+            .and_then(|dir| fs::read_to_string(dir.join(VARIABLES_FILE)).ok())
+            .and_then(|yaml| serde_yaml::from_str(&yaml).ok())
+            .unwrap_or_default();
+        backend.send(UIEvent::SetVariables(variables.clone()));
+        backend.send(UIEvent::SetRefreshInterval(config.refresh_interval));
+        backend.send(UIEvent::SetAnomalies(
+            config.anomaly_window,
+            config.anomaly_sigmas,
+        ));
+        let recent_values = session_path
+            .parent()
+            .map(|dir| RecentValues::load(&dir.join(RECENT_VALUES)))
+            .unwrap_or_default();
         Self {
-            inputs: [
-                Input {
-                    buffer: "".to_owned(),
-                    cursor_position: 0,
-                },
-                Input {
-                    buffer: "".to_owned(),
-                    cursor_position: 0,
-                },
-                Input {
-                    buffer: "".to_owned(),
-                    cursor_position: 0,
-                },
-                Input {
-                    buffer: "".to_owned(),
-                    cursor_position: 0,
-                },
-            ],
+            inputs: Default::default(),
             session,
-            theme: Theme {
-                focus_fg: palette.c500,
-                chart_fg: palette.c900,
-                elastic_fg: palette.c400,
-                net_fg: palette.c400,
-                webex_fg: tailwind::AMBER.c400,
-                value_fg: palette.c400,
-            },
+            session_path,
+            pending: BTreeMap::default(),
+            dirty: false,
+            numbers: config.number_format(),
+            timezone: config.timezone(),
+            read_only,
+            text,
+            config,
+            theme: Theme::new(palette),
             input_mode: InputMode::Normal,
             focus: Focus::Default,
             backend,
             selected_query: String::new(),
             list_state: ListState::default(),
-            datasets: BTreeMap::default(),
+            list_height: 0,
+            view_height: 0,
+            motion: Motion::default(),
+            datasets: Datasets::default(),
+            time_range: None,
+            variables,
+            last_input: Instant::now(),
+            idle: false,
+            scrub: None,
+            stats_facet: None,
+            expanded: false,
+            date_picker: None,
+            traces: TraceView::default(),
+            loading: VecDeque::default(),
+            load_total: 0,
+            last_load: Instant::now(),
+            entities: EntityView::default(),
+            alerts: AlertView::default(),
+            import: ImportView::default(),
+            console: ConsoleView::default(),
+            hosts: HostView::default(),
+            kube: KubeView::default(),
+            crashes: CrashView::default(),
+            slos: SloView::default(),
+            workloads: WorkloadView::default(),
+            templates: TemplateView::default(),
+            recent_values,
+            exported: None,
+            export_message: None,
+            layout: None,
+            side_width: 15,
+            preflight: None,
+            api_ok: None,
+            help: None,
+            palette: 0,
+            quit: false,
+            show_diagnostics: false,
+            error: None,
+            query_error: None,
+            script: None,
+            config_watch: None,
+            notified: BTreeMap::default(),
+            flash: None,
+            bell: false,
+            suspend: false,
+            redraw: true,
+        }
+    }
+
+    // Returns false if the user chose to quit from the diagnostic screen
+    pub fn check_credentials<B: Backend>(
+        &mut self,
+        terminal: &mut Terminal<B>,
+    ) -> io::Result<bool> {
+        loop {
+            match self.backend.preflight() {
+                Ok(()) => {
+                    self.preflight = None;
+                    return Ok(true);
+                }
+                Err(failure) => self.preflight = Some(failure),
+            }
+            terminal.draw(|f| render_preflight(self, f, f.size()))?;
+
+            loop {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind != KeyEventKind::Press {
+                        continue;
+                    }
+                    match key.code {
+                        KeyCode::Char('r') => break,
+                        KeyCode::Char('c') => {
+                            self.preflight = None;
+                            return Ok(true);
+                        }
+                        KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+                        _ => {}
+                    }
+                }
+            }
         }
     }
 
     pub fn run<B: Backend>(mut self, terminal: &mut Terminal<B>) -> io::Result<()> {
+        let input = read_input(&self.backend.wakeup);
+        let mut last_draw = Instant::now();
         loop {
-            terminal.draw(|f| self.ui(f))?;
+            // Spinners move on every tick while anything is loading
+            let interval = match self.fetching() {
+                true => TICK,
+                false => REDRAW_INTERVAL,
+            };
+            if self.redraw || last_draw.elapsed() >= interval {
+                terminal.draw(|f| self.ui(f))?;
+                self.redraw = false;
+                last_draw = Instant::now();
+            }
+            if self.bell {
+                self.bell = false;
+                let mut stdout = std::io::stdout();
+                stdout.write_all(b"\x07").and_then(|_| stdout.flush())?;
+            }
 
-            if self.session.is_some() {
+            if self.session.is_some() && self.focus != Focus::SessionLoad {
                 self.focus = Focus::SessionLoad;
                 self.input_mode = InputMode::Input;
+                self.redraw = true;
             }
 
+            self.check_idle();
+            self.reload_config();
+            self.dispatch_load();
+
             // Manual event handlers.
-            if let Ok(true) = event::poll(Duration::from_millis(50)) {
-                if let Event::Key(key) = event::read()? {
-                    match self.input_mode {
-                        InputMode::Normal if key.kind == KeyEventKind::Press => match key.code {
-                            KeyCode::Char('q') => return Ok(()),
-                            KeyCode::Char('e') => {
-                                self.set_focus(Focus::QueryInput);
-                                self.input_mode = InputMode::Input;
+            match self.script.as_mut() {
+                Some(script) if !script.is_done() => {
+                    // Scripts are recorded, so every step is drawn
+                    self.redraw = true;
+                    match script.next_key() {
+                        Some(key) => self.handle_key(key),
+                        None => thread::sleep(Duration::from_millis(10)),
+                    }
+                }
+                _ => {
+                    // Sleeps until a key or payload arrives, or the next tick for the timers
+                    self.redraw |= self.backend.wakeup.wait(TICK);
+                    while let Ok(event) = input.try_recv() {
+                        match event {
+                            Event::Key(key) => self.handle_key(key),
+                            // Resized before the next draw, which lays out for the new size
+                            Event::Resize(_, _) => terminal.autoresize()?,
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            if self.quit {
+                return Ok(());
+            }
+            if self.suspend {
+                self.suspend = false;
+                suspend(terminal)?;
+                self.redraw = true;
+                // Refreshes carried on where they were, but the idle timer shouldn't count the pause
+                self.wake();
+            }
+
+            // Payloads arrive ready to show, as the backend tasks parse responses, build the
+            // series and mark anomalies. Handling one only moves it into place (hosts are also
+            // re-sorted, 100 rows at most), so draining them here doesn't hold up keys.
+            while let Ok(payload) = self.backend.data_rx.try_recv() {
+                self.handle_payload(payload);
+            }
+            while let Ok(payload) = self.backend.import_rx.try_recv() {
+                self.handle_import(payload);
+            }
+            while let Ok(payload) = self.backend.alert_rx.try_recv() {
+                self.handle_alert(payload);
+            }
+            while let Ok(entities) = self.backend.entity_rx.try_recv() {
+                self.handle_entities(entities);
+            }
+            while let Ok(payload) = self.backend.trace_rx.try_recv() {
+                self.handle_trace(payload);
+            }
+            while let Ok(response) = self.backend.console_rx.try_recv() {
+                self.handle_console(response);
+            }
+            while let Ok(hosts) = self.backend.host_rx.try_recv() {
+                self.handle_hosts(hosts);
+            }
+            while let Ok((path, rows)) = self.backend.kube_rx.try_recv() {
+                self.handle_kube(path, rows);
+            }
+            while let Ok(groups) = self.backend.crash_rx.try_recv() {
+                self.handle_crashes(groups);
+            }
+            while let Ok(slos) = self.backend.slo_rx.try_recv() {
+                self.handle_slos(slos);
+            }
+            while let Ok(payload) = self.backend.workload_rx.try_recv() {
+                self.handle_workload(payload);
+            }
+        }
+    }
+
+    // Overlays and list views take keys directly, everything else goes through an Action
+    pub fn handle_key(&mut self, key: KeyEvent) {
+        self.wake();
+        if key.kind != KeyEventKind::Press {
+            return;
+        }
+        if self.error.take().is_some() {
+            return;
+        }
+        match self.focus {
+            _ if self.help.is_some() => self.help_key(key.code),
+            _ if self.show_diagnostics => self.diagnostics_key(key.code),
+            Focus::Palette => self.palette_key(key.code),
+            Focus::DatePicker => self.date_picker_key(key.code),
+            Focus::Traces => self.traces_key(key),
+            Focus::Entities => self.entities_key(key),
+            Focus::Alerts => self.alerts_key(key),
+            Focus::Import => self.import_key(key),
+            Focus::Hosts => self.hosts_key(key),
+            Focus::Kubernetes => self.kube_key(key),
+            Focus::Crashes => self.crashes_key(key),
+            Focus::ServiceLevels => self.slos_key(key),
+            Focus::Workloads => self.workloads_key(key),
+            Focus::Templates => self.templates_key(key),
+            Focus::TemplateVariable if matches!(key.code, KeyCode::Up | KeyCode::Down) => {
+                self.cycle_recent(key.code == KeyCode::Up)
+            }
+            Focus::Console if matches!(self.input_mode, InputMode::Normal) => {
+                self.console_key(key.code)
+            }
+            _ => {
+                // Bindings are plain characters, so Ctrl and Alt chords keep their defaults
+                let bound = match (&self.input_mode, key.code) {
+                    (InputMode::Normal, KeyCode::Char(c))
+                        if !key
+                            .modifiers
+                            .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+                    {
+                        self.config.keybindings().get(&c).copied()
+                    }
+                    _ => None,
+                };
+                let action = bound.or_else(|| {
+                    action::from_key(key, &self.input_mode, self.focus, self.scrub.is_some())
+                });
+                if let Some(action) = action {
+                    self.handle_action(action);
+                }
+            }
+        }
+    }
+
+    pub fn handle_action(&mut self, action: Action) {
+        let motion = std::mem::take(&mut self.motion);
+        match action {
+            Action::EditQuery
+            | Action::Duplicate
+            | Action::Delete
+            | Action::RenameQuery
+            | Action::EditNote
+            | Action::EditAxis
+            | Action::EditThresholds
+            | Action::AddLink
+            | Action::Import
+            | Action::NewTab
+            | Action::RenameTab
+            | Action::CloseTab
+            | Action::MoveToNextTab
+                if self.read_only => {}
+            Action::Suspend => self.suspend = true,
+            Action::Quit => match self.config.quit_behaviour {
+                _ if !self.dirty || self.read_only => self.quit = true,
+                QuitBehaviour::Always => match self.save_session() {
+                    Ok(()) => self.quit = true,
+                    Err(e) => self.show_error(e),
+                },
+                QuitBehaviour::Never => self.quit = true,
+                QuitBehaviour::Ask => {
+                    self.set_focus(Focus::SessionSave);
+                    self.input_mode = InputMode::Input;
+                }
+            },
+            Action::EditQuery => {
+                self.query_error = None;
+                self.set_focus(Focus::QueryInput);
+                self.input_mode = InputMode::Input;
+            }
+            Action::Duplicate if !self.selected_query.is_empty() => {
+                let text = self.selected_query.to_owned();
+                self.query_error = None;
+                self.set_focus(Focus::QueryInput);
+                self.input_mode = InputMode::Input;
+                self.inputs[QUERY as usize].cursor_position = text.len();
+                self.inputs[QUERY as usize].buffer = text;
+            }
+            Action::SelectNext => match motion.count {
+                Some(count) => self.move_selection(count as isize),
+                None => self.next(),
+            },
+            Action::SelectPrevious => match motion.count {
+                Some(count) => self.move_selection(-(count as isize)),
+                None => self.previous(),
+            },
+            Action::Count(0) if motion.count.is_none() => {}
+            Action::Count(digit) => {
+                let count = motion.count.unwrap_or(0) * 10 + digit as usize;
+                self.motion.count = Some(count.min(MAX_COUNT));
+            }
+            // 5gg and 5G both go to the fifth query
+            Action::Go if motion.g => self.select_line(motion.count.unwrap_or(1)),
+            Action::Go => self.motion = Motion { g: true, ..motion },
+            Action::Bottom => self.select_line(motion.count.unwrap_or(self.datasets.len())),
+            Action::HalfPageDown | Action::HalfPageUp => {
+                let rows = (self.list_height as usize / 2).max(1) * motion.count.unwrap_or(1);
+                match action {
+                    Action::HalfPageDown => self.move_selection(rows as isize),
+                    _ => self.move_selection(-(rows as isize)),
+                }
+            }
+            Action::ShiftDown => self.shift_query(false),
+            Action::ShiftUp => self.shift_query(true),
+            Action::TogglePin => self.toggle_pin(),
+            Action::Delete => self.delete(),
+            Action::RenameQuery if self.focus != Focus::QueryInput && !self.datasets.is_empty() => {
+                self.set_focus(Focus::Rename);
+                self.input_mode = InputMode::Input;
+            }
+            Action::EditAxis if !self.datasets.is_empty() => {
+                let spec = self
+                    .datasets
+                    .get(&self.selected_query)
+                    .map(|data| data.axis.spec())
+                    .unwrap_or_default();
+                self.set_focus(Focus::Axis);
+                self.inputs[AXIS as usize].cursor_position = spec.len();
+                self.inputs[AXIS as usize].buffer = spec;
+                self.input_mode = InputMode::Input;
+            }
+            Action::EditThresholds if !self.datasets.is_empty() => {
+                let spec = self
+                    .datasets
+                    .get(&self.selected_query)
+                    .map(|data| Threshold::spec_all(&data.thresholds))
+                    .unwrap_or_default();
+                self.set_focus(Focus::Thresholds);
+                self.inputs[THRESHOLDS as usize].cursor_position = spec.len();
+                self.inputs[THRESHOLDS as usize].buffer = spec;
+                self.input_mode = InputMode::Input;
+            }
+            Action::EditVariables => {
+                let spec = variables_spec(&self.variables);
+                self.set_focus(Focus::Variables);
+                self.inputs[VARIABLES as usize].cursor_position = spec.len();
+                self.inputs[VARIABLES as usize].buffer = spec;
+                self.input_mode = InputMode::Input;
+            }
+            Action::EditNote if !self.datasets.is_empty() => {
+                let note = self
+                    .datasets
+                    .get(&self.selected_query)
+                    .map(|data| data.note.to_owned())
+                    .unwrap_or_default();
+                self.set_focus(Focus::Note);
+                self.inputs[NOTE as usize].cursor_position = note.len();
+                self.inputs[NOTE as usize].buffer = note;
+                self.input_mode = InputMode::Input;
+            }
+            Action::AddLink if !self.datasets.is_empty() => {
+                self.set_focus(Focus::Link);
+                self.input_mode = InputMode::Input;
+            }
+            Action::OpenLinks => self.open_links(),
+            Action::OpenQueryBuilder => self.open_query_builder(),
+            Action::EditTimeRange if !self.datasets.is_empty() => {
+                self.set_focus(Focus::TimeRange);
+                self.input_mode = InputMode::Input;
+            }
+            Action::Import => {
+                self.set_focus(Focus::Import);
+                self.import.message = None;
+                self.refresh_import();
+            }
+            Action::Export if !self.datasets.is_empty() => {
+                let name = match &self.exported {
+                    Some((name, _)) => name.to_owned(),
+                    None => self
+                        .session_path
+                        .file_stem()
+                        .map(|stem| stem.to_string_lossy().into_owned())
+                        .unwrap_or_default(),
+                };
+                self.set_focus(Focus::Export);
+                self.inputs[EXPORT as usize].cursor_position = name.len();
+                self.inputs[EXPORT as usize].buffer = name;
+                self.input_mode = InputMode::Input;
+            }
+            Action::NextLayout => self.next_layout(),
+            Action::Help => self.help = Some(0),
+            Action::Diagnostics => self.show_diagnostics = !self.show_diagnostics,
+            Action::Palette => {
+                self.palette = 0;
+                self.set_focus(Focus::Palette);
+                self.input_mode = InputMode::Input;
+            }
+            Action::NextTab => self.switch_tab(true),
+            Action::PreviousTab => self.switch_tab(false),
+            Action::NewTab => {
+                let tabs = self.datasets.tabs();
+                let name = (tabs.len() + 1..)
+                    .map(|n| n.to_string())
+                    .find(|name| !tabs.contains(name))
+                    .unwrap_or_default();
+                self.datasets.new_tab(name);
+                self.select_first();
+                self.rename_tab();
+            }
+            Action::RenameTab => self.rename_tab(),
+            Action::CloseTab => {
+                self.datasets.close_tab();
+                self.select_first();
+                self.dirty = true;
+            }
+            Action::MoveToNextTab if !self.datasets.is_empty() => {
+                let next = (self.datasets.current_tab() + 1) % self.datasets.tabs().len();
+                self.datasets.assign(&self.selected_query, next);
+                self.select_first();
+                self.dirty = true;
+            }
+            Action::Alerts => {
+                self.set_focus(Focus::Alerts);
+                self.alerts.message = None;
+                self.refresh_alerts();
+            }
+            Action::Entities => {
+                self.set_focus(Focus::Entities);
+                self.entities.kind = EntityKind::Apm;
+                self.refresh_entities();
+            }
+            Action::Browser => {
+                self.set_focus(Focus::Entities);
+                self.entities.kind = EntityKind::Browser;
+                self.refresh_entities();
+            }
+            Action::Mobile => {
+                self.set_focus(Focus::Entities);
+                self.entities.kind = EntityKind::Mobile;
+                self.refresh_entities();
+            }
+            Action::Console => self.set_focus(Focus::Console),
+            Action::Hosts => {
+                self.set_focus(Focus::Hosts);
+                self.refresh_hosts();
+            }
+            Action::Templates => {
+                self.set_focus(Focus::Templates);
+                let selected = (!self.config.templates.is_empty()).then_some(0);
+                self.templates.list_state.select(selected);
+            }
+            Action::Workloads => {
+                self.set_focus(Focus::Workloads);
+                self.workloads.message = None;
+                self.refresh_workloads();
+            }
+            Action::ServiceLevels => {
+                self.set_focus(Focus::ServiceLevels);
+                self.refresh_slos();
+            }
+            Action::Kubernetes => {
+                self.set_focus(Focus::Kubernetes);
+                self.open_kube(vec![]);
+            }
+            Action::Traces => {
+                self.set_focus(Focus::Traces);
+                self.traces.spans = None;
+                self.refresh_traces();
+            }
+            Action::ToggleDashboard => {
+                self.expanded = false;
+                match self.focus {
+                    Focus::Dashboard => self.set_focus(Focus::Default),
+                    _ => self.set_focus(Focus::Dashboard),
+                }
+            }
+            Action::Scrub { grow, pan } => self.scrub_chart(grow, pan),
+            Action::ClearScrub => self.scrub = None,
+            Action::ToggleExpanded => self.expanded = !self.expanded,
+            Action::ZoomIn => self.zoom_in(),
+            Action::ZoomOut => self.zoom_out(),
+            Action::SaveSession => {
+                if let Err(e) = self.save_session() {
+                    self.show_error(e);
+                }
+            }
+            Action::ToggleColourBlind => self.config.colour_blind = !self.config.colour_blind,
+            Action::ToggleSnapshot => {
+                if let Some(data) = self.datasets.get_mut(&self.selected_query) {
+                    data.snapshot = match data.snapshot {
+                        Some(_) => None,
+                        None => Some(Snapshot {
+                            taken: Utc::now().timestamp() as f64,
+                            facets: data.facets.clone(),
+                        }),
+                    };
+                    data.version += 1;
+                }
+            }
+            Action::NextFacet => {
+                if let Some(data) = self.datasets.get(&self.selected_query) {
+                    let next = match &self.stats_facet {
+                        Some(current) => data.facets.keys().skip_while(|f| *f != current).nth(1),
+                        None => data.facets.keys().next(),
+                    };
+                    self.stats_facet = next.cloned();
+                }
+            }
+            Action::NextTimezone => self.timezone = self.timezone.next(self.config.timezone()),
+            Action::NextChartStyle => {
+                if let Some(data) = self.datasets.get_mut(&self.selected_query) {
+                    data.style = data.style.next();
+                    self.dirty = true;
+                }
+            }
+            Action::Submit => {
+                match self.focus {
+                    Focus::QueryInput if !self.input_buffer(QUERY).trim().is_empty() => {
+                        match parse_source(self.input_buffer(QUERY)) {
+                            Ok(source) => {
+                                self.query_error = None;
+                                self.add_source(source);
+                                self.dirty = true;
                             }
-                            KeyCode::Char('j') => self.next(),
-                            KeyCode::Char('k') => self.previous(),
-                            KeyCode::Char('x') => self.delete(),
-                            KeyCode::Char('r') => match self.focus {
-                                Focus::QueryInput => {}
-                                _ => {
-                                    if self.datasets.len() != 0 {
-                                        self.set_focus(Focus::Rename);
-                                        self.input_mode = InputMode::Input;
+                            // Leave the query open to be fixed
+                            Err(e) => {
+                                self.query_error = Some(e.to_string());
+                                return;
+                            }
+                        }
+                    }
+                    Focus::Rename => {
+                        self.rename_current_query();
+                        self.dirty = true;
+                    }
+                    Focus::TabName => {
+                        let name = self.input_buffer(TAB_NAME).trim().to_owned();
+                        if !name.is_empty() && !self.datasets.tabs().contains(&name) {
+                            self.datasets.rename_tab(name);
+                            self.dirty = true;
+                        }
+                    }
+                    Focus::SessionLoad => {
+                        match self.input_buffer(SESSION_LOAD) {
+                            // Load session
+                            "y" | "Y" => {
+                                let mut session = self
+                                    .session
+                                    .clone()
+                                    .unwrap_or_default()
+                                    .into_iter()
+                                    .collect::<Vec<_>>();
+                                // Pinned queries sit at the top of the list, so load them first
+                                session.sort_by_key(|(_, entry)| entry.position);
+                                // Tabs are saved in order, so first appearance gives their order
+                                let mut tabs: Vec<String> = vec![];
+                                for (_, entry) in &session {
+                                    if !entry.tab.is_empty() && !tabs.contains(&entry.tab) {
+                                        tabs.push(entry.tab.to_owned());
                                     }
                                 }
-                            },
-                            KeyCode::Char('d') => match self.focus {
-                                Focus::Dashboard => self.set_focus(Focus::Default),
-                                _ => self.set_focus(Focus::Dashboard),
-                            },
-                            _ => (),
-                        },
-                        InputMode::Input if key.kind == KeyEventKind::Press => match key.code {
-                            KeyCode::Enter => {
-                                match self.focus {
-                                    Focus::QueryInput => {
-                                        if let Ok(query) = self.input_buffer(QUERY).to_nrql() {
-                                            self.add_query(query);
-                                        }
-                                    }
-                                    Focus::Rename => {
-                                        self.rename_current_query();
-                                    }
-                                    Focus::SessionLoad => {
-                                        match self.input_buffer(SESSION_LOAD) {
-                                            // Load session
-                                            "y" | "Y" => {
-                                                let mut session =
-                                                    self.session.clone().unwrap().into_iter();
-                                                while let Some((_alias, query)) = session.next() {
-                                                    if let Ok(query) = query.trim().to_nrql() {
-                                                        self.add_query(query);
-                                                        // self.set_focus(Focus::Loading);
-                                                    }
-                                                }
-                                                // };
-                                            }
-                                            // Don't load session
-                                            _ => {}
+                                self.datasets.set_tabs(tabs);
+                                session.sort_by_key(|(_, entry)| (!entry.pinned, entry.position));
+                                for (alias, entry) in session {
+                                    if let Ok(source) = parse_source(&upgrade_query(&entry.query)) {
+                                        // Restored once the first payload arrives
+                                        let key = source.key();
+                                        self.datasets.reserve(&key, entry.pinned);
+                                        if !entry.tab.is_empty() {
+                                            self.datasets.assign_named(&key, &entry.tab);
                                         }
-                                        // Clear previous session once loaded
-                                        self.session = None;
-
-                                        // Update focus to home
-                                        self.set_focus(Focus::Default);
+                                        self.pending.insert(key, (alias, entry));
+                                        self.loading.push_back(source);
+                                        self.load_total += 1;
+                                        // self.set_focus(Focus::Loading);
                                     }
-                                    _ => {}
-                                };
-                                self.inputs[self.focus as usize].buffer.clear();
-                                self.reset_cursor();
-                                self.set_focus(Focus::Default);
-                                self.input_mode = InputMode::Normal;
+                                }
+                                // };
                             }
-                            KeyCode::Char(to_insert) => {
-                                self.enter_char(to_insert);
+                            // Don't load session
+                            _ => {}
+                        }
+                        // Clear previous session once loaded
+                        self.session = None;
+
+                        // Update focus to home
+                        self.set_focus(Focus::Default);
+                    }
+                    Focus::Note => {
+                        let note = self.input_buffer(NOTE).trim().to_owned();
+                        if let Some(data) = self.datasets.get_mut(&self.selected_query) {
+                            data.note = note;
+                            self.dirty = true;
+                        }
+                    }
+                    Focus::Axis => match YAxis::parse(self.input_buffer(AXIS)) {
+                        Ok(axis) => {
+                            if let Some(data) = self.datasets.get_mut(&self.selected_query) {
+                                data.axis = axis;
+                                self.dirty = true;
                             }
-                            KeyCode::Backspace => {
-                                self.delete_char();
+                        }
+                        // Stay open so the settings can be corrected
+                        Err(e) => {
+                            self.show_error(e);
+                            return;
+                        }
+                    },
+                    Focus::Thresholds => {
+                        match Threshold::parse_all(self.input_buffer(THRESHOLDS)) {
+                            Ok(thresholds) => {
+                                if let Some(data) = self.datasets.get_mut(&self.selected_query) {
+                                    data.thresholds = thresholds;
+                                    self.dirty = true;
+                                }
                             }
-                            KeyCode::Left => {
-                                self.move_cursor_left();
+                            Err(e) => {
+                                self.show_error(e);
+                                return;
                             }
-                            KeyCode::Right => {
-                                self.move_cursor_right();
+                        }
+                    }
+                    Focus::Variables => match parse_variables(self.input_buffer(VARIABLES)) {
+                        Ok(variables) => self.set_variables(variables),
+                        Err(e) => {
+                            self.show_error(e);
+                            return;
+                        }
+                    },
+                    Focus::Link => {
+                        let link = self.input_buffer(LINK).trim().to_owned();
+                        if let Some(data) = self.datasets.get_mut(&self.selected_query) {
+                            if !link.is_empty() {
+                                data.links.push(link);
+                                self.dirty = true;
                             }
-                            KeyCode::Esc => {
-                                self.set_focus(Focus::Default);
-                                self.input_mode = InputMode::Normal;
+                        }
+                    }
+                    Focus::TimeRange => {
+                        if let Some(range) = parse_time_range(self.input_buffer(TIME_RANGE)) {
+                            self.set_time_range(range);
+                        }
+                    }
+                    Focus::Export => self.export_dashboard(),
+                    // Ask for the next variable, or open the query box once they are all given
+                    Focus::TemplateVariable => {
+                        let value = self.input_buffer(TEMPLATE_VARIABLE).trim().to_owned();
+                        let variable = self.template_variable();
+                        if let (Some(variable), Some((_, values))) =
+                            (variable, self.templates.filling.as_mut())
+                        {
+                            self.recent_values.remember(&variable, &value);
+                            values.insert(variable, value);
+                        }
+                        self.prompt_template_variable();
+                        return;
+                    }
+                    // Keep the query to edit and run again
+                    Focus::Console => {
+                        let query = self.input_buffer(CONSOLE).trim().to_owned();
+                        if !query.is_empty() {
+                            self.console.loading = true;
+                            self.console.scroll = 0;
+                            self.backend.run_graphql(query);
+                        }
+                        self.input_mode = InputMode::Normal;
+                        return;
+                    }
+                    Focus::SessionSave => {
+                        let saved = match self.input_buffer(SESSION_SAVE) {
+                            "y" | "Y" => self.save_session(),
+                            _ => Ok(()),
+                        };
+                        // Stay open rather than lose unsaved queries
+                        match saved {
+                            Ok(()) => {
+                                self.quit = true;
+                                return;
                             }
-                            _ => {}
-                        },
-                        _ => {}
+                            Err(e) => self.show_error(e),
+                        }
                     }
-                }
+                    _ => {}
+                };
+                self.inputs[self.focus as usize].buffer.clear();
+                self.reset_cursor();
+                self.set_focus(Focus::Default);
+                self.input_mode = InputMode::Normal;
             }
-
-            while let Some(payload) = self.backend.data_rx.try_iter().next() {
-                if let Entry::Vacant(e) = self.datasets.entry(payload.query.clone()) {
-                    e.insert(Dataset {
-                        query_alias: None,
-                        facets: payload.data,
-                        bounds: payload.bounds,
-                        selection: payload.selection,
-                    });
-                } else {
-                    _ = self
-                        .datasets
-                        .entry(payload.query.to_owned())
-                        .and_modify(|data| {
-                            data.facets = payload.data;
-                            data.bounds = payload.bounds;
-                        })
-                }
+            Action::OpenDatePicker => {
+                self.date_picker = Some(DatePicker::at(self.timezone.now()));
+                self.set_focus(Focus::DatePicker);
             }
+            Action::InsertChar(to_insert) => self.enter_char(to_insert),
+            Action::DeleteChar => self.delete_char(),
+            Action::CursorLeft => self.move_cursor_left(),
+            Action::CursorRight => self.move_cursor_right(),
+            Action::Cancel if self.focus == Focus::Console => self.input_mode = InputMode::Normal,
+            Action::Cancel => {
+                self.query_error = None;
+                self.templates.filling = None;
+                self.set_focus(Focus::Default);
+                self.input_mode = InputMode::Normal;
+            }
+            _ => {}
         }
     }
 
-    pub fn ui(&mut self, frame: &mut Frame) {
-        if self.focus == Focus::Loading {
-            render_loading(self, frame, frame.size());
+    pub fn handle_payload(&mut self, payload: Payload) {
+        if !self.backend.is_running(&payload.query) {
+            return;
         }
-        if self.focus == Focus::SessionLoad {
-            render_load_session(self, frame, frame.size());
+        if !payload.query.starts_with(FILE_PREFIX) {
+            self.api_ok = Some(payload.error.is_none());
+        }
+        if let Some(data) = self.datasets.get_mut(&payload.query) {
+            data.anomalies = payload.anomalies;
+            data.facets = payload.data;
+            data.comparison = payload.comparison;
+            data.bounds = payload.bounds;
+            data.histogram = payload.histogram;
+            data.scalars = payload.scalars;
+            data.facet_totals = payload.facet_totals;
+            data.notice = payload.notice;
+            data.error = payload.error;
+            data.version += 1;
+        } else {
+            let (alias, entry) = self.pending.remove(&payload.query).unwrap_or_default();
+            let key = payload.query.to_owned();
+            self.datasets.insert(
+                key,
+                Dataset {
+                    query_alias: (!alias.is_empty() && alias != payload.query).then_some(alias),
+                    note: entry.note,
+                    links: entry.links,
+                    facets: payload.data,
+                    comparison: payload.comparison,
+                    anomalies: payload.anomalies,
+                    snapshot: None,
+                    bounds: payload.bounds,
+                    selection: payload.selection,
+                    histogram: payload.histogram,
+                    scalars: payload.scalars,
+                    facet_totals: payload.facet_totals,
+                    style: entry.style,
+                    axis: entry.axis,
+                    thresholds: entry.thresholds,
+                    history: vec![],
+                    notice: payload.notice,
+                    error: payload.error,
+                    version: 0,
+                },
+            );
+        }
+        self.notify_crossing(&payload.query);
+    }
+
+    // Bell and flash once the query is over a threshold, then not again until the cooldown
+    pub fn notify_crossing(&mut self, key: &str) {
+        if !self.config.notify {
             return;
         }
-        if self.focus == Focus::Dashboard {
-            render_dashboard(self, frame, frame.size());
+        let Some(data) = self.datasets.get(key) else {
+            return;
+        };
+        let Some(threshold) = data.breached() else {
+            return;
+        };
+        let cooldown = Duration::from_secs(self.config.notify_cooldown);
+        if self
+            .notified
+            .get(key)
+            .is_some_and(|last| last.elapsed() < cooldown)
+        {
             return;
         }
-        let area = frame.size();
-        // TODO: Possible to pre-compute?
-        let horizontal = Layout::horizontal([Constraint::Percentage(15), Constraint::Min(20)]);
-        let vertical = Layout::vertical([Constraint::Length(3), Constraint::Min(20)]);
-        let [input_area, rest] = vertical.areas(area);
-        let [list_area, graph_area] = horizontal.areas(rest);
 
-        render_query_box(self, frame, input_area);
-        render_query_list(self, frame, list_area);
-        match self.focus {
-            Focus::Default | Focus::QueryInput => {
-                render_graph(self, frame, graph_area);
+        let unit = data.axis.unit.unwrap_or(Unit::infer(&data.selection));
+        let message = self.text.fill(
+            Msg::ThresholdCrossed,
+            &[
+                data.query_alias.as_deref().unwrap_or(key),
+                &threshold.name(unit, &self.numbers),
+            ],
+        );
+        warn!("{}", message);
+        self.flash = Some((message, Color::Red, Instant::now()));
+        self.notified.insert(key.to_owned(), Instant::now());
+        self.bell = true;
+    }
+
+    pub fn handle_import(&mut self, payload: ImportPayload) {
+        match payload {
+            ImportPayload::Dashboards(dashboards) => {
+                self.import.loading = false;
+                self.import
+                    .list_state
+                    .select((!dashboards.is_empty()).then_some(0));
+                self.import.dashboards = dashboards;
             }
-            Focus::Rename => {
-                render_rename_dialog(self, frame, graph_area);
+            ImportPayload::Dashboard(Ok(dashboard)) => self.import_dashboard(dashboard),
+            ImportPayload::Dashboard(Err(e)) => self.import.message = Some(e),
+            ImportPayload::Exported(Ok((name, guid))) => {
+                self.export_message = Some(self.text.fill(Msg::Exported, &[&name]));
+                self.exported = Some((name, guid));
+            }
+            ImportPayload::Exported(Err(e)) => {
+                self.export_message = Some(self.text.fill(Msg::ExportFailed, &[&e]))
             }
-            // Should never be reached
-            _ => panic!(),
         }
     }
 
-    fn rename_current_query(&mut self) {
-        self.datasets
-            .entry(self.selected_query.to_owned())
-            .and_modify(|v| v.query_alias = Some(self.inputs[RENAME as usize].buffer.to_owned()));
-    }
-
-    pub fn input_buffer(&self, focus: isize) -> &str {
-        self.inputs[focus as usize].buffer.as_str()
+    pub fn handle_alert(&mut self, payload: AlertPayload) {
+        match payload {
+            AlertPayload::Issues(issues) => {
+                self.alerts.loading = false;
+                self.alerts
+                    .list_state
+                    .select((!issues.is_empty()).then_some(0));
+                self.alerts.issues = issues;
+            }
+            AlertPayload::Acknowledged(issue_id, Ok(())) => {
+                self.alerts.message = Some(self.text.fill(Msg::Acknowledged, &[&issue_id]));
+                self.alerts.loading = true;
+                self.backend.fetch_issues();
+            }
+            AlertPayload::Acknowledged(issue_id, Err(e)) => {
+                self.alerts.message =
+                    Some(self.text.fill(Msg::AcknowledgeFailed, &[&issue_id, &e]));
+            }
+            AlertPayload::Condition(Ok(nrql)) => {
+                let range = self
+                    .time_range
+                    .clone()
+                    .unwrap_or_else(|| TimeRange::last(TimeRange::PRESETS[0]));
+                match reorder_nrql(&nrql, &range).and_then(|q| q.as_str().to_nrql().ok()) {
+                    Some(mut query) => {
+                        // Conditions are written as single values, chart them over time instead
+                        if !query.is_timeseries() {
+                            query.mode = "TIMESERIES".to_owned();
+                        }
+                        self.alerts.message = None;
+                        self.add_query(query);
+                        self.dirty = true;
+                        self.set_focus(Focus::Default);
+                    }
+                    None => {
+                        self.alerts.message = Some(self.text.fill(Msg::ConditionFailed, &[&nrql]))
+                    }
+                }
+            }
+            AlertPayload::Condition(Err(e)) => self.alerts.message = Some(e),
+        }
     }
 
-    fn add_query(&self, query: NRQLQuery) {
-        self.backend.add_query(query);
+    pub fn handle_entities(&mut self, entities: Vec<Entity>) {
+        self.entities.loading = false;
+        self.entities
+            .list_state
+            .select((!entities.is_empty()).then_some(0));
+        self.entities.entities = entities;
     }
 
-    fn clamp_cursor(&self, new_cursor_pos: usize) -> usize {
-        new_cursor_pos.clamp(0, self.inputs[self.focus as usize].buffer.len())
+    pub fn handle_trace(&mut self, payload: TracePayload) {
+        match payload {
+            TracePayload::Summaries(summaries) => {
+                self.traces.loading = false;
+                self.traces
+                    .list_state
+                    .select((!summaries.is_empty()).then_some(0));
+                self.traces.summaries = summaries;
+            }
+            // Ignore spans for a trace that is no longer open
+            TracePayload::Spans(trace_id, spans) => {
+                if let Some((open, _)) = &self.traces.spans {
+                    if *open == trace_id {
+                        self.traces.loading = false;
+                        self.traces.spans = Some((trace_id, spans));
+                    }
+                }
+            }
+        }
     }
 
-    fn reset_cursor(&mut self) {
-        self.inputs[self.focus as usize].cursor_position = 0;
+    pub fn ui(&mut self, frame: &mut Frame) {
+        let size = frame.size();
+        if size.width < MIN_WIDTH || size.height < MIN_HEIGHT {
+            render_too_small(self, frame, size);
+            return;
+        }
+        let [area, status_area] =
+            Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.size());
+        render_status_bar(self, frame, status_area);
+        self.render_focus(frame, area);
+        if self.help.is_some() {
+            render_help(self, frame, area);
+        } else if self.show_diagnostics {
+            render_diagnostics(self, frame, area);
+        }
+        if self.error.is_some() {
+            render_error(self, frame, area);
+        }
     }
 
-    fn move_cursor_left(&mut self) {
+    fn render_focus(&mut self, frame: &mut Frame, area: Rect) {
+        if self.focus == Focus::Loading {
+            render_loading(self, frame, area);
+        }
+        if self.focus == Focus::SessionLoad {
+            render_load_session(self, frame, area);
+            return;
+        }
+        if self.focus == Focus::SessionSave {
+            render_save_session(self, frame, area);
+            return;
+        }
+        if self.focus == Focus::Dashboard {
+            render_dashboard(self, frame, area);
+            return;
+        }
+        if self.focus == Focus::Traces {
+            render_traces(self, frame, area);
+            return;
+        }
+        if self.focus == Focus::Entities {
+            render_entities(self, frame, area);
+            return;
+        }
+        if self.focus == Focus::Alerts {
+            render_alerts(self, frame, area);
+            return;
+        }
+        if self.focus == Focus::Import {
+            render_import(self, frame, area);
+            return;
+        }
+        if self.focus == Focus::Hosts {
+            render_hosts(self, frame, area);
+            return;
+        }
+        if self.focus == Focus::Workloads {
+            render_workloads(self, frame, area);
+            return;
+        }
+        if self.focus == Focus::Templates {
+            render_templates(self, frame, area);
+            return;
+        }
+        if self.focus == Focus::ServiceLevels {
+            render_slos(self, frame, area);
+            return;
+        }
+        if self.focus == Focus::Crashes {
+            render_crashes(self, frame, area);
+            return;
+        }
+        if self.focus == Focus::Kubernetes {
+            render_kube(self, frame, area);
+            return;
+        }
+        if self.focus == Focus::Console {
+            render_console(self, frame, area);
+            return;
+        }
+        let vertical = Layout::vertical([Constraint::Length(3), Constraint::Min(1)]);
+        let [input_area, rest] = vertical.areas(area);
+        let graph_area = match area.width < COMPACT_WIDTH {
+            true => {
+                render_query_box(self, frame, input_area);
+                rest
+            }
+            false => self.render_side(frame, input_area, rest),
+        };
+        match self.focus {
+            Focus::Default | Focus::QueryInput => {
+                render_graph(self, frame, graph_area);
+            }
+            Focus::Rename | Focus::TabName => {
+                render_rename_dialog(self, frame, graph_area);
+            }
+            Focus::Note
+            | Focus::Link
+            | Focus::Axis
+            | Focus::Thresholds
+            | Focus::TemplateVariable
+            | Focus::Variables => {
+                render_note_dialog(self, frame, graph_area);
+            }
+            Focus::TimeRange => {
+                render_time_range_dialog(self, frame, graph_area);
+            }
+            Focus::DatePicker => {
+                render_date_picker(self, frame, graph_area);
+            }
+            Focus::Export => {
+                render_export_dialog(self, frame, graph_area);
+            }
+            Focus::Palette => {
+                render_graph(self, frame, graph_area);
+                render_palette(self, frame, area);
+            }
+            _ => render_graph(self, frame, graph_area),
+        }
+    }
+
+    // Query list, anomalies and details beside the graph, returning the area left for it
+    fn render_side(&mut self, frame: &mut Frame, input_area: Rect, area: Rect) -> Rect {
+        let horizontal =
+            Layout::horizontal([Constraint::Percentage(self.side_width), Constraint::Min(20)]);
+        let [side_area, graph_area] = horizontal.areas(area);
+        let anomalies = anomaly_lines(self).len().min(5) as u16;
+        let [list_area, anomalies_area, details_area] = Layout::vertical([
+            Constraint::Min(10),
+            Constraint::Length(match anomalies {
+                0 => 0,
+                n => n + 2,
+            }),
+            Constraint::Length(8),
+        ])
+        .areas(side_area);
+        let list_area = match self.datasets.tabs().len() {
+            1 => list_area,
+            _ => {
+                let [tabs_area, list_area] =
+                    Layout::vertical([Constraint::Length(1), Constraint::Min(1)]).areas(list_area);
+                render_tabs(self, frame, tabs_area);
+                list_area
+            }
+        };
+
+        render_query_box(self, frame, input_area);
+        render_query_list(self, frame, list_area);
+        if anomalies > 0 {
+            render_anomalies(self, frame, anomalies_area);
+        }
+        render_details(self, frame, details_area);
+        graph_area
+    }
+
+    fn rename_current_query(&mut self) {
+        if let Some(data) = self.datasets.get_mut(&self.selected_query) {
+            data.query_alias = Some(self.inputs[RENAME as usize].buffer.to_owned());
+        }
+    }
+
+    // KEYMAP with the config's keybindings applied: each bound key is listed beside the
+    // action's default, and removed from whatever it used to do
+    pub fn keymap(&self) -> Vec<(Msg, Vec<(String, Msg)>)> {
+        const MOTIONS: &[(Msg, Action)] = &[
+            (Msg::KeySelect, Action::SelectNext),
+            (Msg::KeySelect, Action::SelectPrevious),
+            (Msg::KeyTopBottom, Action::Bottom),
+            (Msg::KeyHalfPage, Action::HalfPageDown),
+            (Msg::KeyHalfPage, Action::HalfPageUp),
+            (Msg::KeyReorder, Action::ShiftDown),
+            (Msg::KeyReorder, Action::ShiftUp),
+        ];
+        let mut keymap = KEYMAP
+            .iter()
+            .map(|(group, keys)| {
+                let keys = keys
+                    .iter()
+                    .map(|(key, description)| (key.to_string(), *description))
+                    .collect::<Vec<_>>();
+                (*group, keys)
+            })
+            .collect::<Vec<_>>();
+        let Some((_, graph)) = keymap
+            .iter_mut()
+            .find(|(group, _)| *group == Msg::HelpGraph)
+        else {
+            return keymap;
+        };
+
+        for (c, action) in self.config.keybindings() {
+            let Some((description, _)) = COMMANDS
+                .iter()
+                .chain(MOTIONS)
+                .find(|(_, bound)| *bound == action)
+            else {
+                continue;
+            };
+            let c = c.to_string();
+            // Chords such as Ctrl+D/U are not plain keys and stay as they are
+            let plain = |key: &String| !key.contains('+');
+            for (key, _) in graph
+                .iter_mut()
+                .filter(|(key, d)| d != description && plain(key))
+            {
+                *key = key
+                    .split('/')
+                    .filter(|k| *k != c)
+                    .collect::<Vec<_>>()
+                    .join("/");
+            }
+            graph.retain(|(key, _)| !key.is_empty());
+            match graph.iter_mut().find(|(_, d)| d == description) {
+                Some((key, _)) if !key.split('/').any(|k| k == c) => *key = format!("{key}/{c}"),
+                Some(_) => {}
+                None => graph.push((c, *description)),
+            }
+        }
+        keymap
+    }
+
+    fn help_key(&mut self, code: KeyCode) {
+        let lines = self
+            .keymap()
+            .iter()
+            .map(|(_, keys)| keys.len() + 2)
+            .sum::<usize>();
+        let Some(offset) = self.help.as_mut() else {
+            return;
+        };
+        match code {
+            KeyCode::Down | KeyCode::Char('j') => {
+                *offset = u16::min(*offset + 1, lines.saturating_sub(1) as u16)
+            }
+            KeyCode::Up | KeyCode::Char('k') => *offset = offset.saturating_sub(1),
+            KeyCode::Esc | KeyCode::Char('?') | KeyCode::Char('q') => self.help = None,
+            _ => {}
+        }
+    }
+
+    fn diagnostics_key(&mut self, code: KeyCode) {
+        if let KeyCode::F(12) | KeyCode::Esc | KeyCode::Char('q') = code {
+            self.show_diagnostics = false;
+        }
+    }
+
+    // Commands matching the palette input, best match first
+    pub fn palette_matches(&self) -> Vec<(Msg, Action)> {
+        let pattern = self.input_buffer(PALETTE);
+        let mut matches = COMMANDS
+            .iter()
+            .filter_map(|(msg, action)| {
+                fuzzy_score(pattern, self.text.get(*msg)).map(|score| (score, *msg, *action))
+            })
+            .collect::<Vec<_>>();
+        matches.sort_by_key(|(score, _, _)| *score);
+        matches
+            .into_iter()
+            .map(|(_, msg, action)| (msg, action))
+            .collect()
+    }
+
+    fn palette_key(&mut self, code: KeyCode) {
+        let count = self.palette_matches().len();
+        match code {
+            KeyCode::Down if count > 0 => self.palette = (self.palette + 1) % count,
+            KeyCode::Up if count > 0 => self.palette = (self.palette + count - 1) % count,
+            KeyCode::Enter => {
+                let action = self
+                    .palette_matches()
+                    .get(self.palette)
+                    .map(|(_, action)| *action);
+                self.close_palette();
+                if let Some(action) = action {
+                    self.handle_action(action);
+                }
+            }
+            KeyCode::Esc => self.close_palette(),
+            KeyCode::Char(to_insert) => {
+                self.enter_char(to_insert);
+                self.palette = 0;
+            }
+            KeyCode::Backspace => {
+                self.delete_char();
+                self.palette = 0;
+            }
+            KeyCode::Left => self.move_cursor_left(),
+            KeyCode::Right => self.move_cursor_right(),
+            _ => {}
+        }
+    }
+
+    fn close_palette(&mut self) {
+        self.inputs[PALETTE as usize].buffer.clear();
+        self.reset_cursor();
+        self.set_focus(Focus::Default);
+        self.input_mode = InputMode::Normal;
+    }
+
+    fn date_picker_key(&mut self, code: KeyCode) {
+        let Some(picker) = self.date_picker.as_mut() else {
+            return;
+        };
+        match code {
+            KeyCode::Left | KeyCode::Char('h') => picker.field = picker.field.saturating_sub(1),
+            KeyCode::Right | KeyCode::Char('l') => picker.field = usize::min(picker.field + 1, 4),
+            KeyCode::Up | KeyCode::Char('k') => picker.adjust(1),
+            KeyCode::Down | KeyCode::Char('j') => picker.adjust(-1),
+            KeyCode::Tab => picker.until = !picker.until,
+            KeyCode::Enter => {
+                let clause = if picker.until { "UNTIL" } else { "SINCE" };
+                let literal = picker.literal();
+                let input = &mut self.inputs[QUERY as usize];
+                input.buffer = set_clause(&input.buffer, clause, &literal);
+                input.cursor_position = input.buffer.len();
+                self.date_picker = None;
+                self.set_focus(Focus::QueryInput);
+            }
+            KeyCode::Esc => {
+                self.date_picker = None;
+                self.set_focus(Focus::QueryInput);
+            }
+            _ => {}
+        }
+    }
+
+    // Keys every list view shares: j/k and the arrows move the selection, wrapping at either
+    // end, and take the same gg/G, Ctrl+D/U and count motions as the query list, which stop at
+    // the ends instead. r refreshes, and Esc, q or a key that opens the view closes it. False
+    // for any other key, which is left to the view.
+    fn list_key(
+        &mut self,
+        key: KeyEvent,
+        list: fn(&mut App) -> (&mut ListState, usize),
+        refresh: Option<fn(&mut App)>,
+        open: &[char],
+    ) -> bool {
+        let motion = std::mem::take(&mut self.motion);
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('0') if !ctrl && motion.count.is_none() => return true,
+            KeyCode::Char(c) if !ctrl && c.is_ascii_digit() => {
+                let count = motion.count.unwrap_or(0) * 10 + c.to_digit(10).unwrap_or(0) as usize;
+                self.motion.count = Some(count.min(MAX_COUNT));
+                return true;
+            }
+            KeyCode::Char('g') if !ctrl && !motion.g => {
+                self.motion = Motion { g: true, ..motion };
+                return true;
+            }
+            _ => {}
+        }
+
+        let half_page = (self.view_height as usize / 2).max(1);
+        let (list_state, count) = list(self);
+        let selected = list_state.selected();
+        let i = selected.unwrap_or(0);
+        let last = count.saturating_sub(1);
+        // By position in the list, counting from one
+        let line = |line: usize| line.clamp(1, count.max(1)) - 1;
+        let moved = (count > 0)
+            .then(|| match key.code {
+                KeyCode::Char('d') if ctrl => {
+                    Some(usize::min(i + half_page * motion.count.unwrap_or(1), last))
+                }
+                KeyCode::Char('u') if ctrl => {
+                    Some(i.saturating_sub(half_page * motion.count.unwrap_or(1)))
+                }
+                KeyCode::Down | KeyCode::Char('j') => Some(match motion.count {
+                    Some(by) => usize::min(i + by, last),
+                    None => selected.map_or(0, |i| (i + 1) % count),
+                }),
+                KeyCode::Up | KeyCode::Char('k') => Some(match motion.count {
+                    Some(by) => i.saturating_sub(by),
+                    None => selected.map_or(0, |i| (i + count - 1) % count),
+                }),
+                // 5gg and 5G both go to the fifth row
+                KeyCode::Char('g') if !ctrl => Some(line(motion.count.unwrap_or(1))),
+                KeyCode::Char('G') => Some(line(motion.count.unwrap_or(count))),
+                _ => None,
+            })
+            .flatten();
+        if let Some(moved) = moved {
+            list_state.select(Some(moved));
+            return true;
+        }
+
+        match key.code {
+            KeyCode::Char('r') => match refresh {
+                Some(refresh) => refresh(self),
+                None => return false,
+            },
+            KeyCode::Esc | KeyCode::Char('q') => self.set_focus(Focus::Default),
+            KeyCode::Char(c) if open.contains(&c) => self.set_focus(Focus::Default),
+            _ => return false,
+        }
+        true
+    }
+
+    fn traces_key(&mut self, key: KeyEvent) {
+        if key.code == KeyCode::Esc && self.traces.spans.is_some() {
+            self.traces.spans = None;
+            return;
+        }
+        if self.list_key(
+            key,
+            |app| (&mut app.traces.list_state, app.traces.summaries.len()),
+            Some(App::refresh_traces),
+            &['T'],
+        ) {
+            return;
+        }
+        if key.code == KeyCode::Enter {
+            let trace_id = self
+                .traces
+                .list_state
+                .selected()
+                .and_then(|i| self.traces.summaries.get(i))
+                .map(|summary| summary.trace_id.to_owned());
+            if let Some(trace_id) = trace_id {
+                self.open_trace(&trace_id);
+            }
+        }
+    }
+
+    pub fn handle_console(&mut self, response: Result<String, String>) {
+        self.console.loading = false;
+        self.console.response = match response {
+            Ok(json) => json,
+            Err(e) => e,
+        };
+    }
+
+    fn console_key(&mut self, code: KeyCode) {
+        let console = &mut self.console;
+        match code {
+            KeyCode::Char('e') | KeyCode::Char('i') => self.input_mode = InputMode::Input,
+            KeyCode::Enter => self.handle_action(Action::Submit),
+            KeyCode::Down | KeyCode::Char('j') => console.scroll = console.scroll.saturating_add(1),
+            KeyCode::Up | KeyCode::Char('k') => console.scroll = console.scroll.saturating_sub(1),
+            KeyCode::PageDown => console.scroll = console.scroll.saturating_add(10),
+            KeyCode::PageUp => console.scroll = console.scroll.saturating_sub(10),
+            KeyCode::Esc | KeyCode::Char('C') | KeyCode::Char('q') => {
+                self.set_focus(Focus::Default)
+            }
+            _ => {}
+        }
+    }
+
+    pub fn handle_hosts(&mut self, hosts: Vec<Host>) {
+        self.hosts.loading = false;
+        self.hosts.hosts = hosts;
+        self.hosts.sort();
+        let selected = (!self.hosts.hosts.is_empty()).then_some(0);
+        self.hosts.list_state.select(selected);
+    }
+
+    fn hosts_key(&mut self, key: KeyEvent) {
+        if self.list_key(
+            key,
+            |app| (&mut app.hosts.list_state, app.hosts.hosts.len()),
+            Some(App::refresh_hosts),
+            &['H'],
+        ) {
+            return;
+        }
+        let selected = self.hosts.list_state.selected();
+        match key.code {
+            KeyCode::Char('s') => {
+                self.hosts.sort = (self.hosts.sort + 1) % 4;
+                self.hosts.sort();
+            }
+            KeyCode::Enter if !self.read_only => {
+                let host = selected.and_then(|i| self.hosts.hosts.get(i)).cloned();
+                if let Some(host) = host {
+                    self.add_host_queries(&host);
+                    self.set_focus(Focus::Default);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn refresh_hosts(&mut self) {
+        self.hosts.loading = true;
+        self.backend.fetch_hosts();
+    }
+
+    // Full charts of a host's resource use and its busiest processes
+    fn add_host_queries(&mut self, host: &Host) {
+        let name = &host.name;
+        let hostname = nrql_string(name);
+        let queries = [
+            (
+                "cpu",
+                format!("FROM SystemSample SELECT average(cpuPercent) WHERE hostname = {} SINCE 30 minutes ago UNTIL now LIMIT MAX TIMESERIES", hostname),
+            ),
+            (
+                "memory",
+                format!("FROM SystemSample SELECT average(memoryUsedPercent) WHERE hostname = {} SINCE 30 minutes ago UNTIL now LIMIT MAX TIMESERIES", hostname),
+            ),
+            (
+                "disk",
+                format!("FROM SystemSample SELECT average(diskUsedPercent) WHERE hostname = {} SINCE 30 minutes ago UNTIL now LIMIT MAX TIMESERIES", hostname),
+            ),
+            (
+                "processes",
+                format!("FROM ProcessSample SELECT average(cpuPercent) WHERE hostname = {} FACET processDisplayName SINCE 30 minutes ago UNTIL now LIMIT 10 TIMESERIES", hostname),
+            ),
+        ];
+        self.add_labelled_queries(name, queries);
+    }
+
+    pub fn handle_workload(&mut self, payload: WorkloadPayload) {
+        // Golden signals are added for this many of a workload's applications at most
+        const KEY_ENTITIES: usize = 5;
+        match payload {
+            WorkloadPayload::Workloads(workloads) => {
+                self.workloads.loading = false;
+                self.workloads
+                    .list_state
+                    .select((!workloads.is_empty()).then_some(0));
+                self.workloads.workloads = workloads;
+            }
+            WorkloadPayload::Members(name, Ok(members)) => {
+                let apps = members
+                    .into_iter()
+                    .filter(|member| member.domain == "APM" && member.entity_type == "APPLICATION")
+                    .take(KEY_ENTITIES)
+                    .collect::<Vec<_>>();
+                if apps.is_empty() {
+                    self.workloads.message = Some(self.text.fill(Msg::WorkloadEmpty, &[&name]));
+                    return;
+                }
+                self.workloads.message = None;
+                self.datasets.open_tab(&name);
+                self.select_first();
+                for app in apps {
+                    self.add_entity_queries(&Entity {
+                        guid: app.guid,
+                        name: app.name,
+                        apm_summary: None,
+                    });
+                }
+                self.set_focus(Focus::Default);
+            }
+            WorkloadPayload::Members(_, Err(e)) => self.workloads.message = Some(e),
+        }
+    }
+
+    fn workloads_key(&mut self, key: KeyEvent) {
+        if self.list_key(
+            key,
+            |app| (&mut app.workloads.list_state, app.workloads.workloads.len()),
+            Some(App::refresh_workloads),
+            &['U'],
+        ) {
+            return;
+        }
+        if key.code != KeyCode::Enter || self.read_only {
+            return;
+        }
+        let selected = self.workloads.list_state.selected();
+        if let Some(workload) = selected.and_then(|i| self.workloads.workloads.get(i)) {
+            self.backend.fetch_workload_members(workload);
+            self.workloads.message = Some(self.text.get(Msg::FetchingWorkload).to_owned());
+        }
+    }
+
+    fn refresh_workloads(&mut self) {
+        self.workloads.loading = true;
+        self.backend.fetch_workloads();
+    }
+
+    fn templates_key(&mut self, key: KeyEvent) {
+        if self.list_key(
+            key,
+            |app| (&mut app.templates.list_state, app.config.templates.len()),
+            None,
+            &['Y'],
+        ) {
+            return;
+        }
+        if key.code != KeyCode::Enter || self.read_only {
+            return;
+        }
+        let selected = self.templates.list_state.selected();
+        if let Some(template) = selected.and_then(|i| self.config.templates.get(i)).cloned() {
+            self.templates.filling = Some((template, BTreeMap::new()));
+            self.prompt_template_variable();
+        }
+    }
+
+    // The first variable of the template being filled in without a value yet
+    pub fn template_variable(&self) -> Option<String> {
+        let (template, values) = self.templates.filling.as_ref()?;
+        template
+            .variables()
+            .into_iter()
+            .find(|variable| !values.contains_key(variable))
+    }
+
+    // Prompts for the next variable starting from its last value, or puts the expanded query
+    // in the query box to be checked and run
+    fn prompt_template_variable(&mut self) {
+        let Some((template, values)) = &self.templates.filling else {
+            return;
+        };
+        let (focus, text) = match self.template_variable() {
+            Some(variable) => (
+                Focus::TemplateVariable,
+                self.recent_values
+                    .get(&variable)
+                    .first()
+                    .cloned()
+                    .unwrap_or_default(),
+            ),
+            None => {
+                let query = template.expand(values);
+                self.templates.filling = None;
+                self.save_recent_values();
+                self.query_error = None;
+                (Focus::QueryInput, query)
+            }
+        };
+        self.templates.recent = 0;
+        self.set_focus(focus);
+        self.inputs[focus as usize].cursor_position = text.len();
+        self.inputs[focus as usize].buffer = text;
+        self.input_mode = InputMode::Input;
+    }
+
+    fn cycle_recent(&mut self, older: bool) {
+        let Some(variable) = self.template_variable() else {
+            return;
+        };
+        let recent = self.recent_values.get(&variable);
+        if recent.is_empty() {
+            return;
+        }
+        let i = match older {
+            true => usize::min(self.templates.recent + 1, recent.len() - 1),
+            false => self.templates.recent.saturating_sub(1),
+        };
+        let value = recent[i].to_owned();
+        self.templates.recent = i;
+        self.inputs[TEMPLATE_VARIABLE as usize].cursor_position = value.len();
+        self.inputs[TEMPLATE_VARIABLE as usize].buffer = value;
+    }
+
+    // Kept next to the session file
+    fn save_recent_values(&self) {
+        if self.read_only {
+            return;
+        }
+        let Some(dir) = self.session_path.parent() else {
+            return;
+        };
+        if let Err(e) = self.recent_values.save(&dir.join(RECENT_VALUES)) {
+            warn!("could not save recent template values: {}", e);
+        }
+    }
+
+    fn refresh_slos(&mut self) {
+        self.slos.loading = true;
+        self.backend.fetch_slos();
+    }
+
+    pub fn handle_slos(&mut self, slos: Vec<Slo>) {
+        self.slos.loading = false;
+        self.slos.list_state.select((!slos.is_empty()).then_some(0));
+        self.slos.slos = slos;
+    }
+
+    fn slos_key(&mut self, key: KeyEvent) {
+        if self.list_key(
+            key,
+            |app| (&mut app.slos.list_state, app.slos.slos.len()),
+            Some(App::refresh_slos),
+            &['S'],
+        ) {
+            return;
+        }
+        if key.code != KeyCode::Enter || self.read_only {
+            return;
+        }
+        let selected = self.slos.list_state.selected();
+        if let Some(slo) = selected.and_then(|i| self.slos.slos.get(i)).cloned() {
+            self.add_slo_queries(&slo);
+            self.set_focus(Focus::Default);
+        }
+    }
+
+    // Attainment over the SLO's window with its target drawn in, and the recent burn rate
+    fn add_slo_queries(&mut self, slo: &Slo) {
+        let queries = [
+            ("attainment", slo.attainment_nrql(true)),
+            ("burn rate", slo.burn_nrql()),
+        ];
+        self.add_labelled_queries(&slo.name, queries);
+        let alias = format!("{} attainment", slo.name);
+        if let Some((_, entry)) = self.pending.values_mut().find(|(name, _)| *name == alias) {
+            entry.thresholds = vec![Threshold {
+                value: slo.target,
+                label: "target".to_owned(),
+                colour: "red".to_owned(),
+            }];
+        }
+    }
+
+    fn open_kube(&mut self, path: Vec<String>) {
+        self.kube.path = path.clone();
+        self.kube.rows.clear();
+        self.kube.list_state.select(None);
+        self.kube.loading = true;
+        self.backend.fetch_kube(path);
+    }
+
+    pub fn handle_kube(&mut self, path: Vec<String>, rows: Vec<KubeRow>) {
+        // Moved to another level since this was asked for
+        if path != self.kube.path {
+            return;
+        }
+        self.kube.loading = false;
+        self.kube.rows = rows;
+        let selected = (!self.kube.rows.is_empty()).then_some(0);
+        self.kube.list_state.select(selected);
+    }
+
+    fn kube_key(&mut self, key: KeyEvent) {
+        if self.list_key(
+            key,
+            |app| (&mut app.kube.list_state, app.kube.rows.len()),
+            Some(App::refresh_kube),
+            &['P'],
+        ) {
+            return;
+        }
+        let selected = self.kube.list_state.selected();
+        match key.code {
+            KeyCode::Enter => {
+                let Some(row) = selected.and_then(|i| self.kube.rows.get(i)) else {
+                    return;
+                };
+                let mut path = self.kube.path.clone();
+                path.push(row.name.clone());
+                if path.len() < 3 {
+                    self.open_kube(path);
+                } else if !self.read_only {
+                    self.add_pod_queries(&path[0], &path[1], &path[2]);
+                    self.set_focus(Focus::Default);
+                }
+            }
+            KeyCode::Backspace | KeyCode::Char('h') if !self.kube.path.is_empty() => {
+                let mut path = self.kube.path.clone();
+                path.pop();
+                self.open_kube(path);
+            }
+            _ => {}
+        }
+    }
+
+    fn refresh_kube(&mut self) {
+        self.open_kube(self.kube.path.clone());
+    }
+
+    // A pod's restarts and resource use, and how much it is logging
+    fn add_pod_queries(&mut self, cluster: &str, namespace: &str, pod: &str) {
+        let (namespace, pod_name) = (nrql_string(namespace), nrql_string(pod));
+        let filter = format!(
+            "clusterName = {} AND namespaceName = {} AND podName = {}",
+            nrql_string(cluster),
+            namespace,
+            pod_name
+        );
+        let queries = [
+            (
+                "restarts",
+                format!("FROM K8sContainerSample SELECT max(restartCount) WHERE {} FACET containerName SINCE 30 minutes ago UNTIL now LIMIT MAX TIMESERIES", filter),
+            ),
+            (
+                "cpu cores",
+                format!("FROM K8sContainerSample SELECT average(cpuUsedCores) WHERE {} FACET containerName SINCE 30 minutes ago UNTIL now LIMIT MAX TIMESERIES", filter),
+            ),
+            (
+                "memory",
+                format!("FROM K8sContainerSample SELECT average(memoryWorkingSetBytes) WHERE {} FACET containerName SINCE 30 minutes ago UNTIL now LIMIT MAX TIMESERIES", filter),
+            ),
+            (
+                "logs",
+                format!("FROM Log SELECT count(*) WHERE namespace_name = {} AND pod_name = {} FACET level SINCE 30 minutes ago UNTIL now LIMIT MAX TIMESERIES", namespace, pod_name),
+            ),
+        ];
+        self.add_labelled_queries(pod, queries);
+    }
+
+    fn entities_key(&mut self, key: KeyEvent) {
+        if self.list_key(
+            key,
+            |app| (&mut app.entities.list_state, app.entities.entities.len()),
+            Some(App::refresh_entities),
+            &['E', 'B', 'O'],
+        ) {
+            return;
+        }
+        if key.code != KeyCode::Enter || self.read_only {
+            return;
+        }
+        let entity = self
+            .entities
+            .list_state
+            .selected()
+            .and_then(|i| self.entities.entities.get(i))
+            .cloned();
+        let Some(entity) = entity else {
+            return;
+        };
+        match self.entities.kind {
+            EntityKind::Apm => {
+                self.add_entity_queries(&entity);
+                self.set_focus(Focus::Default);
+            }
+            EntityKind::Browser => {
+                self.add_browser_queries(&entity);
+                self.set_focus(Focus::Default);
+            }
+            EntityKind::Mobile => {
+                self.add_mobile_queries(&entity);
+                self.open_crashes(entity.name);
+            }
+        }
+    }
+
+    fn refresh_entities(&mut self) {
+        self.entities.entities.clear();
+        self.entities.list_state.select(None);
+        self.entities.loading = true;
+        match self.entities.kind {
+            EntityKind::Apm => self.backend.fetch_entities(),
+            EntityKind::Browser => self.backend.fetch_apps("BROWSER"),
+            EntityKind::Mobile => self.backend.fetch_apps("MOBILE"),
+        }
+    }
+
+    // Crash-free users and crashes by app version, in a tab of the app's own
+    fn add_mobile_queries(&mut self, entity: &Entity) {
+        let name = &entity.name;
+        let app = nrql_string(name);
+        // The filter's where is lowercase so the parser doesn't end the SELECT at it
+        let queries = [
+            (
+                "crash-free users",
+                format!("FROM MobileSession, MobileCrash SELECT 100 - percentage(uniqueCount(deviceUuid), where eventType() = 'MobileCrash') WHERE appName = {} SINCE 1 day ago UNTIL now LIMIT MAX TIMESERIES", app),
+            ),
+            (
+                "crashes by version",
+                format!("FROM MobileCrash SELECT count(*) WHERE appName = {} FACET appVersion SINCE 1 day ago UNTIL now LIMIT 10 TIMESERIES", app),
+            ),
+        ];
+        self.datasets.open_tab(name);
+        self.select_first();
+        self.add_labelled_queries(name, queries);
+    }
+
+    fn open_crashes(&mut self, app: String) {
+        self.set_focus(Focus::Crashes);
+        self.crashes.app = app;
+        self.refresh_crashes();
+    }
+
+    fn refresh_crashes(&mut self) {
+        self.crashes.groups.clear();
+        self.crashes.list_state.select(None);
+        self.crashes.loading = true;
+        self.backend.fetch_crashes(&self.crashes.app);
+    }
+
+    pub fn handle_crashes(&mut self, groups: Vec<CrashGroup>) {
+        self.crashes.loading = false;
+        self.crashes
+            .list_state
+            .select((!groups.is_empty()).then_some(0));
+        self.crashes.groups = groups;
+    }
+
+    fn crashes_key(&mut self, key: KeyEvent) {
+        self.list_key(
+            key,
+            |app| (&mut app.crashes.list_state, app.crashes.groups.len()),
+            Some(App::refresh_crashes),
+            &[],
+        );
+    }
+
+    // Core web vitals, slowest pages and JavaScript errors for a browser app, in a tab of its own
+    fn add_browser_queries(&mut self, entity: &Entity) {
+        let name = &entity.name;
+        let app = nrql_string(name);
+        let queries = [
+            (
+                "largest contentful paint",
+                format!("FROM PageViewTiming SELECT percentile(largestContentfulPaint, 75) WHERE appName = {} SINCE 30 minutes ago UNTIL now LIMIT MAX TIMESERIES", app),
+            ),
+            (
+                "interaction to next paint",
+                format!("FROM PageViewTiming SELECT percentile(interactionToNextPaint, 75) WHERE appName = {} SINCE 30 minutes ago UNTIL now LIMIT MAX TIMESERIES", app),
+            ),
+            (
+                "layout shift",
+                format!("FROM PageViewTiming SELECT percentile(cumulativeLayoutShift, 75) WHERE appName = {} SINCE 30 minutes ago UNTIL now LIMIT MAX TIMESERIES", app),
+            ),
+            (
+                "page load",
+                format!("FROM PageView SELECT average(duration) WHERE appName = {} FACET pageUrl SINCE 30 minutes ago UNTIL now LIMIT 10 TIMESERIES", app),
+            ),
+            (
+                "interactions",
+                format!("FROM BrowserInteraction SELECT average(duration) WHERE appName = {} FACET browserInteractionName SINCE 30 minutes ago UNTIL now LIMIT 10 TIMESERIES", app),
+            ),
+            (
+                "js errors",
+                format!("FROM JavaScriptError SELECT count(*) WHERE appName = {} FACET errorMessage SINCE 30 minutes ago UNTIL now LIMIT 10 TIMESERIES", app),
+            ),
+        ];
+        self.datasets.open_tab(name);
+        self.select_first();
+        self.add_labelled_queries(name, queries);
+    }
+
+    // Pre-built timeseries for an APM application, named after it
+    fn add_entity_queries(&mut self, entity: &Entity) {
+        let name = &entity.name;
+        let app = nrql_string(name);
+        let queries = [
+            (
+                "response time",
+                format!("FROM Transaction SELECT average(duration) WHERE appName = {} SINCE 30 minutes ago UNTIL now LIMIT MAX TIMESERIES", app),
+            ),
+            (
+                "throughput",
+                format!("FROM Transaction SELECT rate(count(*), 1 minute) WHERE appName = {} SINCE 30 minutes ago UNTIL now LIMIT MAX TIMESERIES", app),
+            ),
+            (
+                "errors",
+                format!("FROM TransactionError SELECT count(*) WHERE appName = {} SINCE 30 minutes ago UNTIL now LIMIT MAX TIMESERIES", app),
+            ),
+        ];
+        self.add_labelled_queries(name, queries);
+    }
+
+    // Adds each query aliased as "<name> <label>", in the current time range if one is set
+    fn add_labelled_queries<const N: usize>(&mut self, name: &str, queries: [(&str, String); N]) {
+        for (label, nrql) in queries {
+            let Ok(mut query) = nrql.as_str().to_nrql() else {
+                continue;
+            };
+            if let Some(range) = &self.time_range {
+                query.set_time_range(range);
+            }
+            let Ok(key) = query.to_string() else {
+                continue;
+            };
+            if self.datasets.get(&key).is_none() {
+                let alias = format!("{} {}", name, label);
+                self.pending.insert(key, (alias, SessionQuery::default()));
+            }
+            self.add_query(query);
+        }
+        self.dirty = true;
+    }
+
+    fn alerts_key(&mut self, key: KeyEvent) {
+        if self.list_key(
+            key,
+            |app| (&mut app.alerts.list_state, app.alerts.issues.len()),
+            Some(App::refresh_alerts),
+            &['A'],
+        ) {
+            return;
+        }
+        let selected = self.alerts.list_state.selected();
+        let issue = selected.and_then(|i| self.alerts.issues.get(i)).cloned();
+        match key.code {
+            KeyCode::Char('a') => {
+                if let Some(issue) = issue {
+                    self.alerts.message =
+                        Some(self.text.fill(Msg::Acknowledging, &[&issue.issue_id]));
+                    self.backend.acknowledge_issue(&issue.issue_id);
+                }
+            }
+            KeyCode::Enter if !self.read_only => {
+                match issue.and_then(|issue| issue.condition_family_id.first().copied()) {
+                    Some(condition) => {
+                        self.alerts.message =
+                            Some(self.text.get(Msg::FetchingCondition).to_owned());
+                        self.backend.fetch_condition(condition);
+                    }
+                    None => self.alerts.message = Some(self.text.get(Msg::NoCondition).to_owned()),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn refresh_alerts(&mut self) {
+        self.alerts.loading = true;
+        self.backend.fetch_issues();
+    }
+
+    fn import_key(&mut self, key: KeyEvent) {
+        if self.list_key(
+            key,
+            |app| (&mut app.import.list_state, app.import.dashboards.len()),
+            Some(App::refresh_import),
+            &['I'],
+        ) {
+            return;
+        }
+        if key.code != KeyCode::Enter {
+            return;
+        }
+        let guid = self
+            .import
+            .list_state
+            .selected()
+            .and_then(|i| self.import.dashboards.get(i))
+            .map(|dashboard| dashboard.guid.to_owned());
+        if let Some(guid) = guid {
+            self.import.message = Some(self.text.get(Msg::FetchingDashboard).to_owned());
+            self.backend.fetch_dashboard(&guid);
+        }
+    }
+
+    fn refresh_import(&mut self) {
+        self.import.loading = true;
+        self.backend.fetch_dashboards();
+    }
+
+    // Queues every widget query as a named dataset, loaded like a session
+    fn import_dashboard(&mut self, dashboard: Dashboard) {
+        let fallback = TimeRange::last(TimeRange::PRESETS[0]);
+        let (mut imported, mut skipped) = (0, 0);
+        for widget in dashboard.pages.iter().flat_map(|page| &page.widgets) {
+            let title = widget.title.clone().unwrap_or_default();
+            for (i, nrql) in widget.queries().iter().enumerate() {
+                let query = reorder_nrql(nrql, &fallback).and_then(|q| q.as_str().to_nrql().ok());
+                let Some(mut query) = query else {
+                    skipped += 1;
+                    continue;
+                };
+                if let Some(range) = &self.time_range {
+                    query.set_time_range(range);
+                }
+                let Ok(key) = query.to_string() else {
+                    skipped += 1;
+                    continue;
+                };
+                if self.datasets.get(&key).is_some() || self.pending.contains_key(&key) {
+                    skipped += 1;
+                    continue;
+                }
+
+                let alias = match i {
+                    0 => format!("{} / {}", dashboard.name, title),
+                    _ => format!("{} / {} ({})", dashboard.name, title, i + 1),
+                };
+                self.datasets.reserve(&key, false);
+                self.pending.insert(key, (alias, SessionQuery::default()));
+                self.loading.push_back(DataSource::Nrql(Box::new(query)));
+                self.load_total += 1;
+                imported += 1;
+            }
+        }
+
+        if imported == 0 {
+            self.import.message = Some(self.text.fill(
+                Msg::NothingImported,
+                &[&dashboard.name, &skipped.to_string()],
+            ));
+            return;
+        }
+        self.dirty = true;
+        self.import.message = None;
+        self.set_focus(Focus::Default);
+    }
+
+    // Sends every NRQL dataset, under its alias, to a New Relic dashboard
+    fn export_dashboard(&mut self) {
+        let name = self.input_buffer(EXPORT).trim().to_owned();
+        if name.is_empty() {
+            return;
+        }
+        let widgets = self
+            .datasets
+            .iter()
+            .filter(|(key, _)| !key.starts_with(FILE_PREFIX))
+            .filter_map(|(key, data)| {
+                let query = parse_key(key).ok()?.with_variables(&self.variables);
+                let nrql = query.nrql();
+                let title = data.query_alias.clone().unwrap_or(nrql.clone());
+                Some((title, nrql, query.account))
+            })
+            .collect::<Vec<_>>();
+        if widgets.is_empty() {
+            self.export_message = Some(self.text.get(Msg::NothingToExport).to_owned());
+            return;
+        }
+
+        // Re-exporting under the same name updates that dashboard rather than creating another
+        let guid = self
+            .exported
+            .as_ref()
+            .filter(|(exported, _)| *exported == name)
+            .map(|(_, guid)| guid.to_owned());
+        self.export_message = Some(self.text.get(Msg::Exporting).to_owned());
+        self.backend.export_dashboard(guid, name, widgets);
+    }
+
+    fn next_layout(&mut self) {
+        let count = self.config.layouts.len();
+        if count == 0 {
+            return;
+        }
+        let i = self.layout.map_or(0, |i| (i + 1) % count);
+        let preset = self.config.layouts[i].clone();
+
+        match preset.view {
+            View::Graph => self.set_focus(Focus::Default),
+            View::Dashboard | View::Expanded => self.set_focus(Focus::Dashboard),
+        }
+        self.expanded = preset.view == View::Expanded;
+        self.side_width = preset.side_width.clamp(5, 60);
+
+        let selected = match preset.select.as_deref() {
+            Some("pinned") => self
+                .datasets
+                .keys()
+                .find(|key| self.datasets.is_pinned(key))
+                .cloned(),
+            Some(select) => self
+                .datasets
+                .iter()
+                .find(|(key, data)| *key == select || data.query_alias.as_deref() == Some(select))
+                .map(|(key, _)| key.to_owned()),
+            None => None,
+        };
+        if let Some(key) = selected {
+            self.selected_query = key;
+            self.sync_selection();
+        }
+        self.layout = Some(i);
+    }
+
+    fn refresh_traces(&mut self) {
+        let range = self
+            .time_range
+            .clone()
+            .unwrap_or_else(|| TimeRange::last(TimeRange::PRESETS[0]));
+        self.traces.loading = true;
+        self.backend.fetch_traces(&range);
+    }
+
+    // Entry point for drilling into a trace from anywhere that knows its trace.id
+    pub fn open_trace(&mut self, trace_id: &str) {
+        self.set_focus(Focus::Traces);
+        self.traces.spans = Some((trace_id.to_owned(), vec![]));
+        self.traces.loading = true;
+        self.backend.fetch_spans(trace_id);
+    }
+
+    fn dispatch_load(&mut self) {
+        if self.last_load.elapsed() < LOAD_INTERVAL {
+            return;
+        }
+        match self.loading.pop_front() {
+            Some(source) => {
+                self.add_source(source);
+                self.last_load = Instant::now();
+                self.redraw = true;
+            }
+            None => self.load_total = 0,
+        }
+    }
+
+    // Whether any query is waiting on its first result or on a request
+    pub fn fetching(&self) -> bool {
+        !self.pending.is_empty()
+            || self
+                .datasets
+                .keys()
+                .any(|key| self.backend.diagnostics.in_flight(key).is_some())
+    }
+
+    // Seconds until the selected query next refreshes, None when it won't on a timer
+    pub fn next_refresh(&self) -> Option<u64> {
+        if self.selected_query.is_empty() || self.selected_query.starts_with(FILE_PREFIX) {
+            return None;
+        }
+        // Mirrors the schedule in backend::refresh_timeseries
+        let now = Utc::now().timestamp() as u64;
+        let interval = match self.idle {
+            true if self.config.idle_refresh == 0 => return None,
+            true => self.config.idle_refresh,
+            false => self.config.refresh_interval,
+        };
+        Some((interval - now % interval) % interval)
+    }
+
+    // Account the selected query runs against
+    pub fn active_account(&self) -> String {
+        parse_key(&self.selected_query)
+            .ok()
+            .and_then(|query| query.account)
+            .map(|account| account.to_string())
+            .unwrap_or_else(|| self.backend.client.account_id())
+    }
+
+    fn reload_config(&mut self) {
+        let Some(watch) = &self.config_watch else {
+            return;
+        };
+        if watch.changed() {
+            let loaded = Config::read(&watch.path);
+            self.apply_config(loaded);
+        }
+    }
+
+    // The account, API key, language, cache and session directory still need a restart
+    pub fn apply_config(&mut self, loaded: Result<Config>) {
+        self.redraw = true;
+        let config = match loaded {
+            Ok(config) => config,
+            Err(e) => {
+                warn!("config not reloaded: {}", e);
+                let problems = e.to_string().lines().collect::<Vec<_>>().join(" ");
+                let message = self.text.fill(Msg::ConfigInvalid, &[&problems]);
+                self.flash = Some((message, Color::Red, Instant::now()));
+                return;
+            }
+        };
+        if config.refresh_interval != self.config.refresh_interval {
+            self.backend
+                .send(UIEvent::SetRefreshInterval(config.refresh_interval));
+        }
+        if (config.anomaly_window, config.anomaly_sigmas)
+            != (self.config.anomaly_window, self.config.anomaly_sigmas)
+        {
+            self.backend.send(UIEvent::SetAnomalies(
+                config.anomaly_window,
+                config.anomaly_sigmas,
+            ));
+        }
+        if config.timezone != self.config.timezone {
+            self.timezone = config.timezone();
+        }
+        if config.locale != self.config.locale {
+            self.numbers = config.number_format();
+        }
+        self.theme = Theme::new(config.palette());
+        self.config = config;
+        let message = self.text.get(Msg::ConfigReloaded).to_owned();
+        self.flash = Some((message, Color::Green, Instant::now()));
+    }
+
+    fn check_idle(&mut self) {
+        let timeout = self.config.idle_timeout;
+        if !self.idle && timeout > 0 && self.last_input.elapsed().as_secs() >= timeout {
+            self.backend.send(UIEvent::Idle(self.config.idle_refresh));
+            self.idle = true;
+            self.redraw = true;
+        }
+    }
+
+    fn wake(&mut self) {
+        self.last_input = Instant::now();
+        if self.idle {
+            self.backend.send(UIEvent::Active);
+            self.idle = false;
+        }
+    }
+
+    // Queries keep $name as written, the backend re-issues those using a changed variable
+    fn set_variables(&mut self, variables: Variables) {
+        if variables == self.variables {
+            return;
+        }
+        self.backend.send(UIEvent::SetVariables(variables.clone()));
+        self.variables = variables;
+        self.dirty = true;
+    }
+
+    fn set_time_range(&mut self, range: TimeRange) {
+        self.backend.send(UIEvent::SetTimeRange(range.clone()));
+
+        // Running tasks rewrite their own queries, so re-key everything to match
+        let retime = |key: &str| {
+            parse_key(key)
+                .ok()
+                .and_then(|mut query| query.set_time_range(&range).to_string().ok())
+                .unwrap_or(key.to_owned())
+        };
+        let keys = self
+            .datasets
+            .all_keys()
+            .chain(
+                self.pending
+                    .keys()
+                    .filter(|k| self.datasets.get(k).is_none()),
+            )
+            .map(|key| retime(key))
+            .collect::<Vec<_>>();
+        self.datasets.rekey_all(retime);
+        // Queries that only differed by time range now share a task, so release the extras
+        let mut seen = BTreeSet::new();
+        for key in keys {
+            if !seen.insert(key.to_owned()) {
+                self.backend.send(UIEvent::DeleteQuery(key));
+            }
+        }
+        self.pending = std::mem::take(&mut self.pending)
+            .into_iter()
+            .map(|(key, entry)| (retime(&key), entry))
+            .collect();
+        self.loading.iter_mut().for_each(|source| {
+            if let DataSource::Nrql(query) = source {
+                query.set_time_range(&range);
+            }
+        });
+        self.selected_query = retime(&self.selected_query);
+        self.sync_selection();
+        self.time_range = Some(range);
+        self.dirty = true;
+    }
+
+    // Grows the selection leftwards by `grow` steps and shifts it by `pan` steps
+    fn scrub_chart(&mut self, grow: f64, pan: f64) {
+        let Some(data) = self.datasets.get(&self.selected_query) else {
+            return;
+        };
+        let (min_x, max_x) = (data.bounds.mins.0, Utc::now().timestamp() as f64);
+        if min_x >= max_x {
+            return;
+        }
+        let step = (max_x - min_x) / 20.0;
+
+        let (start, end) = self.scrub.unwrap_or((max_x, max_x));
+        let end = end.clamp(min_x, max_x);
+        let start = (start - grow * step).clamp(min_x, end);
+        let shift = (pan * step).clamp(min_x - start, max_x - end);
+
+        self.scrub = Some((start + shift, end + shift));
+    }
+
+    fn zoom_in(&mut self) {
+        let Some((start, end)) = self.scrub.take() else {
+            return;
+        };
+        let Ok(mut query) = parse_key(&self.selected_query) else {
+            return;
+        };
+        if end - start < 1.0 {
+            return;
+        }
+
+        // Aim for roughly 60 buckets across the selected window
+        let bucket = f64::max((end - start) / 60.0, 1.0) as i64;
+        query
+            .set_since(((start * 1000.0) as i64).to_string())
+            .set_until(((end * 1000.0) as i64).to_string());
+        query.mode = format!("TIMESERIES {} seconds", bucket);
+
+        let previous = self.selected_query.to_owned();
+        let Some(key) = self.replace_query(&previous, query) else {
+            return;
+        };
+        if let Some(data) = self.datasets.get_mut(&key) {
+            data.history.push(previous);
+        }
+    }
+
+    fn zoom_out(&mut self) {
+        let Some(previous) = self
+            .datasets
+            .get_mut(&self.selected_query)
+            .and_then(|data| data.history.pop())
+        else {
+            return;
+        };
+        if let Ok(query) = parse_key(&previous) {
+            let current = self.selected_query.to_owned();
+            self.replace_query(&current, query);
+        }
+        self.scrub = None;
+    }
+
+    // Swaps a running query for a new one, carrying its dataset over to the new key
+    fn replace_query(&mut self, old: &str, query: NRQLQuery) -> Option<String> {
+        let key = query.to_string().ok()?;
+        self.backend.send(UIEvent::DeleteQuery(old.to_owned()));
+        self.datasets.rekey(old, key.to_owned());
+        if self.selected_query == old {
+            self.selected_query = key.to_owned();
+        }
+        self.sync_selection();
+        self.add_query(query);
+        Some(key)
+    }
+
+    fn switch_tab(&mut self, forward: bool) {
+        self.datasets.select_tab(forward);
+        self.select_first();
+    }
+
+    fn rename_tab(&mut self) {
+        let name = self.datasets.tabs()[self.datasets.current_tab()].to_owned();
+        self.set_focus(Focus::TabName);
+        self.inputs[TAB_NAME as usize].cursor_position = name.len();
+        self.inputs[TAB_NAME as usize].buffer = name;
+        self.input_mode = InputMode::Input;
+    }
+
+    // Selects the top query of the current tab, if it has any
+    fn select_first(&mut self) {
+        self.selected_query = self.datasets.keys().next().cloned().unwrap_or_default();
+        self.sync_selection();
+        self.scrub = None;
+        self.expanded = false;
+    }
+
+    fn sync_selection(&mut self) {
+        let i = self.datasets.position(&self.selected_query);
+        self.list_state.select(i);
+    }
+
+    fn toggle_pin(&mut self) {
+        if self.datasets.get(&self.selected_query).is_some() {
+            self.datasets.toggle_pin(&self.selected_query);
+            self.sync_selection();
+            self.dirty = true;
+        }
+    }
+
+    fn shift_query(&mut self, up: bool) {
+        if self.datasets.get(&self.selected_query).is_some() {
+            self.datasets.shift(&self.selected_query, up);
+            self.sync_selection();
+            self.dirty = true;
+        }
+    }
+
+    pub fn input_buffer(&self, focus: isize) -> &str {
+        self.inputs[focus as usize].buffer.as_str()
+    }
+
+    fn add_query(&mut self, query: NRQLQuery) {
+        self.add_source(DataSource::Nrql(Box::new(query)));
+    }
+
+    fn add_source(&mut self, source: DataSource) {
+        // Identical queries share one dataset, so just select the existing one
+        let key = source.key();
+        if self.backend.is_running(&key) {
+            // Pull it into the current tab, so it can be selected
+            self.datasets.assign(&key, self.datasets.current_tab());
+            self.selected_query = key;
+            self.sync_selection();
+            return;
+        }
+        // Keeps it in the tab it was added from, even if the first payload arrives after a switch
+        self.datasets.reserve(&key, false);
+        self.backend.add_source(source);
+    }
+
+    fn clamp_cursor(&self, new_cursor_pos: usize) -> usize {
+        new_cursor_pos.clamp(0, self.inputs[self.focus as usize].buffer.len())
+    }
+
+    fn reset_cursor(&mut self) {
+        self.inputs[self.focus as usize].cursor_position = 0;
+    }
+
+    fn move_cursor_left(&mut self) {
         let cursor_moved_left = self.inputs[self.focus as usize]
             .cursor_position
             .saturating_sub(1);
@@ -350,20 +3040,22 @@ impl App {
     }
 
     pub fn delete(&mut self) {
-        let i = self.list_state.selected().unwrap();
-        let to_delete = self
-            .datasets
-            .keys()
-            .nth(i)
-            .cloned()
-            .expect("ERROR: Could not index query for deletion!");
+        let Some(i) = self.list_state.selected() else {
+            return;
+        };
+        let Some(to_delete) = self.datasets.keys().nth(i).cloned() else {
+            return;
+        };
 
-        let (removed, _) = self.datasets.remove_entry(&to_delete).unwrap();
-        // TODO: Fix deleted queries reappearing on new data!
-        _ = self.backend.ui_tx.send(removed);
+        self.datasets.remove(&to_delete);
+        self.dirty = true;
+        self.backend.send(UIEvent::DeleteQuery(to_delete));
     }
 
     pub fn next(&mut self) {
+        if self.datasets.is_empty() {
+            return;
+        }
         let i = match self.list_state.selected() {
             Some(i) => {
                 if i >= self.datasets.len() - 1 {
@@ -374,16 +3066,13 @@ impl App {
             }
             None => 0,
         };
-        self.list_state.select(Some(i));
-        self.selected_query = self
-            .datasets
-            .keys()
-            .nth(i)
-            .expect("ERROR: Could not select query!")
-            .to_owned();
+        self.select(i);
     }
 
     pub fn previous(&mut self) {
+        if self.datasets.is_empty() {
+            return;
+        }
         let i = match self.list_state.selected() {
             Some(i) => {
                 if i == 0 {
@@ -394,32 +3083,581 @@ impl App {
             }
             None => 0,
         };
-        self.list_state.select(Some(i));
-        self.selected_query = self
-            .datasets
-            .keys()
-            .nth(i)
-            .expect("ERROR: Could not select query!")
-            .to_owned();
+        self.select(i);
     }
 
-    // TODO: Prompt user for session save on exit (q)
-    pub fn save_session(&self) {
-        let output = self
-            .datasets
+    // Moves by a number of queries, stopping at either end rather than wrapping
+    fn move_selection(&mut self, by: isize) {
+        if self.datasets.is_empty() {
+            return;
+        }
+        let last = self.datasets.len() as isize - 1;
+        let i = self.list_state.selected().unwrap_or(0) as isize;
+        self.select((i + by).clamp(0, last) as usize);
+    }
+
+    // By position in the list, counting from one
+    fn select_line(&mut self, line: usize) {
+        if !self.datasets.is_empty() {
+            self.select(line.clamp(1, self.datasets.len()) - 1);
+        }
+    }
+
+    fn select(&mut self, i: usize) {
+        if let Some(key) = self.datasets.keys().nth(i) {
+            self.selected_query = key.to_owned();
+            self.list_state.select(Some(i));
+        }
+    }
+
+    pub fn open_links(&self) {
+        if let Some(data) = self.datasets.get(&self.selected_query) {
+            data.links.iter().for_each(|link| open_url(link));
+        }
+    }
+
+    pub fn open_query_builder(&self) {
+        if self.selected_query.starts_with(FILE_PREFIX) {
+            return;
+        }
+        let Ok(query) = parse_key(&self.selected_query) else {
+            return;
+        };
+        let account = query
+            .account
+            .map(|account| account.to_string())
+            .unwrap_or_else(|| self.backend.client.account_id());
+        open_url(&format!(
+            "{}?account={}&query={}",
+            QUERY_BUILDER,
+            account,
+            encode_component(&query.with_variables(&self.variables).nrql())
+        ));
+    }
+
+    pub fn save_session(&mut self) -> Result<()> {
+        if self.read_only {
+            return Ok(());
+        }
+        // Grouped by tab, so positions also record the tab order
+        let datasets = &self.datasets;
+        let tabs = datasets.tabs();
+        let output = tabs
             .iter()
-            .map(|(q, data)| {
+            .flat_map(|tab| datasets.all().filter(move |(q, _)| datasets.tab(q) == *tab))
+            .enumerate()
+            .map(|(position, (q, data))| {
                 (
                     data.query_alias.clone().unwrap_or(q.to_owned()),
-                    q.to_owned(),
+                    SessionQuery {
+                        query: q.to_owned(),
+                        note: data.note.to_owned(),
+                        links: data.links.clone(),
+                        position,
+                        pinned: datasets.is_pinned(q),
+                        style: data.style,
+                        axis: data.axis.clone(),
+                        thresholds: data.thresholds.clone(),
+                        tab: match tabs.len() {
+                            1 => String::new(),
+                            _ => datasets.tab(q).to_owned(),
+                        },
+                    },
                 )
             })
-            .collect::<BTreeMap<String, String>>();
+            .collect::<Session>();
+
+        let yaml: String = serde_yaml::to_string(&output)
+            .map_err(|e| anyhow!("Could not serialize queries: {}", e))?;
+        File::create(&self.session_path)
+            .and_then(|mut file| file.write_all(yaml.as_bytes()))
+            .map_err(|e| anyhow!("Could not write {}: {}", self.session_path.display(), e))?;
+        self.save_variables()?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    // Removed once the last variable is, so they don't come back on the next start
+    fn save_variables(&self) -> Result<()> {
+        let Some(dir) = self.session_path.parent() else {
+            return Ok(());
+        };
+        let path = dir.join(VARIABLES_FILE);
+        if self.variables.is_empty() {
+            _ = fs::remove_file(path);
+            return Ok(());
+        }
+        let yaml = serde_yaml::to_string(&self.variables)
+            .map_err(|e| anyhow!("Could not serialize variables: {}", e))?;
+        fs::write(&path, yaml).map_err(|e| anyhow!("Could not write {}: {}", path.display(), e))
+    }
+
+    // Shown over the current view until the next key press
+    pub fn show_error(&mut self, e: anyhow::Error) {
+        error!("{}", e);
+        self.error = Some(e.to_string());
+    }
+}
+
+// Terminal events are read on their own thread, so the UI loop only wakes when one arrives
+fn read_input(wakeup: &Wakeup) -> Receiver<Event> {
+    let (tx, rx) = channel(wakeup);
+    thread::spawn(move || {
+        while let Ok(event) = event::read() {
+            if tx.send(event).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+// Restores the shell's terminal, stops until SIGCONT (fg), then takes the terminal back and
+// redraws everything, as the shell will have drawn over the screen
+#[cfg(unix)]
+fn suspend<B: Backend>(terminal: &mut Terminal<B>) -> io::Result<()> {
+    let mut stdout = std::io::stdout();
+    disable_raw_mode()?;
+    stdout.execute(LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+    // SAFETY: raise only sends a signal to this process
+    unsafe {
+        libc::raise(libc::SIGTSTP);
+    }
+    stdout.execute(EnterAlternateScreen)?;
+    enable_raw_mode()?;
+    terminal.clear()
+}
+
+#[cfg(not(unix))]
+fn suspend<B: Backend>(_terminal: &mut Terminal<B>) -> io::Result<()> {
+    Ok(())
+}
+
+pub fn open_url(url: &str) {
+    let opener = if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    if let Err(e) = Command::new(opener)
+        .arg(url)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+    {
+        warn!("could not open {} with {}: {}", url, opener, e);
+    }
+}
+
+// Percent-encodes everything outside the unreserved set of RFC 3986
+fn encode_component(input: &str) -> String {
+    input
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+pub fn parse_key(key: &str) -> Result<NRQLQuery> {
+    key.trim().to_nrql()
+}
+
+// Case-insensitive subsequence match, scored by the gaps between matched characters so
+// tighter matches sort first. None when the pattern doesn't match.
+fn fuzzy_score(pattern: &str, text: &str) -> Option<usize> {
+    let text = text.to_lowercase();
+    let mut chars = text.chars().enumerate();
+    let mut score = 0;
+    let mut last = None;
+    for wanted in pattern
+        .to_lowercase()
+        .chars()
+        .filter(|c| !c.is_whitespace())
+    {
+        let (i, _) = chars.find(|(_, c)| *c == wanted)?;
+        score += match last {
+            Some(last) => i - last - 1,
+            None => i,
+        };
+        last = Some(i);
+    }
+    Some(score)
+}
+
+// Input starting with FILE loads a local series instead of running NRQL
+fn parse_source(input: &str) -> Result<DataSource> {
+    let input = input.trim();
+    match input.get(..FILE_PREFIX.len()) {
+        Some(prefix) if prefix.eq_ignore_ascii_case(FILE_PREFIX) => Ok(DataSource::File(
+            PathBuf::from(input[FILE_PREFIX.len()..].trim()),
+        )),
+        // Queries written as in New Relic, SELECT first, are rearranged for the parser
+        _ if input.to_ascii_uppercase().starts_with("SELECT ") => parse_key(input)
+            .or_else(|e| {
+                reorder_nrql(input, &TimeRange::last(TimeRange::PRESETS[0]))
+                    .and_then(|nrql| nrql.as_str().to_nrql().ok())
+                    .ok_or(e)
+            })
+            .map(|query| DataSource::Nrql(Box::new(query))),
+        _ => parse_key(input).map(|query| DataSource::Nrql(Box::new(query))),
+    }
+}
+
+fn parse_time_range(input: &str) -> Option<TimeRange> {
+    let input = input.trim();
+    if let Ok(i) = input.parse::<usize>() {
+        return TimeRange::PRESETS
+            .get(i.wrapping_sub(1))
+            .map(|preset| TimeRange::last(preset));
+    }
+    match input.split_once("UNTIL") {
+        Some((since, until)) => Some(TimeRange {
+            since: since.trim().to_owned(),
+            until: until.trim().to_owned(),
+        }),
+        None if !input.is_empty() => Some(TimeRange {
+            since: input.to_owned(),
+            until: "now".to_owned(),
+        }),
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use ratatui::style::{palette::tailwind, Color};
+
+    use super::*;
+    use crate::testing::{add_query, app, pending_aliases, recv};
+
+    #[test]
+    fn vim_motions_move_through_the_query_list() {
+        let mut app = app(Ok(vec![]));
+        for app_name in ["a", "b", "c", "d", "e", "f"] {
+            add_query(&mut app, &format!("FROM Transaction SELECT count(*) WHERE appName = '{}' SINCE 30 minutes ago UNTIL now LIMIT MAX TIMESERIES", app_name));
+        }
+        let mut keys = |keys: &str| {
+            for c in keys.chars() {
+                app.handle_key(KeyEvent::from(KeyCode::Char(c)));
+            }
+            app.list_state.selected()
+        };
+        assert_eq!(keys("gg"), Some(0));
+        assert_eq!(keys("3j"), Some(3));
+        assert_eq!(keys("10j"), Some(5));
+        assert_eq!(keys("2k"), Some(3));
+        assert_eq!(keys("G"), Some(5));
+        assert_eq!(keys("2G"), Some(1));
+        assert_eq!(keys("4gg"), Some(3));
+        // A count only applies to the key after it
+        assert_eq!(keys("2fj"), Some(4));
+
+        app.list_height = 4;
+        app.handle_action(Action::Go);
+        app.handle_action(Action::Go);
+        app.handle_key(KeyEvent::new(KeyCode::Char('d'), KeyModifiers::CONTROL));
+        assert_eq!(app.list_state.selected(), Some(2));
+        app.handle_key(KeyEvent::new(KeyCode::Char('u'), KeyModifiers::CONTROL));
+        assert_eq!(app.list_state.selected(), Some(0));
+    }
+
+    #[test]
+    fn duplicates_start_from_the_selected_query() {
+        let mut app = app(Ok(vec![]));
+        add_query(
+            &mut app,
+            "FROM Transaction SELECT count(*) WHERE appName = 'api' SINCE 30 minutes ago UNTIL now LIMIT MAX TIMESERIES",
+        );
+        let original = app.selected_query.to_owned();
+        app.handle_key(KeyEvent::from(KeyCode::Char('D')));
+        assert!(app.focus == Focus::QueryInput);
+        assert_eq!(app.input_buffer(QUERY), original);
+
+        let changed = original.replace("'api'", "'web'");
+        app.inputs[QUERY as usize].cursor_position = changed.len();
+        app.inputs[QUERY as usize].buffer = changed;
+        app.handle_action(Action::Submit);
+        let payload = recv(&app.backend.data_rx);
+        app.handle_payload(payload);
+        assert_eq!(app.datasets.len(), 2);
+        assert!(app.datasets.get(&original).is_some());
+    }
+
+    #[test]
+    fn list_views_wrap_their_selection_and_close() {
+        let mut app = app(Ok(vec![]));
+        app.set_focus(Focus::Traces);
+        app.traces.summaries = vec![TraceSummary::default(); 3];
+        app.traces.spans = Some((String::new(), vec![]));
+        let mut key = |code: KeyCode| {
+            app.handle_key(KeyEvent::from(code));
+            app.traces.list_state.selected()
+        };
+        assert_eq!(key(KeyCode::Char('j')), Some(0));
+        assert_eq!(key(KeyCode::Char('k')), Some(2));
+        assert_eq!(key(KeyCode::Down), Some(0));
+
+        // Backs out of the trace being viewed first
+        app.handle_key(KeyEvent::from(KeyCode::Esc));
+        assert!(app.traces.spans.is_none());
+        assert!(app.focus == Focus::Traces);
+        app.handle_key(KeyEvent::from(KeyCode::Char('T')));
+        assert!(app.focus == Focus::Default);
+    }
+
+    #[test]
+    fn list_views_take_counts_and_jumps() {
+        let mut app = app(Ok(vec![]));
+        app.set_focus(Focus::Traces);
+        app.traces.summaries = vec![TraceSummary::default(); 10];
+        app.view_height = 6;
+        let mut keys = |keys: &str| {
+            for c in keys.chars() {
+                app.handle_key(KeyEvent::from(KeyCode::Char(c)));
+            }
+            app.traces.list_state.selected()
+        };
+        assert_eq!(keys("3j"), Some(3));
+        // Counted moves stop at either end rather than wrapping
+        assert_eq!(keys("20j"), Some(9));
+        assert_eq!(keys("20k"), Some(0));
+        assert_eq!(keys("k"), Some(9));
+        assert_eq!(keys("gg"), Some(0));
+        assert_eq!(keys("G"), Some(9));
+        assert_eq!(keys("4G"), Some(3));
+        assert_eq!(keys("2gg"), Some(1));
 
-        let yaml: String =
-            serde_yaml::to_string(&output).expect("ERROR: Could not serialize queries!");
-        let mut file = File::open("").expect("ERROR: Could not open file!");
-        file.write_all(yaml.as_bytes())
-            .expect("ERROR: Could not write to file!");
+        let mut ctrl = |c: char| {
+            app.handle_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL));
+            app.traces.list_state.selected()
+        };
+        assert_eq!(ctrl('d'), Some(4));
+        assert_eq!(ctrl('d'), Some(7));
+        assert_eq!(ctrl('d'), Some(9));
+        assert_eq!(ctrl('u'), Some(6));
+        assert!(app.focus == Focus::Traces);
+    }
+
+    #[test]
+    fn config_reloads_apply_live_or_report_problems() {
+        let mut app = app(Ok(vec![]));
+        let config = Config::default();
+        app.apply_config(config.validate().map(|_| Config {
+            theme: "sky".to_owned(),
+            keybindings: [("traces".to_owned(), "w".to_owned())].into(),
+            ..config
+        }));
+        let (message, colour, _) = app.flash.clone().unwrap();
+        assert_eq!(
+            (message.as_str(), colour),
+            ("Config reloaded", Color::Green)
+        );
+        assert_eq!(app.theme.focus_fg, tailwind::SKY.c500);
+        app.handle_key(KeyEvent::from(KeyCode::Char('w')));
+        assert!(app.focus == Focus::Traces);
+        app.handle_action(Action::Cancel);
+
+        let invalid = Config {
+            theme: "mauve".to_owned(),
+            ..Config::default()
+        };
+        app.apply_config(invalid.validate().map(|_| invalid));
+        let (message, colour, _) = app.flash.clone().unwrap();
+        assert_eq!(
+            (message.as_str(), colour),
+            (
+                "Config not reloaded: theme: unknown theme mauve, expected one of blue, emerald, indigo, red, amber, rose, lime, fuchsia, sky",
+                Color::Red
+            )
+        );
+        assert_eq!(app.config.theme, "sky");
+    }
+
+    #[test]
+    fn hosts_sort_by_usage_and_open_their_charts() {
+        let row = |host: &str, end: f64, cpu: f64, memory: f64| {
+            serde_json::from_value::<server::timeseries::TimeseriesResult>(serde_json::json!({
+                "beginTimeSeconds": end - 60.0,
+                "endTimeSeconds": end,
+                "facet": host,
+                "cpu": cpu,
+                "memory": memory,
+            }))
+            .unwrap()
+        };
+        let mut app = app(Ok(vec![
+            row("alpha", 60.0, 10.0, 80.0),
+            row("alpha", 120.0, 20.0, 85.0),
+            row("beta", 60.0, 50.0, 30.0),
+            row("beta", 120.0, 90.0, 35.0),
+        ]));
+        app.handle_action(Action::Hosts);
+        assert!(app.focus == Focus::Hosts);
+        let hosts = recv(&app.backend.host_rx);
+        app.handle_hosts(hosts);
+        assert!(!app.hosts.loading);
+
+        app.handle_key(KeyEvent::from(KeyCode::Char('s')));
+        assert_eq!(app.hosts.hosts[0].name, "beta");
+        app.handle_key(KeyEvent::from(KeyCode::Char('s')));
+        assert_eq!(app.hosts.hosts[0].name, "alpha");
+
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+        assert!(app.focus == Focus::Default);
+        let aliases = pending_aliases(&app);
+        assert!(aliases.contains(&"alpha cpu"));
+        assert!(aliases.contains(&"alpha processes"));
+    }
+
+    #[test]
+    fn kubernetes_drills_down_and_back_up() {
+        let row = |name: &str| {
+            serde_json::from_value::<server::timeseries::TimeseriesResult>(serde_json::json!({
+                "facet": name,
+                "restarts": 4,
+            }))
+            .unwrap()
+        };
+        let mut app = app(Ok(vec![row("prod"), row("staging")]));
+        let reply = |app: &mut App| {
+            let (path, rows) = recv(&app.backend.kube_rx);
+            app.handle_kube(path, rows);
+        };
+        app.handle_action(Action::Kubernetes);
+        assert!(app.focus == Focus::Kubernetes);
+        reply(&mut app);
+
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+        assert!(app.kube.loading);
+        reply(&mut app);
+        // A late reply for the level just left is dropped
+        app.handle_kube(vec![], vec![]);
+        assert_eq!(app.kube.rows.len(), 2);
+        app.handle_key(KeyEvent::from(KeyCode::Char('j')));
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+        assert_eq!(app.kube.path, vec!["prod", "staging"]);
+        reply(&mut app);
+
+        app.handle_key(KeyEvent::from(KeyCode::Backspace));
+        assert_eq!(app.kube.path, vec!["prod"]);
+        reply(&mut app);
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+        reply(&mut app);
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+        assert!(app.focus == Focus::Default);
+        let aliases = pending_aliases(&app);
+        assert!(aliases.contains(&"prod restarts"));
+        assert!(aliases.contains(&"prod logs"));
+    }
+
+    #[test]
+    fn browser_apps_open_a_tab_of_rum_queries() {
+        let shop = || Entity {
+            guid: "guid".to_owned(),
+            name: "shop".to_owned(),
+            apm_summary: None,
+        };
+        let mut app = app(Ok(vec![]));
+        app.handle_action(Action::Browser);
+        assert!(app.focus == Focus::Entities);
+        app.handle_entities(vec![shop()]);
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+        assert!(app.focus == Focus::Default);
+        assert_eq!(app.datasets.tabs()[app.datasets.current_tab()], "shop");
+        let aliases = pending_aliases(&app);
+        assert!(aliases.contains(&"shop largest contentful paint"));
+        assert!(aliases.contains(&"shop js errors"));
+
+        // Opening the same app again reuses its tab
+        app.handle_action(Action::Browser);
+        app.handle_entities(vec![shop()]);
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+        assert_eq!(app.datasets.tabs().len(), 2);
+    }
+
+    #[test]
+    fn mobile_apps_chart_crashes_and_open_crash_groups() {
+        let mut app = app(Ok(vec![]));
+        app.handle_action(Action::Mobile);
+        app.handle_entities(vec![Entity {
+            guid: "guid".to_owned(),
+            name: "shop-android".to_owned(),
+            apm_summary: None,
+        }]);
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+        assert!(app.focus == Focus::Crashes);
+        let aliases = pending_aliases(&app);
+        assert!(aliases.contains(&"shop-android crash-free users"));
+        assert!(aliases.contains(&"shop-android crashes by version"));
+
+        app.handle_crashes(recv(&app.backend.crash_rx));
+        app.handle_key(KeyEvent::from(KeyCode::Esc));
+        assert!(app.focus == Focus::Default);
+    }
+
+    #[test]
+    fn workloads_add_golden_signals_for_their_applications() {
+        use server::workload::Member;
+
+        let member = |name: &str, domain: &str| Member {
+            guid: name.to_owned(),
+            name: name.to_owned(),
+            domain: domain.to_owned(),
+            entity_type: "APPLICATION".to_owned(),
+        };
+        let mut app = app(Ok(vec![]));
+        app.handle_action(Action::Workloads);
+        assert!(app.focus == Focus::Workloads);
+        app.handle_workload(WorkloadPayload::Members(
+            "checkout".to_owned(),
+            Ok(vec![member("web", "BROWSER")]),
+        ));
+        assert!(app.focus == Focus::Workloads);
+        assert!(app.pending.is_empty());
+
+        app.handle_workload(WorkloadPayload::Members(
+            "checkout".to_owned(),
+            Ok(vec![member("web", "BROWSER"), member("api", "APM")]),
+        ));
+        assert!(app.focus == Focus::Default);
+        assert_eq!(app.datasets.tabs()[app.datasets.current_tab()], "checkout");
+        let aliases = pending_aliases(&app);
+        assert!(aliases.contains(&"api response time"));
+        assert!(!aliases.iter().any(|alias| alias.starts_with("web")));
+    }
+
+    #[test]
+    fn keybindings_leave_chords_alone_and_show_in_help() {
+        let mut app = app(Ok(vec![]));
+        app.config.keybindings = [
+            ("traces".to_owned(), "d".to_owned()),
+            ("hosts".to_owned(), "w".to_owned()),
+            ("workloads".to_owned(), "U".to_owned()),
+        ]
+        .into();
+        app.handle_key(KeyEvent::new(KeyCode::Char('d'), KeyModifiers::CONTROL));
+        assert!(app.focus == Focus::Default);
+        app.handle_key(KeyEvent::from(KeyCode::Char('d')));
+        assert!(app.focus == Focus::Traces);
+
+        let keymap = app.keymap();
+        let key = |description: Msg| {
+            keymap[0]
+                .1
+                .iter()
+                .find(|(_, d)| *d == description)
+                .map(|(key, _)| key.as_str())
+        };
+        assert_eq!(key(Msg::KeyTraces), Some("T/d"));
+        assert_eq!(key(Msg::KeyHosts), Some("H/w"));
+        assert_eq!(key(Msg::KeyDashboard), None);
+        assert_eq!(key(Msg::KeyHalfPage), Some("Ctrl+D/U"));
+        assert_eq!(key(Msg::KeyWorkloads), Some("U"));
     }
 }
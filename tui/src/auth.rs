@@ -0,0 +1,181 @@
+use anyhow::{anyhow, Result};
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind},
+    terminal::{disable_raw_mode, enable_raw_mode},
+};
+use std::{
+    env,
+    io::{self, Write},
+    process::{Command, Stdio},
+};
+
+use crate::config::Config;
+
+const SERVICE: &str = "xrelic";
+const KEY_ENTRY: &str = "api-key";
+
+// Precedence: NR_API_KEY, then the OS keychain, then api_key in the config file.
+// Failing all three the key is prompted for and stored in the keychain.
+pub fn api_key(config: &Config) -> Result<String> {
+    if let Some((key, _)) = find_api_key(config) {
+        return Ok(key);
+    }
+
+    let key = loop {
+        match prompt("New Relic user API key: ", true) {
+            Some(key) if key.is_empty() => continue,
+            Some(key) => break key,
+            None => return Err(anyhow!("No API key provided.")),
+        }
+    };
+    if !keychain_set(&key) {
+        eprintln!("Could not store the API key in the keychain, it will be asked for again.");
+    }
+    Ok(key)
+}
+
+// Precedence: NR_ACCOUNT, then account in the config file, then a prompt
+pub fn account(config: &Config) -> Result<i64> {
+    if let Some((account, _)) = find_account(config) {
+        return account.map_err(|e| anyhow!(e));
+    }
+
+    loop {
+        match prompt("New Relic account ID: ", false) {
+            Some(account) => match account.parse::<i64>() {
+                Ok(account) => return Ok(account),
+                Err(_) if account.is_empty() => {}
+                Err(_) => eprintln!("{} is not a number.", account),
+            },
+            None => return Err(anyhow!("No account ID provided.")),
+        }
+    }
+}
+
+// The key and where it was found, without prompting
+pub fn find_api_key(config: &Config) -> Option<(String, &'static str)> {
+    if let Some(key) = env::var("NR_API_KEY").ok().filter(|key| !key.is_empty()) {
+        return Some((key, "NR_API_KEY"));
+    }
+    if let Some(key) = keychain_get() {
+        return Some((key, "keychain"));
+    }
+    config.api_key.clone().map(|key| (key, "config file"))
+}
+
+// The account and where it was found, without prompting. NR_ACCOUNT may fail to parse.
+pub fn find_account(config: &Config) -> Option<(Result<i64, String>, &'static str)> {
+    if let Ok(account) = env::var("NR_ACCOUNT") {
+        let account = account
+            .parse::<i64>()
+            .map_err(|_| format!("NR_ACCOUNT is not a number: {}", account));
+        return Some((account, "NR_ACCOUNT"));
+    }
+    config.account.map(|account| (Ok(account), "config file"))
+}
+
+fn keychain_get() -> Option<String> {
+    let output = if cfg!(target_os = "macos") {
+        Command::new("security")
+            .args([
+                "find-generic-password",
+                "-s",
+                SERVICE,
+                "-a",
+                KEY_ENTRY,
+                "-w",
+            ])
+            .stderr(Stdio::null())
+            .output()
+    } else {
+        Command::new("secret-tool")
+            .args(["lookup", "service", SERVICE, "account", KEY_ENTRY])
+            .stderr(Stdio::null())
+            .output()
+    }
+    .ok()?;
+
+    let key = String::from_utf8(output.stdout).ok()?.trim().to_owned();
+    (output.status.success() && !key.is_empty()).then_some(key)
+}
+
+fn keychain_set(key: &str) -> bool {
+    if cfg!(target_os = "macos") {
+        // -U updates an existing entry rather than failing
+        return Command::new("security")
+            .args([
+                "add-generic-password",
+                "-U",
+                "-s",
+                SERVICE,
+                "-a",
+                KEY_ENTRY,
+                "-w",
+                key,
+            ])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success());
+    }
+
+    // secret-tool reads the secret from stdin, keeping it out of the process list
+    let child = Command::new("secret-tool")
+        .args([
+            "store", "--label", SERVICE, "service", SERVICE, "account", KEY_ENTRY,
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    let Ok(mut child) = child else {
+        return false;
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        _ = stdin.write_all(key.as_bytes());
+    }
+    child.wait().is_ok_and(|status| status.success())
+}
+
+// Reads a line in raw mode so hidden input is never echoed, None if Esc was pressed
+fn prompt(label: &str, hidden: bool) -> Option<String> {
+    print!("{}", label);
+    _ = io::stdout().flush();
+    enable_raw_mode().expect("ERROR: Could not enable raw mode!");
+
+    let mut input = String::new();
+    let mut cancelled = false;
+    while let Ok(event) = event::read() {
+        let Event::Key(key) = event else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Enter => break,
+            KeyCode::Esc => {
+                cancelled = true;
+                break;
+            }
+            KeyCode::Backspace => {
+                let erased = input.pop().is_some();
+                if erased && !hidden {
+                    print!("\u{8} \u{8}");
+                }
+            }
+            KeyCode::Char(c) => {
+                input.push(c);
+                if !hidden {
+                    print!("{}", c);
+                }
+            }
+            _ => {}
+        }
+        _ = io::stdout().flush();
+    }
+
+    disable_raw_mode().expect("ERROR: Could not disable raw mode!");
+    println!();
+    (!cancelled).then(|| input.trim().to_owned())
+}
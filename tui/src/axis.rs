@@ -0,0 +1,241 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::format::NumberFormat;
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Unit {
+    #[default]
+    Count,
+    Milliseconds,
+    Seconds,
+    Percent,
+    Bytes,
+}
+
+impl Unit {
+    // A guess from the SELECT, e.g. New Relic reports duration attributes in seconds
+    pub fn infer(select: &str) -> Self {
+        let select = select.to_lowercase();
+        if select.contains("percentage(") {
+            Unit::Percent
+        } else if select.contains("bytes") || select.contains("memory") {
+            Unit::Bytes
+        } else if select.contains("duration") {
+            Unit::Seconds
+        } else {
+            Unit::Count
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "count" => Some(Unit::Count),
+            "ms" => Some(Unit::Milliseconds),
+            "s" => Some(Unit::Seconds),
+            "%" | "percent" => Some(Unit::Percent),
+            "b" | "bytes" => Some(Unit::Bytes),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Unit::Count => "count",
+            Unit::Milliseconds => "ms",
+            Unit::Seconds => "s",
+            Unit::Percent => "%",
+            Unit::Bytes => "bytes",
+        }
+    }
+
+    pub fn format(&self, value: f64, numbers: &NumberFormat) -> String {
+        let abs = value.abs();
+        match self {
+            Unit::Percent => format!("{}%", numbers.format(value, precision(abs))),
+            Unit::Milliseconds if abs >= 1000.0 => Unit::Seconds.format(value / 1000.0, numbers),
+            Unit::Milliseconds => format!("{} ms", numbers.format(value, precision(abs))),
+            Unit::Seconds if abs < 1.0 && abs > 0.0 => {
+                Unit::Milliseconds.format(value * 1000.0, numbers)
+            }
+            Unit::Seconds => format!("{} s", numbers.format(value, precision(abs))),
+            Unit::Bytes => scaled(
+                value,
+                1024.0,
+                &[" B", " KiB", " MiB", " GiB", " TiB"],
+                numbers,
+            ),
+            Unit::Count => scaled(value, 1000.0, &["", "k", "M", "G", "T"], numbers),
+        }
+    }
+}
+
+// Fewer decimals the larger the value, none for whole numbers
+fn precision(abs: f64) -> usize {
+    match abs {
+        _ if abs.fract() == 0.0 || abs >= 100.0 => 0,
+        _ if abs >= 10.0 => 1,
+        _ => 2,
+    }
+}
+
+fn scaled(value: f64, base: f64, suffixes: &[&str], numbers: &NumberFormat) -> String {
+    let mut value = value;
+    let mut i = 0;
+    while value.abs() >= base && i + 1 < suffixes.len() {
+        value /= base;
+        i += 1;
+    }
+    format!(
+        "{}{}",
+        numbers.format(value, precision(value.abs())),
+        suffixes[i]
+    )
+}
+
+// Per-query y axis settings, typed as e.g. "log min=1 max=500 unit=ms". The unit is inferred
+// from the SELECT unless set.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct YAxis {
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub log: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<Unit>,
+}
+
+impl YAxis {
+    pub fn is_default(&self) -> bool {
+        *self == YAxis::default()
+    }
+
+    pub fn parse(spec: &str) -> Result<Self> {
+        let number = |value: &str| {
+            value
+                .parse::<f64>()
+                .map_err(|_| anyhow!("expected a number, not {}", value))
+        };
+        let mut axis = YAxis::default();
+        for setting in spec.split([' ', ',']).filter(|setting| !setting.is_empty()) {
+            match setting.split_once('=') {
+                None if setting == "log" => axis.log = true,
+                None if setting == "linear" => axis.log = false,
+                Some(("min", value)) => axis.min = Some(number(value)?),
+                Some(("max", value)) => axis.max = Some(number(value)?),
+                Some(("unit", "auto")) => axis.unit = None,
+                Some(("unit", value)) => {
+                    axis.unit =
+                        Some(Unit::parse(value).ok_or_else(|| anyhow!("unknown unit {}", value))?)
+                }
+                _ => {
+                    return Err(anyhow!(
+                        "expected log, linear, min=, max= or unit=, not {}",
+                        setting
+                    ))
+                }
+            }
+        }
+        if let (Some(min), Some(max)) = (axis.min, axis.max) {
+            if min >= max {
+                return Err(anyhow!("min must be below max"));
+            }
+        }
+        Ok(axis)
+    }
+
+    // The settings as they would be typed, to edit them again
+    pub fn spec(&self) -> String {
+        let mut settings = vec![];
+        if self.log {
+            settings.push("log".to_owned());
+        }
+        if let Some(min) = self.min {
+            settings.push(format!("min={}", min));
+        }
+        if let Some(max) = self.max {
+            settings.push(format!("max={}", max));
+        }
+        if let Some(unit) = self.unit {
+            settings.push(format!("unit={}", unit.name()));
+        }
+        settings.join(" ")
+    }
+
+    // Where a value is plotted, None for values a log axis can't show
+    pub fn scale(&self, value: f64) -> Option<f64> {
+        match self.log {
+            true => (value > 0.0).then(|| value.log10()),
+            false => Some(value),
+        }
+    }
+
+    pub fn unscale(&self, y: f64) -> f64 {
+        match self.log {
+            true => 10_f64.powf(y),
+            false => y,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_settings() {
+        let axis = YAxis::parse("log min=1, max=500 unit=ms").unwrap();
+        assert_eq!(
+            axis,
+            YAxis {
+                log: true,
+                min: Some(1.0),
+                max: Some(500.0),
+                unit: Some(Unit::Milliseconds),
+            }
+        );
+        assert_eq!(YAxis::parse(&axis.spec()).unwrap(), axis);
+        assert!(YAxis::parse("").unwrap().is_default());
+        assert!(YAxis::parse("unit=furlongs").is_err());
+        assert!(YAxis::parse("min=5 max=1").is_err());
+        assert!(YAxis::parse("squiggly").is_err());
+    }
+
+    #[test]
+    fn infers_units_from_select() {
+        assert_eq!(Unit::infer("average(duration)"), Unit::Seconds);
+        assert_eq!(
+            Unit::infer("percentage(count(*), WHERE error IS true)"),
+            Unit::Percent
+        );
+        assert_eq!(Unit::infer("max(memoryUsedBytes)"), Unit::Bytes);
+        assert_eq!(Unit::infer("count(*)"), Unit::Count);
+    }
+
+    #[test]
+    fn formats_with_units() {
+        let numbers = NumberFormat::default();
+        assert_eq!(Unit::Count.format(1_250_000.0, &numbers), "1.25M");
+        assert_eq!(Unit::Count.format(42.0, &numbers), "42");
+        assert_eq!(Unit::Seconds.format(0.25, &numbers), "250 ms");
+        assert_eq!(Unit::Seconds.format(3.0, &numbers), "3 s");
+        assert_eq!(Unit::Milliseconds.format(1500.0, &numbers), "1.50 s");
+        assert_eq!(Unit::Percent.format(12.5, &numbers), "12.5%");
+        assert_eq!(Unit::Bytes.format(1536.0, &numbers), "1.50 KiB");
+    }
+
+    #[test]
+    fn log_scale_drops_values_it_cannot_plot() {
+        let axis = YAxis {
+            log: true,
+            ..YAxis::default()
+        };
+        assert!((axis.scale(1000.0).unwrap() - 3.0).abs() < 1e-9);
+        assert_eq!(axis.scale(0.0), None);
+        assert!((axis.unscale(2.0) - 100.0).abs() < 1e-9);
+        assert_eq!(YAxis::default().scale(0.0), Some(0.0));
+    }
+}
@@ -1,22 +1,44 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
 use std::{
-    collections::BTreeMap,
-    sync::mpsc::{channel, Receiver, Sender},
-    time::Duration,
+    collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
+    fs,
+    future::Future,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::{
+        mpsc::{self, Receiver, SendError, Sender},
+        Arc, Condvar, Mutex,
+    },
+    time::{Duration, Instant, SystemTime},
 };
 use tokio::{
     runtime::{self, Runtime},
+    sync::broadcast::{self, Receiver as BReceiver, Sender as BSender},
+    task::JoinHandle,
     time::sleep,
 };
+use tracing::{debug, warn};
 
-use chrono::{Timelike, Utc};
-use crossbeam_channel::{unbounded, Receiver as MReceiver, Sender as MSender};
+use chrono::Utc;
 use server::{
+    alert::Issue,
+    dashboard::Dashboard,
+    entity::Entity,
+    newrelic::NrqlError,
+    preflight::PreflightFailure,
+    service_level::Indicator,
     timeseries::{Timeseries, TimeseriesResult},
+    trace::{Span, TraceSummary},
+    workload::{Member, Workload},
     NewRelicClient,
 };
 
-use crate::query::NRQLQuery;
+use crate::{
+    dataset::anomalies,
+    diagnostics::Diagnostics,
+    query::{nrql_string, NRQLQuery, TimeRange, Variables},
+};
 
 #[derive(Clone, Copy)]
 pub struct Bounds {
@@ -24,26 +46,388 @@ pub struct Bounds {
     pub maxes: (f64, f64),
 }
 
+// A single value of a query without TIMESERIES, with the same column from COMPARE WITH
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scalar {
+    pub name: String,
+    pub value: f64,
+    pub previous: Option<f64>,
+}
+
+// The value of one facet over the whole window, from a FACET query without TIMESERIES
+#[derive(Clone, Debug, PartialEq)]
+pub struct FacetTotal {
+    pub name: String,
+    pub value: f64,
+}
+
+#[derive(Clone)]
 pub struct Payload {
     pub query: String,
     pub data: BTreeMap<String, Vec<(f64, f64)>>,
+    pub comparison: BTreeMap<String, Vec<(f64, f64)>>,
     pub bounds: Bounds,
+    // Points far enough from the rolling mean of each facet, worked out here rather than in
+    // the UI loop so a large result doesn't hold up keys
+    pub anomalies: BTreeMap<String, Vec<(f64, f64)>>,
     pub selection: String,
+    // Bucket labels and counts of a histogram() query, charted as bars instead of lines
+    pub histogram: Vec<(String, u64)>,
+    // Shown as billboards instead of a chart
+    pub scalars: Vec<Scalar>,
+    // Shown as a breakdown of bars instead of a chart
+    pub facet_totals: Vec<FacetTotal>,
+    pub notice: Option<String>,
+    // Set when the query failed, as opposed to running and returning no rows
+    pub error: Option<String>,
+}
+
+pub enum TracePayload {
+    Summaries(Vec<TraceSummary>),
+    Spans(String, Vec<Span>),
+}
+
+pub enum AlertPayload {
+    Issues(Vec<Issue>),
+    Acknowledged(String, Result<(), String>),
+    // NRQL of the condition behind an issue
+    Condition(Result<String, String>),
+}
+
+// A host's recent CPU, memory and disk use, each as (time, percent)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Host {
+    pub name: String,
+    pub cpu: Vec<(f64, f64)>,
+    pub memory: Vec<(f64, f64)>,
+    pub disk: Vec<(f64, f64)>,
+}
+
+impl Host {
+    // Metrics in table order, after the name
+    pub fn metrics(&self) -> [&[(f64, f64)]; 3] {
+        [&self.cpu, &self.memory, &self.disk]
+    }
+}
+
+// An SLI measured against its first objective
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Slo {
+    pub guid: String,
+    pub name: String,
+    // Percentage of good events
+    pub target: f64,
+    // Length of the rolling window
+    pub days: i64,
+    pub attainment: Option<f64>,
+    // Hourly over the last day, 1 spends the budget exactly over the window
+    pub burn: Vec<(f64, f64)>,
+}
+
+impl Slo {
+    fn of(indicator: &Indicator) -> Self {
+        let objective = indicator.objectives.first();
+        let days = objective
+            .and_then(|objective| objective.time_window.rolling.as_ref())
+            .map_or(7, |rolling| match rolling.unit.as_str() {
+                "WEEK" => rolling.count * 7,
+                _ => rolling.count,
+            });
+        Slo {
+            guid: indicator.guid.to_owned(),
+            name: indicator.name.to_owned(),
+            target: objective.map_or(100.0, |objective| objective.target),
+            days,
+            ..Slo::default()
+        }
+    }
+
+    // Percentage of the error budget still unspent, below zero once it is overspent
+    pub fn budget(&self) -> Option<f64> {
+        let attainment = self.attainment?;
+        let budget = 100.0 - self.target;
+        (budget > 0.0).then(|| 100.0 - (100.0 - attainment) / budget * 100.0)
+    }
+
+    pub fn attainment_nrql(&self, timeseries: bool) -> String {
+        let nrql = format!("FROM Metric SELECT clamp_max(sum(newrelic.sli.good) / sum(newrelic.sli.valid) * 100, 100) WHERE entity.guid = {} SINCE {} days ago UNTIL now LIMIT MAX", nrql_string(&self.guid), self.days);
+        match timeseries {
+            true => format!("{} TIMESERIES", nrql),
+            false => nrql,
+        }
+    }
+
+    pub fn burn_nrql(&self) -> String {
+        let budget = f64::max(100.0 - self.target, 0.001);
+        format!("FROM Metric SELECT (100 - sum(newrelic.sli.good) / sum(newrelic.sli.valid) * 100) / {} WHERE entity.guid = {} SINCE 1 day ago UNTIL now LIMIT MAX TIMESERIES 1 hour", budget, nrql_string(&self.guid))
+    }
+}
+
+// Crashes sharing an exception and location, with where and how the latest one happened
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CrashGroup {
+    pub exception: String,
+    pub location: String,
+    pub crashes: f64,
+    pub users: f64,
+    pub details: Vec<(String, String)>,
+}
+
+// A Kubernetes cluster, namespace or pod and its columns, e.g. ("restarts", 3.0)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct KubeRow {
+    pub name: String,
+    pub values: Vec<(String, f64)>,
+}
+
+// What is listed below a path of [cluster, namespace]: clusters at the top, then namespaces, then pods
+fn kube_nrql(path: &[String]) -> String {
+    match path {
+        [] => "FROM K8sPodSample SELECT uniqueCount(namespaceName) AS 'namespaces', uniqueCount(podName) AS 'pods' FACET clusterName SINCE 30 minutes ago UNTIL now LIMIT 100".to_owned(),
+        [cluster] => format!("FROM K8sPodSample SELECT uniqueCount(podName) AS 'pods', filter(uniqueCount(podName), WHERE status != 'Running') AS 'not running' WHERE clusterName = {} FACET namespaceName SINCE 30 minutes ago UNTIL now LIMIT 100", nrql_string(cluster)),
+        [cluster, namespace, ..] => format!("FROM K8sContainerSample SELECT max(restartCount) AS 'restarts', average(cpuUsedCores) AS 'cpu cores', average(memoryWorkingSetBytes) AS 'memory' WHERE clusterName = {} AND namespaceName = {} FACET podName SINCE 30 minutes ago UNTIL now LIMIT 100", nrql_string(cluster), nrql_string(namespace)),
+    }
+}
+
+static HOSTS: &str = "FROM SystemSample SELECT average(cpuPercent) AS 'cpu', average(memoryUsedPercent) AS 'memory', average(diskUsedPercent) AS 'disk' FACET hostname SINCE 30 minutes ago UNTIL now LIMIT 100 TIMESERIES";
+
+pub enum WorkloadPayload {
+    Workloads(Vec<Workload>),
+    // Entities in the named workload
+    Members(String, Result<Vec<Member>, String>),
+}
+
+pub enum ImportPayload {
+    Dashboards(Vec<Entity>),
+    Dashboard(Result<Dashboard, String>),
+    // Name and guid of the dashboard the session was exported to
+    Exported(Result<(String, String), String>),
+}
+
+#[derive(Clone)]
+pub enum UIEvent {
+    DeleteQuery(String),
+    SetTimeRange(TimeRange),
+    SetVariables(Variables),
+    // Seconds between refreshes while active
+    SetRefreshInterval(u64),
+    // Rolling window and standard deviations anomalies are marked at
+    SetAnomalies(usize, f64),
+    Idle(u64),
+    Active,
+}
+
+pub const FILE_PREFIX: &str = "FILE ";
+
+#[derive(Clone)]
+pub enum DataSource {
+    Nrql(Box<NRQLQuery>),
+    // Local (timestamp, value) series, re-read whenever the file changes
+    File(PathBuf),
+}
+
+impl DataSource {
+    pub fn key(&self) -> String {
+        match self {
+            DataSource::Nrql(query) => query.to_string().unwrap(),
+            DataSource::File(path) => format!("{}{}", FILE_PREFIX, path.display()),
+        }
+    }
+}
+
+// What a new refresh task starts with, after which it follows the UIEvents itself
+#[derive(Clone)]
+pub struct TaskSettings {
+    pub variables: Variables,
+    pub refresh_interval: u64,
+    pub anomaly_window: usize,
+    pub anomaly_sigmas: f64,
+    // Seconds between refreshes while idle
+    pub idle_refresh: Option<u64>,
+}
+
+impl Default for TaskSettings {
+    fn default() -> Self {
+        TaskSettings {
+            variables: Variables::default(),
+            refresh_interval: 5,
+            anomaly_window: 10,
+            anomaly_sigmas: 3.0,
+            idle_refresh: None,
+        }
+    }
+}
+
+// Lets the UI loop sleep until there is a key or a payload to handle, instead of polling
+#[derive(Clone, Default)]
+pub struct Wakeup(Arc<(Mutex<bool>, Condvar)>);
+
+impl Wakeup {
+    pub fn notify(&self) {
+        let (woken, condvar) = &*self.0;
+        *woken.lock().expect("ERROR: Wakeup lock poisoned!") = true;
+        condvar.notify_one();
+    }
+
+    // Returns once notified, or after the timeout if nothing happened, and whether it was notified
+    pub fn wait(&self, timeout: Duration) -> bool {
+        let (woken, condvar) = &*self.0;
+        let guard = woken.lock().expect("ERROR: Wakeup lock poisoned!");
+        let (mut guard, _) = condvar
+            .wait_timeout_while(guard, timeout, |woken| !*woken)
+            .expect("ERROR: Wakeup lock poisoned!");
+        std::mem::take(&mut *guard)
+    }
+}
+
+// Sends to the UI loop and wakes it
+pub struct Tx<T> {
+    tx: Sender<T>,
+    wakeup: Wakeup,
+}
+
+impl<T> Clone for Tx<T> {
+    fn clone(&self) -> Self {
+        Tx {
+            tx: self.tx.clone(),
+            wakeup: self.wakeup.clone(),
+        }
+    }
+}
+
+impl<T> Tx<T> {
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        self.tx.send(value)?;
+        self.wakeup.notify();
+        Ok(())
+    }
+}
+
+pub fn channel<T>(wakeup: &Wakeup) -> (Tx<T>, Receiver<T>) {
+    let (tx, rx) = mpsc::channel();
+    let wakeup = wakeup.clone();
+    (Tx { tx, wakeup }, rx)
+}
+
+pub struct Task {
+    pub source: DataSource,
+    pub handle: JoinHandle<()>,
+    // Identical queries share a single polling task
+    pub refs: usize,
+}
+
+// Recent successful payloads keyed by query (which includes its window), so a re-added or
+// reloaded query renders straight away instead of waiting on the API
+#[derive(Clone)]
+pub struct Cache {
+    ttl: Duration,
+    entries: Arc<Mutex<HashMap<String, (Instant, Payload)>>>,
+}
+
+impl Cache {
+    pub fn new(ttl: Duration) -> Self {
+        Cache {
+            ttl,
+            entries: Arc::default(),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<Payload> {
+        let mut entries = self.entries.lock().expect("ERROR: Cache lock poisoned!");
+        entries.retain(|_, (stored, _)| stored.elapsed() < self.ttl);
+        entries.get(key).map(|(_, payload)| payload.clone())
+    }
+
+    fn insert(&self, payload: &Payload) {
+        if self.ttl.is_zero() || payload.error.is_some() {
+            return;
+        }
+        self.entries
+            .lock()
+            .expect("ERROR: Cache lock poisoned!")
+            .insert(payload.query.to_owned(), (Instant::now(), payload.clone()));
+    }
+
+    fn clear(&self) {
+        self.entries
+            .lock()
+            .expect("ERROR: Cache lock poisoned!")
+            .clear();
+    }
+}
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+// Where refresh tasks get their timeseries, so tests can serve canned results
+pub trait NerdGraph: Send + Sync {
+    fn timeseries(
+        &self,
+        account: Option<i64>,
+        nrql: String,
+    ) -> BoxFuture<'_, Result<Vec<TimeseriesResult>>>;
+}
+
+impl NerdGraph for NewRelicClient {
+    fn timeseries(
+        &self,
+        account: Option<i64>,
+        nrql: String,
+    ) -> BoxFuture<'_, Result<Vec<TimeseriesResult>>> {
+        Box::pin(async move { self.try_query_in::<TimeseriesResult>(account, nrql).await })
+    }
 }
 
 pub struct Backend {
     pub client: NewRelicClient,
+    pub nerdgraph: Arc<dyn NerdGraph>,
     pub runtime: Runtime,
-    pub data_tx: Sender<Payload>,
+    pub wakeup: Wakeup,
+    pub data_tx: Tx<Payload>,
     pub data_rx: Receiver<Payload>,
-    pub ui_tx: MSender<String>,
-    pub ui_rx: MReceiver<String>,
+    pub ui_tx: BSender<UIEvent>,
+    pub trace_tx: Tx<TracePayload>,
+    pub trace_rx: Receiver<TracePayload>,
+    pub entity_tx: Tx<Vec<Entity>>,
+    pub entity_rx: Receiver<Vec<Entity>>,
+    pub alert_tx: Tx<AlertPayload>,
+    pub alert_rx: Receiver<AlertPayload>,
+    pub import_tx: Tx<ImportPayload>,
+    pub import_rx: Receiver<ImportPayload>,
+    // Pretty-printed console responses, or why the request failed
+    pub console_tx: Tx<Result<String, String>>,
+    pub console_rx: Receiver<Result<String, String>>,
+    pub host_tx: Tx<Vec<Host>>,
+    pub host_rx: Receiver<Vec<Host>>,
+    pub workload_tx: Tx<WorkloadPayload>,
+    pub workload_rx: Receiver<WorkloadPayload>,
+    pub slo_tx: Tx<Vec<Slo>>,
+    pub slo_rx: Receiver<Vec<Slo>>,
+    pub crash_tx: Tx<Vec<CrashGroup>>,
+    pub crash_rx: Receiver<Vec<CrashGroup>>,
+    // Rows listed below a Kubernetes path, so a late reply for another level can be dropped
+    pub kube_tx: Tx<(Vec<String>, Vec<KubeRow>)>,
+    pub kube_rx: Receiver<(Vec<String>, Vec<KubeRow>)>,
+    pub tasks: BTreeMap<String, Task>,
+    pub cache: Cache,
+    pub diagnostics: Arc<Diagnostics>,
+    pub settings: TaskSettings,
 }
 
 impl Backend {
-    pub fn new(client: NewRelicClient) -> Self {
-        let (data_tx, data_rx) = channel::<Payload>();
-        let (ui_tx, ui_rx) = unbounded();
+    pub fn new(client: NewRelicClient, cache_ttl: Duration) -> Self {
+        let wakeup = Wakeup::default();
+        let (data_tx, data_rx) = channel::<Payload>(&wakeup);
+        let (ui_tx, _) = broadcast::channel(64);
+        let (trace_tx, trace_rx) = channel::<TracePayload>(&wakeup);
+        let (entity_tx, entity_rx) = channel::<Vec<Entity>>(&wakeup);
+        let (alert_tx, alert_rx) = channel::<AlertPayload>(&wakeup);
+        let (import_tx, import_rx) = channel::<ImportPayload>(&wakeup);
+        let (console_tx, console_rx) = channel::<Result<String, String>>(&wakeup);
+        let (host_tx, host_rx) = channel::<Vec<Host>>(&wakeup);
+        let (workload_tx, workload_rx) = channel::<WorkloadPayload>(&wakeup);
+        let (slo_tx, slo_rx) = channel::<Vec<Slo>>(&wakeup);
+        let (crash_tx, crash_rx) = channel::<Vec<CrashGroup>>(&wakeup);
+        let (kube_tx, kube_rx) = channel::<(Vec<String>, Vec<KubeRow>)>(&wakeup);
         let runtime = runtime::Builder::new_multi_thread()
             .worker_threads(1)
             .thread_name("data")
@@ -52,81 +436,1149 @@ impl Backend {
             .unwrap();
 
         Self {
+            nerdgraph: Arc::new(client.clone()),
             client,
             runtime,
+            wakeup,
             data_tx,
             data_rx,
             ui_tx,
-            ui_rx,
+            trace_tx,
+            trace_rx,
+            entity_tx,
+            entity_rx,
+            alert_tx,
+            alert_rx,
+            import_tx,
+            import_rx,
+            console_tx,
+            console_rx,
+            host_tx,
+            host_rx,
+            workload_tx,
+            workload_rx,
+            slo_tx,
+            slo_rx,
+            crash_tx,
+            crash_rx,
+            kube_tx,
+            kube_rx,
+            tasks: BTreeMap::default(),
+            cache: Cache::new(cache_ttl),
+            diagnostics: Arc::default(),
+            settings: TaskSettings::default(),
         }
     }
 
-    pub fn add_query(&self, query: NRQLQuery) {
+    #[cfg(test)]
+    pub fn with_nerdgraph(mut self, nerdgraph: Arc<dyn NerdGraph>) -> Self {
+        self.nerdgraph = nerdgraph;
+        self
+    }
+
+    #[cfg(test)]
+    pub fn add_query(&mut self, query: NRQLQuery) {
+        self.add_source(DataSource::Nrql(Box::new(query)));
+    }
+
+    pub fn with_diagnostics(mut self, diagnostics: Arc<Diagnostics>) -> Self {
+        self.diagnostics = diagnostics;
+        self
+    }
+
+    pub fn add_source(&mut self, source: DataSource) {
+        let key = source.key();
+        if let Some(task) = self.tasks.get_mut(&key) {
+            task.refs += 1;
+            return;
+        }
+
+        let handle = self.spawn(source.clone());
+        self.tasks.insert(
+            key,
+            Task {
+                source,
+                handle,
+                refs: 1,
+            },
+        );
+    }
+
+    // Aborting the handle drops the task's outstanding request along with it
+    fn spawn(&self, source: DataSource) -> JoinHandle<()> {
         let tx = self.data_tx.clone();
-        let rx = self.ui_rx.clone();
+        match source {
+            DataSource::Nrql(query) => {
+                let rx = self.ui_tx.subscribe();
+                let nerdgraph = self.nerdgraph.clone();
+                let cache = self.cache.clone();
+                let diagnostics = self.diagnostics.clone();
+                let settings = self.settings.clone();
+                self.runtime.spawn(async move {
+                    let refreshed =
+                        refresh_timeseries(*query, settings, nerdgraph, cache, diagnostics, tx, rx);
+                    if let Err(e) = refreshed.await {
+                        debug!("refresh stopped: {}", e);
+                    }
+                })
+            }
+            DataSource::File(path) => {
+                let rx = self.ui_tx.subscribe();
+                let settings = self.settings.clone();
+                self.runtime.spawn(async move {
+                    if let Err(e) = refresh_file(path, settings, tx, rx).await {
+                        debug!("file refresh stopped: {}", e);
+                    }
+                })
+            }
+        }
+    }
+
+    // Replaces the task with a fresh one, so a request made for the old query or values can't
+    // land after the new one
+    fn restart(&self, task: &mut Task, key: &str) {
+        task.handle.abort();
+        self.diagnostics.cancel_request(key);
+        task.handle = self.spawn(task.source.clone());
+    }
+
+    pub fn send(&mut self, event: UIEvent) {
+        match &event {
+            UIEvent::DeleteQuery(key) => {
+                if let Some(task) = self.tasks.get_mut(key) {
+                    task.refs -= 1;
+                    if task.refs == 0 {
+                        self.tasks.remove(key).unwrap().handle.abort();
+                        self.diagnostics.cancel_request(key);
+                    }
+                }
+                return;
+            }
+            UIEvent::SetTimeRange(range) => {
+                let mut tasks: BTreeMap<String, Task> = BTreeMap::default();
+                for (old, mut task) in std::mem::take(&mut self.tasks) {
+                    match &mut task.source {
+                        DataSource::Nrql(query) => {
+                            query.set_time_range(range);
+                        }
+                        DataSource::File(_) => {
+                            tasks.insert(old, task);
+                            continue;
+                        }
+                    }
+                    let key = task.source.key();
+                    // Queries that only differed by time range now collide, keep one task
+                    match tasks.get_mut(&key) {
+                        Some(existing) => {
+                            existing.refs += task.refs;
+                            task.handle.abort();
+                            self.diagnostics.cancel_request(&old);
+                        }
+                        None => {
+                            self.restart(&mut task, &old);
+                            tasks.insert(key, task);
+                        }
+                    }
+                }
+                self.tasks = tasks;
+                return;
+            }
+            // Cached payloads are keyed by the query as written, so they may be for old values
+            UIEvent::SetVariables(variables) => {
+                let previous = std::mem::replace(&mut self.settings.variables, variables.clone());
+                self.cache.clear();
+                // Only queries using a changed variable fetch again
+                let mut tasks = std::mem::take(&mut self.tasks);
+                for (key, task) in &mut tasks {
+                    if let DataSource::Nrql(query) = &task.source {
+                        if query.with_variables(variables) != query.with_variables(&previous) {
+                            self.restart(task, key);
+                        }
+                    }
+                }
+                self.tasks = tasks;
+            }
+            UIEvent::SetRefreshInterval(interval) => self.settings.refresh_interval = *interval,
+            UIEvent::Idle(interval) => self.settings.idle_refresh = Some(*interval),
+            UIEvent::Active => self.settings.idle_refresh = None,
+            // Cached payloads carry anomalies marked with the old settings
+            UIEvent::SetAnomalies(window, sigmas) => {
+                self.settings.anomaly_window = *window;
+                self.settings.anomaly_sigmas = *sigmas;
+                self.cache.clear();
+            }
+        }
+        _ = self.ui_tx.send(event);
+    }
+
+    pub fn fetch_traces(&self, range: &TimeRange) {
+        let query = format!(
+            "SELECT trace.id, root.entity.name, root.span.name, duration.ms, errorCount, spanCount, timestamp FROM DistributedTraceSummary SINCE {} UNTIL {} LIMIT 100",
+            range.since, range.until
+        );
+        let client = self.client.clone();
+        let tx = self.trace_tx.clone();
+        self.runtime.spawn(async move {
+            let summaries = client
+                .try_query::<TraceSummary>(query)
+                .await
+                .unwrap_or_else(|e| {
+                    warn!("trace summaries request failed: {}", e);
+                    vec![]
+                });
+            _ = tx.send(TracePayload::Summaries(summaries));
+        });
+    }
+
+    pub fn fetch_spans(&self, trace_id: &str) {
+        let query = format!(
+            "SELECT id, parent.id, name, service.name, duration.ms, timestamp, error, error.message FROM Span WHERE trace.id = {} SINCE 1 day ago LIMIT MAX",
+            nrql_string(trace_id)
+        );
+        let client = self.client.clone();
+        let tx = self.trace_tx.clone();
+        let trace_id = trace_id.to_owned();
+        self.runtime.spawn(async move {
+            let spans = client.try_query::<Span>(query).await.unwrap_or_else(|e| {
+                warn!("spans request failed for trace {}: {}", trace_id, e);
+                vec![]
+            });
+            _ = tx.send(TracePayload::Spans(trace_id, spans));
+        });
+    }
+
+    pub fn fetch_entities(&self) {
+        let client = self.client.clone();
+        let tx = self.entity_tx.clone();
+        self.runtime.spawn(async move {
+            let entities = client.entities().await.unwrap_or_else(|e| {
+                warn!("entities request failed: {}", e);
+                vec![]
+            });
+            _ = tx.send(entities);
+        });
+    }
+
+    pub fn fetch_apps(&self, domain: &'static str) {
+        let client = self.client.clone();
+        let tx = self.entity_tx.clone();
+        self.runtime.spawn(async move {
+            let entities = client.apps(domain).await.unwrap_or_else(|e| {
+                warn!("{} apps request failed: {}", domain, e);
+                vec![]
+            });
+            _ = tx.send(entities);
+        });
+    }
+
+    pub fn fetch_crashes(&self, app: &str) {
+        let nerdgraph = self.nerdgraph.clone();
+        let tx = self.crash_tx.clone();
+        let nrql = format!("FROM MobileCrash SELECT count(*) AS 'crashes', uniqueCount(deviceUuid) AS 'users', latest(crashMessage) AS 'message', latest(appVersion) AS 'version', latest(crashLocationClass) AS 'class', latest(crashLocationMethod) AS 'method', latest(crashLocationFile) AS 'file', latest(crashLocationLineNumber) AS 'line' WHERE appName = {} FACET crashException, crashLocation SINCE 1 day ago UNTIL now LIMIT 50", nrql_string(app));
+        self.runtime.spawn(async move {
+            let groups = match nerdgraph.timeseries(None, nrql).await {
+                Ok(data) => to_crash_groups(&data),
+                Err(e) => {
+                    warn!("crashes request failed: {}", e);
+                    vec![]
+                }
+            };
+            _ = tx.send(groups);
+        });
+    }
+
+    // The SLIs, then each one's attainment and burn rate
+    pub fn fetch_slos(&self) {
+        let client = self.client.clone();
+        let nerdgraph = self.nerdgraph.clone();
+        let tx = self.slo_tx.clone();
+        self.runtime.spawn(async move {
+            let indicators = client.service_levels().await.unwrap_or_else(|e| {
+                warn!("service levels request failed: {}", e);
+                vec![]
+            });
+            let mut slos = vec![];
+            for indicator in &indicators {
+                let mut slo = Slo::of(indicator);
+                match nerdgraph.timeseries(None, slo.attainment_nrql(false)).await {
+                    Ok(data) => {
+                        slo.attainment = data
+                            .first()
+                            .and_then(|row| row.values().first().map(|(_, value)| *value))
+                    }
+                    Err(e) => warn!("attainment of {} failed: {}", slo.name, e),
+                }
+                match nerdgraph.timeseries(None, slo.burn_nrql()).await {
+                    Ok(data) => {
+                        slo.burn = data
+                            .iter()
+                            .filter_map(|row| {
+                                let (_, value) = row.values().first().cloned()?;
+                                Some((row.end_time_seconds, value))
+                            })
+                            .collect()
+                    }
+                    Err(e) => warn!("burn rate of {} failed: {}", slo.name, e),
+                }
+                slos.push(slo);
+            }
+            _ = tx.send(slos);
+        });
+    }
+
+    pub fn fetch_workloads(&self) {
         let client = self.client.clone();
+        let tx = self.workload_tx.clone();
         self.runtime.spawn(async move {
-            _ = refresh_timeseries(query, client, tx, rx).await;
+            let workloads = client.workloads().await.unwrap_or_else(|e| {
+                warn!("workloads request failed: {}", e);
+                vec![]
+            });
+            _ = tx.send(WorkloadPayload::Workloads(workloads));
         });
     }
+
+    pub fn fetch_workload_members(&self, workload: &Workload) {
+        let client = self.client.clone();
+        let tx = self.workload_tx.clone();
+        let (guid, name) = (workload.guid.to_owned(), workload.name.to_owned());
+        self.runtime.spawn(async move {
+            let members = client
+                .workload_members(&guid)
+                .await
+                .map_err(|e| e.to_string());
+            _ = tx.send(WorkloadPayload::Members(name, members));
+        });
+    }
+
+    pub fn fetch_hosts(&self) {
+        let nerdgraph = self.nerdgraph.clone();
+        let tx = self.host_tx.clone();
+        self.runtime.spawn(async move {
+            let hosts = match nerdgraph.timeseries(None, HOSTS.to_owned()).await {
+                Ok(data) => to_hosts(&data),
+                Err(e) => {
+                    warn!("hosts request failed: {}", e);
+                    vec![]
+                }
+            };
+            _ = tx.send(hosts);
+        });
+    }
+
+    pub fn fetch_kube(&self, path: Vec<String>) {
+        let nerdgraph = self.nerdgraph.clone();
+        let tx = self.kube_tx.clone();
+        self.runtime.spawn(async move {
+            let rows = match nerdgraph.timeseries(None, kube_nrql(&path)).await {
+                Ok(data) => data
+                    .iter()
+                    .map(|row| KubeRow {
+                        name: row.facet.join(", "),
+                        values: row.values(),
+                    })
+                    .collect(),
+                Err(e) => {
+                    warn!("kubernetes request failed: {}", e);
+                    vec![]
+                }
+            };
+            _ = tx.send((path, rows));
+        });
+    }
+
+    pub fn fetch_issues(&self) {
+        let client = self.client.clone();
+        let tx = self.alert_tx.clone();
+        self.runtime.spawn(async move {
+            let issues = client.issues().await.unwrap_or_else(|e| {
+                warn!("issues request failed: {}", e);
+                vec![]
+            });
+            _ = tx.send(AlertPayload::Issues(issues));
+        });
+    }
+
+    pub fn acknowledge_issue(&self, issue_id: &str) {
+        let client = self.client.clone();
+        let tx = self.alert_tx.clone();
+        let issue_id = issue_id.to_owned();
+        self.runtime.spawn(async move {
+            let result = client
+                .acknowledge_issue(&issue_id)
+                .await
+                .map_err(|e| e.to_string());
+            _ = tx.send(AlertPayload::Acknowledged(issue_id, result));
+        });
+    }
+
+    pub fn fetch_condition(&self, condition_id: i64) {
+        let client = self.client.clone();
+        let tx = self.alert_tx.clone();
+        self.runtime.spawn(async move {
+            let result = client
+                .condition_query(condition_id)
+                .await
+                .map_err(|e| e.to_string());
+            _ = tx.send(AlertPayload::Condition(result));
+        });
+    }
+
+    // Blocks until the startup credential check completes
+    pub fn preflight(&self) -> Result<(), PreflightFailure> {
+        self.runtime.block_on(self.client.preflight())
+    }
+
+    pub fn fetch_dashboards(&self) {
+        let client = self.client.clone();
+        let tx = self.import_tx.clone();
+        self.runtime.spawn(async move {
+            let dashboards = client.dashboards().await.unwrap_or_else(|e| {
+                warn!("dashboards request failed: {}", e);
+                vec![]
+            });
+            _ = tx.send(ImportPayload::Dashboards(dashboards));
+        });
+    }
+
+    pub fn fetch_dashboard(&self, guid: &str) {
+        let client = self.client.clone();
+        let tx = self.import_tx.clone();
+        let guid = guid.to_owned();
+        self.runtime.spawn(async move {
+            let dashboard = client.dashboard(&guid).await.map_err(|e| e.to_string());
+            _ = tx.send(ImportPayload::Dashboard(dashboard));
+        });
+    }
+
+    pub fn export_dashboard(
+        &self,
+        guid: Option<String>,
+        name: String,
+        widgets: Vec<(String, String, Option<i64>)>,
+    ) {
+        let client = self.client.clone();
+        let tx = self.import_tx.clone();
+        self.runtime.spawn(async move {
+            let guid = client
+                .export_dashboard(guid.as_deref(), &name, &widgets)
+                .await
+                .map(|guid| (name, guid))
+                .map_err(|e| e.to_string());
+            _ = tx.send(ImportPayload::Exported(guid));
+        });
+    }
+
+    pub fn run_graphql(&self, query: String) {
+        let client = self.client.clone();
+        let tx = self.console_tx.clone();
+        self.runtime.spawn(async move {
+            let response = client
+                .raw_graphql(&query)
+                .await
+                .and_then(|json| serde_json::to_string_pretty(&json).map_err(Into::into))
+                .map_err(|e| e.to_string());
+            _ = tx.send(response);
+        });
+    }
+
+    // Payloads from aborted tasks may still be queued, so check before applying them
+    pub fn is_running(&self, query: &str) -> bool {
+        self.tasks.contains_key(query)
+    }
 }
 
 pub async fn refresh_timeseries(
     query: NRQLQuery,
-    client: NewRelicClient,
-    data_tx: Sender<Payload>,
-    ui_rx: MReceiver<String>,
+    settings: TaskSettings,
+    nerdgraph: Arc<dyn NerdGraph>,
+    cache: Cache,
+    diagnostics: Arc<Diagnostics>,
+    data_tx: Tx<Payload>,
+    mut ui_rx: BReceiver<UIEvent>,
 ) -> Result<()> {
+    let TaskSettings {
+        mut variables,
+        mut refresh_interval,
+        mut anomaly_window,
+        mut anomaly_sigmas,
+        mut idle_refresh,
+    } = settings;
+    // Fetch straight away rather than waiting for the next tick, unless a recent result is cached
+    let mut refresh_now = match cache.get(&query.to_string()?) {
+        Some(payload) => {
+            data_tx.send(payload)?;
+            false
+        }
+        None => true,
+    };
+    // Results of the last fetch, topped up with only the newest buckets on later cycles
+    let mut latest: Vec<TimeseriesResult> = vec![];
     loop {
-        while let Some(q) = ui_rx.try_iter().next() {
-            if query.to_string().unwrap() == q {
-                return Ok(());
+        while let Ok(event) = ui_rx.try_recv() {
+            match event {
+                // The backend restarts the task if its query changed
+                UIEvent::SetVariables(values) => variables = values,
+                UIEvent::SetRefreshInterval(interval) => refresh_interval = interval,
+                UIEvent::SetAnomalies(window, sigmas) => {
+                    anomaly_window = window;
+                    anomaly_sigmas = sigmas;
+                    refresh_now = true;
+                }
+                UIEvent::Idle(interval) => idle_refresh = Some(interval),
+                UIEvent::Active => {
+                    refresh_now = idle_refresh.is_some();
+                    idle_refresh = None;
+                }
+                _ => {}
             }
         }
-        if Utc::now().second() % 5 == 0 {
-            let data = client
-                .query::<TimeseriesResult>(query.to_string().unwrap())
-                .await
-                .unwrap_or_default();
+        let due = match idle_refresh {
+            None => (Utc::now().timestamp() as u64).is_multiple_of(refresh_interval),
+            Some(0) => false,
+            Some(interval) => (Utc::now().timestamp() as u64).is_multiple_of(interval),
+        };
+        if due || refresh_now {
+            refresh_now = false;
+            let key = query.to_string()?;
+            let sent = query.with_variables(&variables);
+            let started = Instant::now();
+            diagnostics.start_request(&key);
+            let fetched = match delta_query(&sent, &latest) {
+                Some((delta, from)) => {
+                    fetch_timeseries(nerdgraph.as_ref(), &delta)
+                        .await
+                        .map(|(data, notice)| {
+                            (
+                                merge(std::mem::take(&mut latest), data, from, &sent),
+                                notice,
+                            )
+                        })
+                }
+                None => fetch_timeseries(nerdgraph.as_ref(), &sent).await,
+            };
+            let latency = started.elapsed();
+            diagnostics.record_request(&key, latency, fetched.is_ok());
+            let (mut data, notice, error) = match fetched {
+                Ok((data, notice)) => {
+                    debug!(query = %key, "fetched {} points in {}ms", data.len(), latency.as_millis());
+                    latest = data.clone();
+                    (data, notice, None)
+                }
+                Err(e) => {
+                    warn!(query = %key, "query failed after {}ms: {}", latency.as_millis(), e);
+                    latest.clear();
+                    (vec![], None, Some(e))
+                }
+            };
+
+            // Faceted results also echo each attribute as a column, which isn't a series
+            let attributes = sent.facet_attributes();
+            for point in data.iter_mut() {
+                point
+                    .columns
+                    .retain(|name, _| !attributes.contains(&name.as_str()));
+            }
+
+            // COMPARE WITH returns both periods in one result set
+            let (previous, data): (Vec<_>, Vec<_>) = data
+                .into_iter()
+                .partition(|point| point.comparison.as_deref() == Some("previous"));
 
             let mut min_bounds: (f64, f64) = (f64::MAX, f64::MAX);
             let mut max_bounds: (f64, f64) = (0 as f64, 0 as f64);
 
+            for (_, value) in data
+                .iter()
+                .chain(previous.iter())
+                .flat_map(|point| point.values())
+            {
+                min_bounds.1 = f64::min(min_bounds.1, value);
+                max_bounds.1 = f64::max(max_bounds.1, value);
+            }
             for point in &data {
                 min_bounds.0 = f64::min(min_bounds.0, point.end_time_seconds);
-                min_bounds.1 = f64::min(min_bounds.1, point.value);
-
                 max_bounds.0 = f64::max(max_bounds.0, point.end_time_seconds);
-                max_bounds.1 = f64::max(max_bounds.1, point.value);
             }
 
-            let mut facets: BTreeMap<String, Vec<(f64, f64)>> = BTreeMap::default();
+            // Shift the previous period onto the current window so the two overlay
+            let start = |points: &[TimeseriesResult]| {
+                points
+                    .iter()
+                    .map(|point| point.begin_time_seconds)
+                    .fold(f64::MAX, f64::min)
+            };
+            let offset = match data.is_empty() || previous.is_empty() {
+                true => 0.0,
+                false => start(&data[..]) - start(&previous[..]),
+            };
 
-            for data in data.into_iter().map(Timeseries::from) {
-                let facet = &data.facet.unwrap_or(String::from("value"));
-                if facets.contains_key(facet) {
-                    facets
-                        .get_mut(facet)
-                        .unwrap()
-                        .extend_from_slice(&[(data.end_time_seconds, data.value)]);
-                } else {
-                    facets.insert(
-                        facet.to_owned(),
-                        vec![(data.begin_time_seconds, data.value)],
-                    );
-                }
+            // Without TIMESERIES there is one row per facet, or a single row without FACET
+            let (scalars, facet_totals) = match (query.is_timeseries(), query.facet.is_empty()) {
+                (true, _) => (vec![], vec![]),
+                (false, true) => (to_scalars(&data, &previous), vec![]),
+                (false, false) => (vec![], to_facet_totals(&data)),
+            };
+            let histogram = to_histogram(&data, &query.select);
+            let data = to_facets(data, 0.0);
+            let payload = Payload {
+                query: key,
+                histogram,
+                scalars,
+                facet_totals,
+                anomalies: anomalies(&data, anomaly_window, anomaly_sigmas),
+                data,
+                comparison: to_facets(previous, offset),
+                bounds: Bounds {
+                    mins: min_bounds,
+                    maxes: max_bounds,
+                },
+                selection: query.select.to_owned(),
+                notice,
+                error,
+            };
+            cache.insert(&payload);
+            data_tx.send(payload)?;
+        }
+        sleep(Duration::from_millis(16)).await;
+    }
+}
+
+pub async fn refresh_file(
+    path: PathBuf,
+    settings: TaskSettings,
+    data_tx: Tx<Payload>,
+    mut ui_rx: BReceiver<UIEvent>,
+) -> Result<()> {
+    let key = DataSource::File(path.clone()).key();
+    let (mut window, mut sigmas) = (settings.anomaly_window, settings.anomaly_sigmas);
+    let mut modified: Option<SystemTime> = None;
+    loop {
+        while let Ok(event) = ui_rx.try_recv() {
+            if let UIEvent::SetAnomalies(new_window, new_sigmas) = event {
+                (window, sigmas) = (new_window, new_sigmas);
+                // Re-read as if the file changed, to re-mark the points
+                modified = None;
+            }
+        }
+        let latest = fs::metadata(&path).and_then(|meta| meta.modified()).ok();
+        if modified.is_none() || latest != modified {
+            modified = latest;
+            let (points, error) = match read_points(&path) {
+                Ok(points) => (points, None),
+                Err(e) => (
+                    vec![],
+                    Some(format!("Could not read {}: {}", path.display(), e)),
+                ),
+            };
+
+            let mut min_bounds: (f64, f64) = (f64::MAX, f64::MAX);
+            let mut max_bounds: (f64, f64) = (0 as f64, 0 as f64);
+            for (x, y) in &points {
+                min_bounds = (f64::min(min_bounds.0, *x), f64::min(min_bounds.1, *y));
+                max_bounds = (f64::max(max_bounds.0, *x), f64::max(max_bounds.1, *y));
             }
 
+            let data = BTreeMap::from([(String::from("value"), points)]);
             data_tx.send(Payload {
-                query: query.to_string().unwrap(),
-                data: facets,
+                query: key.to_owned(),
+                anomalies: anomalies(&data, window, sigmas),
+                data,
+                comparison: BTreeMap::default(),
                 bounds: Bounds {
                     mins: min_bounds,
                     maxes: max_bounds,
                 },
-                selection: query.select.to_owned(),
+                selection: String::from("value"),
+                histogram: vec![],
+                scalars: vec![],
+                facet_totals: vec![],
+                notice: None,
+                error,
             })?
         }
-        sleep(Duration::from_millis(16)).await;
+        sleep(Duration::from_secs(1)).await;
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FilePoint {
+    Pair(f64, f64),
+    Object { timestamp: f64, value: f64 },
+}
+
+// Reads .json as an array of pairs or {timestamp, value} objects, anything else as CSV
+fn read_points(path: &Path) -> Result<Vec<(f64, f64)>> {
+    let contents = fs::read_to_string(path)?;
+    let mut points = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => serde_json::from_str::<Vec<FilePoint>>(&contents)?
+            .into_iter()
+            .map(|point| match point {
+                FilePoint::Pair(timestamp, value) => (timestamp, value),
+                FilePoint::Object { timestamp, value } => (timestamp, value),
+            })
+            .collect::<Vec<_>>(),
+        _ => contents
+            .lines()
+            .filter_map(|line| {
+                let (timestamp, value) = line.split_once(',')?;
+                Some((
+                    timestamp.trim().parse::<f64>().ok()?,
+                    value.trim().parse::<f64>().ok()?,
+                ))
+            })
+            .collect::<Vec<_>>(),
+    };
+    if points.is_empty() {
+        return Err(anyhow!("no (timestamp, value) pairs found"));
+    }
+
+    // Charts are in epoch seconds, so scale down millisecond timestamps
+    for (timestamp, _) in points.iter_mut() {
+        if *timestamp > 1e12 {
+            *timestamp /= 1000.0;
+        }
+    }
+    points.sort_by(|a, b| a.0.total_cmp(&b.0));
+    Ok(points)
+}
+
+// A query for just the newest bucket onwards, when the window ends now and earlier results
+// can be kept. Returns the query and the start of the buckets it replaces.
+fn delta_query(query: &NRQLQuery, latest: &[TimeseriesResult]) -> Option<(NRQLQuery, f64)> {
+    if !query.until.eq_ignore_ascii_case("now")
+        || !query.compare.is_empty()
+        || !query.mode.starts_with("TIMESERIES")
+    {
+        return None;
+    }
+    let last = latest
+        .iter()
+        .max_by(|a, b| a.begin_time_seconds.total_cmp(&b.begin_time_seconds))?;
+    let bucket = last.end_time_seconds - last.begin_time_seconds;
+    if bucket <= 0.0 {
+        return None;
+    }
+
+    // Pin the bucket size, as AUTO would pick a finer one for the short delta window
+    let mut delta = query.clone();
+    delta.set_since(((last.begin_time_seconds * 1000.0) as i64).to_string());
+    delta.mode = format!("TIMESERIES {} seconds", bucket as i64);
+    Some((delta, last.begin_time_seconds))
+}
+
+// Replaces buckets from `from` onwards with the delta, and drops any that slid out of the window
+fn merge(
+    mut latest: Vec<TimeseriesResult>,
+    delta: Vec<TimeseriesResult>,
+    from: f64,
+    query: &NRQLQuery,
+) -> Vec<TimeseriesResult> {
+    latest.retain(|point| point.begin_time_seconds < from);
+    latest.extend(delta);
+    if let Some((since, until)) = query.window() {
+        let start = Utc::now().timestamp() - (until - since);
+        latest.retain(|point| point.end_time_seconds > start as f64);
+    }
+    latest.sort_by(|a, b| a.begin_time_seconds.total_cmp(&b.begin_time_seconds));
+    latest
+}
+
+const MAX_SPLITS: usize = 4;
+
+// Retries result-size failures over progressively halved windows and stitches the results.
+// Only buckets of a TIMESERIES query stitch, the rows of any other query cover the whole window.
+async fn fetch_timeseries(
+    nerdgraph: &dyn NerdGraph,
+    query: &NRQLQuery,
+) -> Result<(Vec<TimeseriesResult>, Option<String>), String> {
+    match nerdgraph.timeseries(query.account, query.nrql()).await {
+        Ok(data) => return Ok((data, None)),
+        Err(e) if is_result_too_large(&e) => {}
+        Err(e) => return Err(e.to_string()),
+    }
+
+    let window = query.window().filter(|_| query.is_timeseries());
+    let Some((since, until)) = window else {
+        return Err("Result too large, and the time window could not be narrowed".to_owned());
+    };
+
+    let mut windows = VecDeque::from([(since, until, 0)]);
+    let mut data: Vec<TimeseriesResult> = vec![];
+    let mut fetched = 0;
+    while let Some((since, until, depth)) = windows.pop_front() {
+        let mid = since + (until - since) / 2;
+        for (since, until) in [(since, mid), (mid, until)] {
+            let mut window = query.clone();
+            window
+                .set_since((since * 1000).to_string())
+                .set_until((until * 1000).to_string());
+            match nerdgraph.timeseries(window.account, window.nrql()).await {
+                Ok(results) => {
+                    data.extend(results);
+                    fetched += 1;
+                }
+                Err(e) if is_result_too_large(&e) && depth + 1 < MAX_SPLITS => {
+                    windows.push_back((since, until, depth + 1));
+                }
+                Err(_) => {
+                    return Err("Result too large, even over narrowed time windows".to_owned())
+                }
+            }
+        }
+    }
+
+    data.sort_by(|a, b| a.begin_time_seconds.total_cmp(&b.begin_time_seconds));
+    Ok((
+        data,
+        Some(format!(
+            "Result too large: stitched from {} narrower time windows",
+            fetched
+        )),
+    ))
+}
+
+fn is_result_too_large(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<NrqlError>()
+        .is_some_and(|e| e.is_result_too_large())
+}
+
+fn to_facets(data: Vec<TimeseriesResult>, offset: f64) -> BTreeMap<String, Vec<(f64, f64)>> {
+    let mut facets: BTreeMap<String, Vec<(f64, f64)>> = BTreeMap::default();
+    let columns = data
+        .iter()
+        .flat_map(|point| point.values())
+        .map(|(column, _)| column)
+        .collect::<BTreeSet<_>>();
+
+    for data in data.into_iter().map(Timeseries::from) {
+        for (column, value) in &data.values {
+            let name = series_name(&data.facet, column, columns.len() == 1);
+            match facets.get_mut(&name) {
+                Some(points) => points.push((data.end_time_seconds + offset, *value)),
+                None => {
+                    facets.insert(name, vec![(data.begin_time_seconds + offset, *value)]);
+                }
+            }
+        }
+    }
+
+    facets
+}
+
+fn to_scalars(data: &[TimeseriesResult], previous: &[TimeseriesResult]) -> Vec<Scalar> {
+    let previous = previous
+        .first()
+        .map(|point| point.values())
+        .unwrap_or_default();
+    data.first()
+        .map(|point| point.values())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(name, value)| Scalar {
+            previous: previous
+                .iter()
+                .find(|(column, _)| *column == name)
+                .map(|(_, value)| *value),
+            name,
+            value,
+        })
+        .collect()
+}
+
+// One host per hostname facet, in the order New Relic returns them
+fn to_hosts(data: &[TimeseriesResult]) -> Vec<Host> {
+    let mut hosts: Vec<Host> = vec![];
+    for point in data {
+        let name = point.facet.join(", ");
+        let i = match hosts.iter().position(|host| host.name == name) {
+            Some(i) => i,
+            None => {
+                hosts.push(Host {
+                    name,
+                    ..Host::default()
+                });
+                hosts.len() - 1
+            }
+        };
+        let host = &mut hosts[i];
+        for (column, value) in point.values() {
+            let series = match column.as_str() {
+                "cpu" => &mut host.cpu,
+                "memory" => &mut host.memory,
+                "disk" => &mut host.disk,
+                _ => continue,
+            };
+            series.push((point.end_time_seconds, value));
+        }
+    }
+    hosts
+}
+
+// Largest groups first, as the query returns them
+fn to_crash_groups(data: &[TimeseriesResult]) -> Vec<CrashGroup> {
+    data.iter()
+        .map(|row| {
+            let number = |column: &str| {
+                row.columns
+                    .get(column)
+                    .and_then(|value| value.as_f64())
+                    .unwrap_or_default()
+            };
+            let details = ["message", "version", "class", "method", "file", "line"]
+                .iter()
+                .filter_map(|column| {
+                    let value = match row.columns.get(*column)? {
+                        serde_json::Value::Null => return None,
+                        serde_json::Value::String(value) => value.to_owned(),
+                        value => value.to_string(),
+                    };
+                    Some((column.to_string(), value))
+                })
+                .collect();
+            CrashGroup {
+                exception: row.facet.first().cloned().unwrap_or_default(),
+                location: row.facet.get(1).cloned().unwrap_or_default(),
+                crashes: number("crashes"),
+                users: number("users"),
+                details,
+            }
+        })
+        .collect()
+}
+
+// In the order New Relic returns them, largest first unless the query says otherwise
+fn to_facet_totals(data: &[TimeseriesResult]) -> Vec<FacetTotal> {
+    let columns = data
+        .iter()
+        .flat_map(|point| point.values())
+        .map(|(column, _)| column)
+        .collect::<BTreeSet<_>>();
+    data.iter()
+        .flat_map(|point| {
+            point
+                .values()
+                .into_iter()
+                .map(|(column, value)| FacetTotal {
+                    name: series_name(&point.facet, &column, columns.len() == 1),
+                    value,
+                })
+        })
+        .collect()
+}
+
+// Bucket counts summed over every row of the first histogram column, labelled by where each
+// bucket starts when the width can be read from histogram(attribute, width, buckets)
+fn to_histogram(data: &[TimeseriesResult], select: &str) -> Vec<(String, u64)> {
+    let mut counts: Vec<f64> = vec![];
+    for buckets in data
+        .iter()
+        .filter_map(|point| point.histograms().into_iter().next())
+        .map(|(_, buckets)| buckets)
+    {
+        counts.resize(usize::max(counts.len(), buckets.len()), 0.0);
+        for (count, bucket) in counts.iter_mut().zip(buckets) {
+            *count += bucket;
+        }
+    }
+
+    let width = select
+        .to_lowercase()
+        .split_once("histogram(")
+        .and_then(|(_, args)| {
+            args.split(')')
+                .next()?
+                .split(',')
+                .nth(1)?
+                .trim()
+                .parse::<f64>()
+                .ok()
+        });
+    counts
+        .iter()
+        .enumerate()
+        .map(|(i, count)| {
+            let label = match width {
+                Some(width) => {
+                    let start = i as f64 * width / counts.len() as f64;
+                    ((start * 100.0).round() / 100.0).to_string()
+                }
+                None => i.to_string(),
+            };
+            (label, count.round() as u64)
+        })
+        .collect()
+}
+
+// One series per facet tuple and selected column, named by whichever of the two vary
+fn series_name(facet: &[String], column: &str, single_column: bool) -> String {
+    let facet = facet.join(", ");
+    match facet.is_empty() {
+        true => column.to_owned(),
+        false if single_column => facet,
+        false => format!("{} {}", facet, column),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::{
+        app::parse_key,
+        testing::{backend, series, StalledNerdGraph},
+    };
+
+    const QUERY: &str = "FROM Transaction SELECT count(*) WHERE appName = 'api' SINCE 30 minutes ago UNTIL now LIMIT MAX TIMESERIES";
+
+    // Fails as too large until `fails` requests have been made, then answers with one point
+    struct TooLarge {
+        fails: usize,
+        sent: AtomicUsize,
+    }
+
+    impl NerdGraph for TooLarge {
+        fn timeseries(
+            &self,
+            _account: Option<i64>,
+            _nrql: String,
+        ) -> BoxFuture<'_, Result<Vec<TimeseriesResult>>> {
+            let sent = self.sent.fetch_add(1, Ordering::SeqCst);
+            let results = match sent < self.fails {
+                true => Err(anyhow!(NrqlError {
+                    message: "Query returned too many results".to_owned(),
+                })),
+                false => Ok(series(None, &[((10 - sent) as f64 * 60.0, 1.0)])),
+            };
+            Box::pin(async move { results })
+        }
+    }
+
+    fn fetch(
+        nerdgraph: TooLarge,
+        nrql: &str,
+    ) -> Result<(Vec<TimeseriesResult>, Option<String>), String> {
+        let runtime = runtime::Builder::new_current_thread().build().unwrap();
+        runtime.block_on(fetch_timeseries(&nerdgraph, &parse_key(nrql).unwrap()))
+    }
+
+    #[test]
+    fn edited_and_deleted_queries_drop_their_outstanding_request() {
+        let mut backend = backend(StalledNerdGraph);
+        let in_flight = |backend: &Backend, key: &str| {
+            let started = Instant::now();
+            while backend.diagnostics.in_flight(key).is_none() {
+                assert!(started.elapsed() < Duration::from_secs(5));
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        };
+        let query = parse_key(QUERY).unwrap();
+        let old = query.to_string().unwrap();
+        backend.add_query(query);
+        in_flight(&backend, &old);
+
+        backend.send(UIEvent::SetTimeRange(TimeRange::last("1 hour")));
+        assert!(backend.diagnostics.in_flight(&old).is_none());
+        let key = backend.tasks.keys().next().unwrap().to_owned();
+        assert!(key.contains("SINCE 1 hour ago"));
+        in_flight(&backend, &key);
+
+        backend.send(UIEvent::DeleteQuery(key.to_owned()));
+        assert!(backend.diagnostics.in_flight(&key).is_none());
+        assert!(backend.tasks.is_empty());
+    }
+
+    #[test]
+    fn too_large_results_are_stitched_from_narrower_windows() {
+        let nerdgraph = TooLarge {
+            fails: 1,
+            sent: AtomicUsize::default(),
+        };
+        let (data, notice) = fetch(nerdgraph, QUERY).unwrap();
+        let begins = data
+            .iter()
+            .map(|point| point.begin_time_seconds)
+            .collect::<Vec<_>>();
+        assert_eq!(begins, vec![480.0, 540.0]);
+        assert_eq!(
+            notice.as_deref(),
+            Some("Result too large: stitched from 2 narrower time windows")
+        );
+
+        let nerdgraph = TooLarge {
+            fails: usize::MAX,
+            sent: AtomicUsize::default(),
+        };
+        assert_eq!(
+            fetch(nerdgraph, QUERY).unwrap_err(),
+            "Result too large, even over narrowed time windows"
+        );
+    }
+
+    #[test]
+    fn only_timeseries_results_are_stitched() {
+        let nerdgraph = TooLarge {
+            fails: 1,
+            sent: AtomicUsize::default(),
+        };
+        let facets = "FROM Transaction SELECT count(*) WHERE appName = 'api' FACET host SINCE 30 minutes ago UNTIL now LIMIT MAX";
+        assert_eq!(
+            fetch(nerdgraph, facets).unwrap_err(),
+            "Result too large, and the time window could not be narrowed"
+        );
+    }
+
+    #[test]
+    fn facets_are_named_by_facet_and_column() {
+        let mut data = series(Some("api"), &[(0.0, 1.0), (60.0, 2.0)]);
+        data.extend(series(Some("web"), &[(0.0, 3.0)]));
+        let facets = to_facets(data, 30.0);
+        assert_eq!(facets["api"], vec![(30.0, 1.0), (150.0, 2.0)]);
+        assert_eq!(facets["web"], vec![(30.0, 3.0)]);
+
+        let mut data = series(None, &[(0.0, 1.0)]);
+        data[0].columns.insert("other".to_owned(), json!(2.0));
+        let facets = to_facets(data, 0.0);
+        assert_eq!(facets.keys().collect::<Vec<_>>(), ["other", "value"]);
+    }
+
+    #[test]
+    fn scalars_carry_the_previous_value() {
+        let scalars = to_scalars(&series(None, &[(0.0, 5.0)]), &series(None, &[(0.0, 4.0)]));
+        assert_eq!(scalars[0].name, "value");
+        assert_eq!(scalars[0].value, 5.0);
+        assert_eq!(scalars[0].previous, Some(4.0));
+    }
+
+    #[test]
+    fn deltas_replace_the_newest_buckets() {
+        let now = Utc::now().timestamp() as f64;
+        let query = parse_key(QUERY).unwrap();
+        let latest = series(
+            None,
+            &[(now - 3600.0, 1.0), (now - 120.0, 2.0), (now - 60.0, 3.0)],
+        );
+
+        let (delta, from) = delta_query(&query, &latest).unwrap();
+        assert_eq!(from, now - 60.0);
+        assert_eq!(delta.mode, "TIMESERIES 60 seconds");
+
+        let merged = merge(
+            latest,
+            series(None, &[(now - 60.0, 4.0), (now, 5.0)]),
+            from,
+            &query,
+        );
+        let values = merged
+            .iter()
+            .flat_map(|point| point.values())
+            .map(|(_, value)| value)
+            .collect::<Vec<_>>();
+        // The hour-old bucket slid out of the 30 minute window
+        assert_eq!(values, vec![2.0, 4.0, 5.0]);
     }
 }
@@ -0,0 +1,428 @@
+use anyhow::{anyhow, Result};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use ratatui::style::palette::tailwind::Palette;
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+    sync::mpsc::{channel, Receiver},
+};
+use tracing::{debug, warn};
+
+use crate::{
+    action::{self, Action},
+    format::{NumberFormat, Timezone},
+    i18n::detect_language,
+    template::QueryTemplate,
+    ui::{PALETTES, THEMES},
+};
+
+// The documented defaults, written by `urelic config init`
+pub const EXAMPLE: &str = include_str!("../config.example.toml");
+
+#[derive(Default, Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum QuitBehaviour {
+    Always,
+    Never,
+    // Only prompts when the session has unsaved changes
+    #[default]
+    Ask,
+}
+
+#[derive(Default, Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum View {
+    #[default]
+    Graph,
+    Dashboard,
+    // A single chart filling the dashboard
+    Expanded,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct LayoutPreset {
+    pub name: String,
+    #[serde(default)]
+    pub view: View,
+    // Alias or query to focus, or "pinned" for the first pinned query
+    pub select: Option<String>,
+    // Width of the side pane as a percentage of the screen
+    #[serde(default = "default_side_width")]
+    pub side_width: u16,
+}
+
+fn default_side_width() -> u16 {
+    15
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub quit_behaviour: QuitBehaviour,
+    // Seconds between refreshes
+    pub refresh_interval: u64,
+    // Seconds without input before refreshes are slowed, 0 disables
+    pub idle_timeout: u64,
+    // Seconds between refreshes while idle, 0 pauses entirely
+    pub idle_refresh: u64,
+    pub colour_blind: bool,
+    // Renders charts as plain text summaries
+    pub screen_reader: bool,
+    // Dotted verticals at each time label
+    pub gridlines: bool,
+    // Standard deviations from the rolling mean before a point is marked as an anomaly, 0 disables
+    pub anomaly_sigmas: f64,
+    // Points the rolling mean is taken over
+    pub anomaly_window: usize,
+    // Rings the terminal bell and flashes the status bar when a query crosses a threshold
+    pub notify: bool,
+    // Seconds before the same query notifies again
+    pub notify_cooldown: u64,
+    // Overrides the locale used for number formatting, e.g. "de_DE"
+    pub locale: Option<String>,
+    // Zone timestamps are shown in: "local", "utc" or an offset such as "+05:30"
+    pub timezone: Option<String>,
+    // Language of the UI text, e.g. "de", otherwise taken from the environment
+    pub language: Option<String>,
+    // Used when NR_ACCOUNT is unset
+    pub account: Option<i64>,
+    // Plaintext fallback below NR_API_KEY and the OS keychain
+    pub api_key: Option<String>,
+    // Seconds a query result is reused when the query is re-added or reloaded, 0 disables
+    pub cache_ttl: u64,
+    // Accent colour, one of THEMES
+    pub theme: String,
+    // Extra keys by action name, e.g. traces = "t", taking precedence over the defaults
+    pub keybindings: BTreeMap<String, String>,
+    // Where the session is saved, otherwise the app directory
+    pub session_dir: Option<PathBuf>,
+    // Cycled through with L
+    pub layouts: Vec<LayoutPreset>,
+    // The query library, inserted with Y
+    pub templates: Vec<QueryTemplate>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            quit_behaviour: QuitBehaviour::default(),
+            refresh_interval: 5,
+            idle_timeout: 600,
+            idle_refresh: 0,
+            colour_blind: false,
+            screen_reader: false,
+            gridlines: false,
+            anomaly_sigmas: 3.0,
+            anomaly_window: 10,
+            notify: false,
+            notify_cooldown: 300,
+            locale: None,
+            timezone: None,
+            language: None,
+            account: None,
+            api_key: None,
+            cache_ttl: 120,
+            theme: "lime".to_owned(),
+            keybindings: BTreeMap::new(),
+            session_dir: None,
+            layouts: vec![
+                LayoutPreset {
+                    name: "triage".to_owned(),
+                    view: View::Graph,
+                    select: Some("pinned".to_owned()),
+                    side_width: 25,
+                },
+                LayoutPreset {
+                    name: "overview".to_owned(),
+                    view: View::Dashboard,
+                    select: None,
+                    side_width: default_side_width(),
+                },
+            ],
+            templates: vec![],
+        }
+    }
+}
+
+// Where the config is looked for, the first that exists wins. config.yaml in the app
+// directory is still read for older installs.
+pub fn config_paths(app_dir: &Path) -> Vec<PathBuf> {
+    let xdg = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+    xdg.map(|dir| dir.join("urelic").join("config.toml"))
+        .into_iter()
+        .chain([app_dir.join("config.toml"), app_dir.join("config.yaml")])
+        .collect()
+}
+
+pub fn find_config(app_dir: &Path) -> Option<PathBuf> {
+    config_paths(app_dir).into_iter().find(|path| path.exists())
+}
+
+// `urelic config init` and `urelic config check`, returning the exit code
+pub fn command(subcommand: Option<&str>, app_dir: &Path) -> i32 {
+    match subcommand {
+        Some("init") => {
+            if let Some(path) = find_config(app_dir) {
+                eprintln!("{} already exists", path.display());
+                return 1;
+            }
+            let path = config_paths(app_dir).remove(0);
+            let written = path
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| fs::write(&path, EXAMPLE));
+            match written {
+                Ok(()) => {
+                    println!("Wrote {}", path.display());
+                    0
+                }
+                Err(e) => {
+                    eprintln!("Could not write {}: {}", path.display(), e);
+                    1
+                }
+            }
+        }
+        Some("check") => {
+            let Some(path) = find_config(app_dir) else {
+                println!("No config found, using defaults. Looked for:");
+                for path in config_paths(app_dir) {
+                    println!("  {}", path.display());
+                }
+                return 0;
+            };
+            match Config::read(&path) {
+                Ok(_) => {
+                    println!("{} is valid", path.display());
+                    0
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    1
+                }
+            }
+        }
+        _ => {
+            eprintln!("Usage: urelic config <init|check>");
+            2
+        }
+    }
+}
+
+// Reports changes to the config file. Its directory is watched, as editors often replace the
+// file rather than write to it.
+pub struct ConfigWatch {
+    pub path: PathBuf,
+    _watcher: RecommendedWatcher,
+    rx: Receiver<()>,
+}
+
+impl ConfigWatch {
+    pub fn new(path: PathBuf) -> Result<Self> {
+        let (tx, rx) = channel();
+        let name = path.file_name().map(|name| name.to_owned());
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let ours = event.is_ok_and(|event| {
+                event
+                    .paths
+                    .iter()
+                    .any(|changed| changed.file_name() == name.as_deref())
+            });
+            if ours {
+                _ = tx.send(());
+            }
+        })?;
+        let dir = path
+            .parent()
+            .ok_or_else(|| anyhow!("{} has no directory to watch", path.display()))?;
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        Ok(ConfigWatch {
+            path,
+            _watcher: watcher,
+            rx,
+        })
+    }
+
+    // Whether the file changed since last asked, a save often raises several events
+    pub fn changed(&self) -> bool {
+        let mut changed = false;
+        while self.rx.try_recv().is_ok() {
+            changed = true;
+        }
+        changed
+    }
+}
+
+impl Config {
+    // Defaults when no config file exists
+    pub fn load(app_dir: &Path) -> Result<Self> {
+        match find_config(app_dir) {
+            Some(path) => Config::read(&path),
+            None => {
+                debug!("no config in {}, using defaults", app_dir.display());
+                Ok(Config::default())
+            }
+        }
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Could not read {}: {}", path.display(), e))?;
+        let parsed: Result<Config> = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml") => serde_yaml::from_str(&text).map_err(Into::into),
+            _ => toml::from_str(&text).map_err(Into::into),
+        };
+        parsed
+            .and_then(|config| config.validate().map(|_| config))
+            .map_err(|e| anyhow!("{} is not a valid config:\n{}", path.display(), e))
+    }
+
+    // Every problem at once, one per line, rather than stopping at the first
+    pub fn validate(&self) -> Result<()> {
+        let mut problems = vec![];
+        if let Some(name) = &self.timezone {
+            if Timezone::parse(name).is_none() {
+                problems.push(format!(
+                    "timezone: unknown zone {}, expected local, utc or an offset such as +05:30",
+                    name
+                ));
+            }
+        }
+        if !THEMES.contains(&self.theme.as_str()) {
+            problems.push(format!(
+                "theme: unknown theme {}, expected one of {}",
+                self.theme,
+                THEMES.join(", ")
+            ));
+        }
+        for (name, key) in &self.keybindings {
+            if action::named(name).is_none() {
+                problems.push(format!("keybindings: unknown action {}", name));
+            }
+            if key.chars().count() != 1 {
+                problems.push(format!(
+                    "keybindings: {} should be a single character, not \"{}\"",
+                    name, key
+                ));
+            }
+        }
+        if self.refresh_interval == 0 {
+            problems.push("refresh_interval: should be at least 1 second".to_owned());
+        }
+        if self.anomaly_window == 0 {
+            problems.push("anomaly_window: should be at least 1".to_owned());
+        }
+        for layout in &self.layouts {
+            if !(5..=90).contains(&layout.side_width) {
+                problems.push(format!(
+                    "layouts: side_width of {} should be between 5 and 90",
+                    layout.name
+                ));
+            }
+        }
+        for template in &self.templates {
+            if template.name.trim().is_empty() || template.query.trim().is_empty() {
+                problems.push("templates: each needs a name and a query".to_owned());
+            }
+        }
+        if let Some(dir) = &self.session_dir {
+            if !dir.is_dir() {
+                problems.push(format!("session_dir: {} is not a directory", dir.display()));
+            }
+        }
+        match problems.is_empty() {
+            true => Ok(()),
+            false => Err(anyhow!(problems.join("\n"))),
+        }
+    }
+
+    pub fn palette(&self) -> &'static Palette {
+        let i = THEMES
+            .iter()
+            .position(|theme| *theme == self.theme)
+            .unwrap_or_default();
+        &PALETTES[i]
+    }
+
+    pub fn keybindings(&self) -> BTreeMap<char, Action> {
+        self.keybindings
+            .iter()
+            .filter_map(|(name, key)| Some((key.chars().next()?, action::named(name)?)))
+            .collect()
+    }
+
+    pub fn session_dir(&self, app_dir: &Path) -> PathBuf {
+        self.session_dir
+            .clone()
+            .unwrap_or_else(|| app_dir.to_owned())
+    }
+
+    pub fn language(&self) -> String {
+        self.language.clone().unwrap_or_else(detect_language)
+    }
+
+    pub fn timezone(&self) -> Timezone {
+        let Some(name) = &self.timezone else {
+            return Timezone::default();
+        };
+        Timezone::parse(name).unwrap_or_else(|| {
+            warn!("unknown timezone {}, using local time", name);
+            Timezone::default()
+        })
+    }
+
+    pub fn number_format(&self) -> NumberFormat {
+        match &self.locale {
+            Some(locale) => NumberFormat::from_locale(locale),
+            None => NumberFormat::detect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn example_documents_the_defaults() {
+        let example: Config = toml::from_str(EXAMPLE).unwrap();
+        assert!(example.validate().is_ok());
+        assert!(example == Config::default());
+    }
+
+    #[test]
+    fn validation_reports_every_problem() {
+        let config: Config = toml::from_str(
+            r#"
+            theme = "mauve"
+            timezone = "mars"
+
+            [keybindings]
+            traces = "tt"
+            fly = "f"
+            "#,
+        )
+        .unwrap();
+        let problems = config.validate().unwrap_err().to_string();
+        assert_eq!(problems.lines().count(), 4);
+        assert!(problems.contains("theme: unknown theme mauve"));
+        assert!(problems.contains("timezone: unknown zone mars"));
+        assert!(problems.contains("keybindings: traces should be a single character"));
+        assert!(problems.contains("keybindings: unknown action fly"));
+
+        let unknown = toml::from_str::<Config>("colour = true").unwrap_err();
+        assert!(unknown.to_string().contains("unknown field `colour`"));
+    }
+
+    #[test]
+    fn keybindings_map_keys_to_actions() {
+        let config: Config =
+            toml::from_str("theme = \"sky\"\n[keybindings]\ntraces = \"w\"").unwrap();
+        assert_eq!(config.keybindings().get(&'w'), Some(&Action::Traces));
+        assert_eq!(config.palette().c500, PALETTES[8].c500);
+    }
+}
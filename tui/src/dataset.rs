@@ -0,0 +1,853 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use ratatui::layout::Rect;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    axis::{Unit, YAxis},
+    backend::{Bounds, FacetTotal, Scalar},
+    threshold::Threshold,
+};
+
+pub struct Dataset {
+    pub query_alias: Option<String>,
+    pub note: String,
+    pub links: Vec<String>,
+    pub facets: BTreeMap<String, Vec<(f64, f64)>>,
+    pub comparison: BTreeMap<String, Vec<(f64, f64)>>,
+    // Points of each facet that stray from the rolling mean
+    pub anomalies: BTreeMap<String, Vec<(f64, f64)>>,
+    pub snapshot: Option<Snapshot>,
+    pub bounds: Bounds,
+    pub selection: String,
+    pub histogram: Vec<(String, u64)>,
+    pub scalars: Vec<Scalar>,
+    pub facet_totals: Vec<FacetTotal>,
+    pub style: ChartStyle,
+    pub axis: YAxis,
+    pub thresholds: Vec<Threshold>,
+    pub history: Vec<String>,
+    pub notice: Option<String>,
+    pub error: Option<String>,
+    // Bumped whenever new data arrives, invalidating any cached chart
+    pub version: u64,
+}
+
+impl Dataset {
+    // The highest threshold at or below the latest value of any facet, or of the billboard
+    pub fn breached(&self) -> Option<&Threshold> {
+        let latest = self
+            .facets
+            .values()
+            .filter_map(|points| points.last().map(|(_, y)| *y))
+            .chain(self.scalars.iter().map(|scalar| scalar.value))
+            .reduce(f64::max)?;
+        self.thresholds
+            .iter()
+            .rev()
+            .find(|threshold| latest >= threshold.value)
+    }
+}
+
+// Frozen copy of a dataset's facets, e.g. from before a deploy, drawn as a ghost and diffed
+// against live data
+pub struct Snapshot {
+    pub taken: f64,
+    pub facets: BTreeMap<String, Vec<(f64, f64)>>,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChartStyle {
+    #[default]
+    Line,
+    Scatter,
+    StackedArea,
+    StackedBar,
+}
+
+impl ChartStyle {
+    pub fn next(self) -> Self {
+        match self {
+            ChartStyle::Line => ChartStyle::Scatter,
+            ChartStyle::Scatter => ChartStyle::StackedArea,
+            ChartStyle::StackedArea => ChartStyle::StackedBar,
+            ChartStyle::StackedBar => ChartStyle::Line,
+        }
+    }
+
+    pub fn is_stacked(&self) -> bool {
+        matches!(self, ChartStyle::StackedArea | ChartStyle::StackedBar)
+    }
+
+    pub fn is_line(&self) -> bool {
+        *self == ChartStyle::Line
+    }
+}
+
+// Chart-ready points, rebuilt only when the dataset version, style, axis, thresholds or chart
+// size changes. Stacked styles replace each facet's points with dots filling its band of the
+// stack, and a log axis plots every y as its power of ten. Thresholds are kept with where they
+// are plotted.
+pub struct Chart {
+    version: u64,
+    size: (u16, u16),
+    pub style: ChartStyle,
+    pub axis: YAxis,
+    pub unit: Unit,
+    pub facets: BTreeMap<String, Vec<(f64, f64)>>,
+    pub comparison: BTreeMap<String, Vec<(f64, f64)>>,
+    pub anomalies: BTreeMap<String, Vec<(f64, f64)>>,
+    pub snapshot: BTreeMap<String, Vec<(f64, f64)>>,
+    pub y_bounds: (f64, f64),
+    pub thresholds: Vec<(f64, Threshold)>,
+}
+
+pub const DEFAULT_TAB: &str = "main";
+
+// Datasets keyed by query, iterated in display order with pinned queries first. Iteration
+// covers the current tab only, all() and all_keys() cover every tab.
+pub struct Datasets {
+    data: BTreeMap<String, Dataset>,
+    order: Vec<String>,
+    pinned: BTreeSet<String>,
+    charts: BTreeMap<String, Chart>,
+    // Never empty. Queries without an entry in tab_of belong to the first tab.
+    tabs: Vec<String>,
+    current: usize,
+    tab_of: BTreeMap<String, String>,
+}
+
+impl Default for Datasets {
+    fn default() -> Self {
+        Self {
+            data: BTreeMap::default(),
+            order: vec![],
+            pinned: BTreeSet::default(),
+            charts: BTreeMap::default(),
+            tabs: vec![DEFAULT_TAB.to_owned()],
+            current: 0,
+            tab_of: BTreeMap::default(),
+        }
+    }
+}
+
+impl Datasets {
+    pub fn len(&self) -> usize {
+        self.keys().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys().next().is_none()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Dataset> {
+        self.data.get(key)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Dataset> {
+        self.data.get_mut(key)
+    }
+
+    // Holds a place in the display order for a query whose data has not arrived yet
+    pub fn reserve(&mut self, key: &str, pinned: bool) {
+        if !self.order.iter().any(|k| k == key) {
+            self.order.push(key.to_owned());
+            self.assign(key, self.current);
+        }
+        if pinned {
+            self.pinned.insert(key.to_owned());
+        }
+        self.sort();
+    }
+
+    pub fn insert(&mut self, key: String, dataset: Dataset) {
+        self.charts.remove(&key);
+        if !self.order.contains(&key) {
+            self.order.push(key.to_owned());
+            self.assign(&key, self.current);
+            self.sort();
+        }
+        self.data.insert(key, dataset);
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Dataset> {
+        self.order.retain(|k| k != key);
+        self.pinned.remove(key);
+        self.charts.remove(key);
+        self.tab_of.remove(key);
+        self.data.remove(key)
+    }
+
+    // Moves a dataset to a new key, keeping its place in the order
+    pub fn rekey(&mut self, old: &str, new: String) {
+        if let Some(dataset) = self.data.remove(old) {
+            self.data.insert(new.to_owned(), dataset);
+        }
+        if self.pinned.remove(old) {
+            self.pinned.insert(new.to_owned());
+        }
+        if let Some(tab) = self.tab_of.remove(old) {
+            self.tab_of.insert(new.to_owned(), tab);
+        }
+        self.charts.remove(old);
+        self.charts.remove(&new);
+        if self.order.contains(&new) {
+            self.order.retain(|k| k != old);
+        } else if let Some(key) = self.order.iter_mut().find(|k| *k == old) {
+            *key = new;
+        }
+    }
+
+    pub fn rekey_all(&mut self, rekey: impl Fn(&str) -> String) {
+        self.data = std::mem::take(&mut self.data)
+            .into_iter()
+            .map(|(key, dataset)| (rekey(&key), dataset))
+            .collect();
+        self.pinned = self.pinned.iter().map(|key| rekey(key)).collect();
+        self.tab_of = std::mem::take(&mut self.tab_of)
+            .into_iter()
+            .map(|(key, tab)| (rekey(&key), tab))
+            .collect();
+        self.charts.clear();
+        // Keys that collide after re-keying keep the first position
+        let mut seen = BTreeSet::new();
+        self.order = std::mem::take(&mut self.order)
+            .into_iter()
+            .map(|key| rekey(&key))
+            .filter(|key| seen.insert(key.to_owned()))
+            .collect();
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.all_keys()
+            .filter(move |key| self.tab(key) == self.tabs[self.current])
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Dataset)> {
+        self.all()
+            .filter(move |(key, _)| self.tab(key) == self.tabs[self.current])
+    }
+
+    pub fn all_keys(&self) -> impl Iterator<Item = &String> {
+        self.order
+            .iter()
+            .filter(move |key| self.data.contains_key(*key))
+    }
+
+    pub fn all(&self) -> impl Iterator<Item = (&String, &Dataset)> {
+        self.order
+            .iter()
+            .filter_map(move |key| self.data.get(key).map(|dataset| (key, dataset)))
+    }
+
+    pub fn tabs(&self) -> &[String] {
+        &self.tabs
+    }
+
+    pub fn current_tab(&self) -> usize {
+        self.current
+    }
+
+    pub fn tab(&self, key: &str) -> &str {
+        self.tab_of.get(key).unwrap_or(&self.tabs[0])
+    }
+
+    // Replaces the tabs, e.g. with those of a session being loaded
+    pub fn set_tabs(&mut self, tabs: Vec<String>) {
+        if !tabs.is_empty() {
+            self.tabs = tabs;
+            self.current = 0;
+            self.tab_of.clear();
+        }
+    }
+
+    pub fn select_tab(&mut self, forward: bool) {
+        let count = self.tabs.len();
+        self.current = match forward {
+            true => (self.current + 1) % count,
+            false => (self.current + count - 1) % count,
+        };
+    }
+
+    pub fn new_tab(&mut self, name: String) {
+        self.tabs.push(name);
+        self.current = self.tabs.len() - 1;
+    }
+
+    // Switches to the tab with this name, adding it if there is none
+    pub fn open_tab(&mut self, name: &str) {
+        match self.tabs.iter().position(|tab| tab == name) {
+            Some(i) => self.current = i,
+            None => self.new_tab(name.to_owned()),
+        }
+    }
+
+    pub fn rename_tab(&mut self, name: String) {
+        let old = std::mem::replace(&mut self.tabs[self.current], name.to_owned());
+        self.tab_of
+            .values_mut()
+            .filter(|tab| **tab == old)
+            .for_each(|tab| *tab = name.to_owned());
+    }
+
+    // Closes the current tab, handing its queries to the tab before it
+    pub fn close_tab(&mut self) {
+        if self.tabs.len() == 1 {
+            return;
+        }
+        let closed = self.tabs.remove(self.current);
+        match self.current.checked_sub(1) {
+            Some(previous) => {
+                let previous = self.tabs[previous].to_owned();
+                self.tab_of
+                    .values_mut()
+                    .filter(|tab| **tab == closed)
+                    .for_each(|tab| *tab = previous.to_owned());
+                self.current -= 1;
+            }
+            // Unassigned queries already follow the first tab
+            None => self.tab_of.retain(|_, tab| *tab != closed),
+        }
+    }
+
+    // Puts a query in the tab at index
+    pub fn assign(&mut self, key: &str, index: usize) {
+        match index {
+            0 => self.tab_of.remove(key),
+            _ => self
+                .tab_of
+                .insert(key.to_owned(), self.tabs[index].to_owned()),
+        };
+    }
+
+    // As assign, creating the tab when no tab has that name
+    pub fn assign_named(&mut self, key: &str, tab: &str) {
+        let index = match self.tabs.iter().position(|t| t == tab) {
+            Some(index) => index,
+            None => {
+                self.tabs.push(tab.to_owned());
+                self.tabs.len() - 1
+            }
+        };
+        self.assign(key, index);
+    }
+
+    pub fn position(&self, key: &str) -> Option<usize> {
+        self.keys().position(|k| k == key)
+    }
+
+    pub fn is_pinned(&self, key: &str) -> bool {
+        self.pinned.contains(key)
+    }
+
+    pub fn toggle_pin(&mut self, key: &str) {
+        if !self.pinned.remove(key) {
+            self.pinned.insert(key.to_owned());
+        }
+        self.sort();
+    }
+
+    // Swaps with the neighbouring dataset, without crossing between pinned and unpinned
+    pub fn shift(&mut self, key: &str, up: bool) {
+        let keys = self.keys().cloned().collect::<Vec<_>>();
+        let Some(i) = keys.iter().position(|k| k == key) else {
+            return;
+        };
+        let neighbour = match up {
+            true => i.checked_sub(1).and_then(|j| keys.get(j)),
+            false => keys.get(i + 1),
+        };
+        let Some(neighbour) = neighbour else {
+            return;
+        };
+        if self.is_pinned(key) != self.is_pinned(neighbour) {
+            return;
+        }
+
+        let a = self.order.iter().position(|k| k == key);
+        let b = self.order.iter().position(|k| k == neighbour);
+        if let (Some(a), Some(b)) = (a, b) {
+            self.order.swap(a, b);
+        }
+    }
+
+    pub fn refresh_chart(&mut self, key: &str, area: Rect) {
+        let Some(data) = self.data.get(key) else {
+            return;
+        };
+        let size = (area.width, area.height);
+        if self.charts.get(key).is_some_and(|chart| {
+            chart.version == data.version
+                && chart.size == size
+                && chart.style == data.style
+                && chart.axis == data.axis
+                && chart.thresholds.iter().map(|(_, t)| t).eq(&data.thresholds)
+        }) {
+            return;
+        }
+
+        // Braille packs two dots per cell, so finer detail than that is never drawn
+        let columns = usize::max(area.width as usize * 2, 1);
+        let (min_x, max_x) = (data.bounds.mins.0, data.bounds.maxes.0);
+        let thin_all = |series: &BTreeMap<String, Vec<(f64, f64)>>| {
+            series
+                .iter()
+                .map(|(facet, points)| (facet.to_owned(), thin(points, min_x, max_x, columns)))
+                .collect()
+        };
+        let unit = data.axis.unit.unwrap_or(Unit::infer(&data.selection));
+        let mut chart = match data.style.is_stacked() {
+            true => {
+                let (facets, top) = stack(&data.facets, data.style, columns, area.height);
+                Chart {
+                    version: data.version,
+                    size,
+                    style: data.style,
+                    axis: data.axis.clone(),
+                    unit,
+                    facets,
+                    comparison: BTreeMap::default(),
+                    anomalies: BTreeMap::default(),
+                    snapshot: BTreeMap::default(),
+                    y_bounds: (0.0, top),
+                    thresholds: vec![],
+                }
+            }
+            false => Chart {
+                version: data.version,
+                size,
+                style: data.style,
+                axis: data.axis.clone(),
+                unit,
+                facets: thin_all(&data.facets),
+                comparison: thin_all(&data.comparison),
+                anomalies: data.anomalies.clone(),
+                snapshot: data
+                    .snapshot
+                    .as_ref()
+                    .map(|snapshot| thin_all(&ghost(snapshot, max_x)))
+                    .unwrap_or_default(),
+                y_bounds: (data.bounds.mins.1, data.bounds.maxes.1),
+                thresholds: vec![],
+            },
+        };
+
+        let axis = &data.axis;
+        let (min, max) = chart
+            .snapshot
+            .values()
+            .flatten()
+            .fold(chart.y_bounds, |(min, max), (_, y)| {
+                (min.min(*y), max.max(*y))
+            });
+        chart.y_bounds = (axis.min.unwrap_or(min), axis.max.unwrap_or(max));
+        if axis.log {
+            for points in chart
+                .facets
+                .values_mut()
+                .chain(chart.comparison.values_mut())
+                .chain(chart.anomalies.values_mut())
+                .chain(chart.snapshot.values_mut())
+            {
+                *points = points
+                    .iter()
+                    .filter_map(|(x, y)| Some((*x, axis.scale(*y)?)))
+                    .collect();
+            }
+            // Unless fixed, the axis spans whole powers of ten around what can be plotted
+            let (lowest, highest) = chart
+                .facets
+                .values()
+                .chain(chart.comparison.values())
+                .chain(chart.snapshot.values())
+                .flatten()
+                .fold((f64::MAX, f64::MIN), |(low, high), (_, y)| {
+                    (low.min(*y), high.max(*y))
+                });
+            let (lowest, highest) = match lowest <= highest {
+                true => (lowest.floor(), highest.ceil().max(lowest.floor() + 1.0)),
+                false => (0.0, 1.0),
+            };
+            chart.y_bounds = (
+                axis.min.and_then(|min| axis.scale(min)).unwrap_or(lowest),
+                axis.max.and_then(|max| axis.scale(max)).unwrap_or(highest),
+            );
+        }
+
+        // Unless fixed, the axis stretches to show every threshold
+        chart.thresholds = data
+            .thresholds
+            .iter()
+            .filter_map(|threshold| Some((axis.scale(threshold.value)?, threshold.clone())))
+            .collect();
+        for (y, _) in &chart.thresholds {
+            let (low, high) = match axis.log {
+                true => (y.floor(), y.ceil()),
+                false => (*y, *y),
+            };
+            if axis.min.is_none() {
+                chart.y_bounds.0 = chart.y_bounds.0.min(low);
+            }
+            if axis.max.is_none() {
+                chart.y_bounds.1 = chart.y_bounds.1.max(high);
+            }
+        }
+        self.charts.insert(key.to_owned(), chart);
+    }
+
+    pub fn chart(&self, key: &str) -> Option<&Chart> {
+        self.charts.get(key)
+    }
+
+    fn sort(&mut self) {
+        // Stable, so the order within each group is preserved
+        let pinned = &self.pinned;
+        self.order.sort_by_key(|key| !pinned.contains(key));
+    }
+}
+
+// Stacks facets in name order, filling each one's band between the facets below it and its
+// own value with dots a braille dot apart. Areas are filled across every column, linearly
+// between buckets, bars only across the first two thirds of each bucket. Returns the fills
+// and the top of the stack.
+fn stack(
+    facets: &BTreeMap<String, Vec<(f64, f64)>>,
+    style: ChartStyle,
+    columns: usize,
+    rows: u16,
+) -> (BTreeMap<String, Vec<(f64, f64)>>, f64) {
+    let mut xs: Vec<f64> = facets.values().flatten().map(|(x, _)| *x).collect();
+    xs.sort_by(f64::total_cmp);
+    xs.dedup();
+
+    // The top of each facet's band at every bucket
+    let mut tops: Vec<Vec<f64>> = vec![];
+    for points in facets.values() {
+        let below = tops.last().cloned().unwrap_or(vec![0.0; xs.len()]);
+        let top = xs
+            .iter()
+            .zip(below)
+            .map(|(x, below)| {
+                below
+                    + points
+                        .iter()
+                        .find(|(px, _)| px == x)
+                        .map_or(0.0, |(_, y)| y.max(0.0))
+            })
+            .collect();
+        tops.push(top);
+    }
+    let top = tops
+        .last()
+        .and_then(|top| top.iter().copied().reduce(f64::max))
+        .unwrap_or(0.0);
+    let (Some(first), Some(last)) = (xs.first().copied(), xs.last().copied()) else {
+        return (BTreeMap::default(), top);
+    };
+
+    // (x, bucket to the left, fraction of the way to the next bucket)
+    let column_width = (last - first) / columns as f64;
+    let samples: Vec<(f64, usize, f64)> = match style {
+        _ if xs.len() == 1 || column_width <= 0.0 => vec![(first, 0, 0.0)],
+        ChartStyle::StackedBar => xs
+            .windows(2)
+            .enumerate()
+            .flat_map(|(i, bucket)| {
+                let steps = ((bucket[1] - bucket[0]) * 2.0 / 3.0 / column_width).max(1.0) as usize;
+                (0..steps).map(move |step| (bucket[0] + step as f64 * column_width, i, 0.0))
+            })
+            .chain([(last, xs.len() - 1, 0.0)])
+            .collect(),
+        _ => (0..=columns)
+            .map(|column| {
+                let x = first + column as f64 * column_width;
+                let i = xs.partition_point(|bucket| *bucket <= x).saturating_sub(1);
+                let fraction = match xs.get(i + 1) {
+                    Some(next) => (x - xs[i]) / (next - xs[i]),
+                    None => 0.0,
+                };
+                (x, i, fraction)
+            })
+            .collect(),
+    };
+
+    let at = |band: &[f64], i: usize, fraction: f64| match band.get(i + 1) {
+        Some(next) => band[i] + (next - band[i]) * fraction,
+        None => band[i],
+    };
+    let step = top / (rows.max(1) as f64 * 4.0);
+    let zeros = vec![0.0; xs.len()];
+    let mut fills = BTreeMap::default();
+    for (n, facet) in facets.keys().enumerate() {
+        let below = match n {
+            0 => &zeros,
+            n => &tops[n - 1],
+        };
+        let mut points = vec![];
+        for (x, i, fraction) in &samples {
+            let (low, high) = (at(below, *i, *fraction), at(&tops[n], *i, *fraction));
+            let mut y = low;
+            while y < high && step > 0.0 {
+                points.push((*x, y));
+                y += step;
+            }
+            if high > low {
+                points.push((*x, high));
+            }
+        }
+        fills.insert(facet.to_owned(), points);
+    }
+    (fills, top)
+}
+
+// The snapshot shifted to end where the live data does, so the two overlap however long ago
+// it was taken
+fn ghost(snapshot: &Snapshot, end: f64) -> BTreeMap<String, Vec<(f64, f64)>> {
+    let taken_end = snapshot
+        .facets
+        .values()
+        .flatten()
+        .fold(f64::MIN, |end, (x, _)| end.max(*x));
+    snapshot
+        .facets
+        .iter()
+        .map(|(facet, points)| {
+            let shifted = points.iter().map(|(x, y)| (x + end - taken_end, *y));
+            (facet.to_owned(), shifted.collect())
+        })
+        .collect()
+}
+
+// Numbers off a series without a second query
+#[derive(Debug, PartialEq)]
+pub struct Stats {
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub p95: f64,
+    pub latest: f64,
+}
+
+impl Stats {
+    pub fn of(points: &[(f64, f64)]) -> Option<Self> {
+        let latest = points.iter().max_by(|a, b| a.0.total_cmp(&b.0))?.1;
+        let mut values = points.iter().map(|(_, y)| *y).collect::<Vec<_>>();
+        values.sort_by(f64::total_cmp);
+        // Nearest rank
+        let rank = ((values.len() as f64 * 0.95).ceil() as usize).saturating_sub(1);
+        Some(Stats {
+            min: values[0],
+            max: values[values.len() - 1],
+            mean: values.iter().sum::<f64>() / values.len() as f64,
+            p95: values[rank],
+            latest,
+        })
+    }
+}
+
+// Facets added together at each timestamp
+pub fn total(facets: &BTreeMap<String, Vec<(f64, f64)>>) -> Vec<(f64, f64)> {
+    let mut totals = BTreeMap::<i64, f64>::new();
+    for (x, y) in facets.values().flatten() {
+        *totals.entry(*x as i64).or_default() += y;
+    }
+    totals.into_iter().map(|(x, y)| (x as f64, y)).collect()
+}
+
+// Points further than `sigmas` standard deviations from the mean of the `window` points before
+// them. After a perfectly flat stretch any change at all counts.
+pub fn anomalies(
+    facets: &BTreeMap<String, Vec<(f64, f64)>>,
+    window: usize,
+    sigmas: f64,
+) -> BTreeMap<String, Vec<(f64, f64)>> {
+    if window < 2 || sigmas <= 0.0 {
+        return BTreeMap::default();
+    }
+    facets
+        .iter()
+        .map(|(facet, points)| {
+            let found: Vec<(f64, f64)> = points
+                .windows(window + 1)
+                .filter_map(|points| {
+                    let (history, (x, y)) = (&points[..window], points[window]);
+                    let mean = history.iter().map(|(_, y)| y).sum::<f64>() / window as f64;
+                    let variance = history.iter().map(|(_, y)| (y - mean).powi(2)).sum::<f64>()
+                        / window as f64;
+                    ((y - mean).abs() > sigmas * variance.sqrt()).then_some((x, y))
+                })
+                .collect();
+            (facet.to_owned(), found)
+        })
+        .filter(|(_, found)| !found.is_empty())
+        .collect()
+}
+
+// A column being thinned, with its lowest and highest point so far
+type Column = (usize, (f64, f64), (f64, f64));
+
+// Keeps the lowest and highest point per column, preserving spikes while dropping the rest
+fn thin(points: &[(f64, f64)], min_x: f64, max_x: f64, columns: usize) -> Vec<(f64, f64)> {
+    if points.len() <= columns * 2 || max_x <= min_x {
+        return points.to_vec();
+    }
+
+    let width = (max_x - min_x) / columns as f64;
+    let mut thinned: Vec<(f64, f64)> = Vec::with_capacity(columns * 2);
+    let mut column: Option<Column> = None;
+    for &point in points {
+        let i = ((point.0 - min_x) / width) as usize;
+        column = match column {
+            Some((j, low, high)) if j == i => Some((
+                i,
+                if point.1 < low.1 { point } else { low },
+                if point.1 > high.1 { point } else { high },
+            )),
+            previous => {
+                if let Some((_, low, high)) = previous {
+                    push_extremes(&mut thinned, low, high);
+                }
+                Some((i, point, point))
+            }
+        };
+    }
+    if let Some((_, low, high)) = column {
+        push_extremes(&mut thinned, low, high);
+    }
+    thinned
+}
+
+fn push_extremes(points: &mut Vec<(f64, f64)>, low: (f64, f64), high: (f64, f64)) {
+    let (first, second) = match low.0 <= high.0 {
+        true => (low, high),
+        false => (high, low),
+    };
+    points.push(first);
+    if first != second {
+        points.push(second);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dataset() -> Dataset {
+        Dataset {
+            query_alias: None,
+            note: String::new(),
+            links: vec![],
+            facets: BTreeMap::default(),
+            comparison: BTreeMap::default(),
+            anomalies: BTreeMap::default(),
+            snapshot: None,
+            bounds: Bounds {
+                mins: (0.0, 0.0),
+                maxes: (0.0, 0.0),
+            },
+            selection: String::new(),
+            histogram: vec![],
+            scalars: vec![],
+            facet_totals: vec![],
+            style: ChartStyle::default(),
+            axis: YAxis::default(),
+            thresholds: vec![],
+            history: vec![],
+            notice: None,
+            error: None,
+            version: 0,
+        }
+    }
+
+    #[test]
+    fn stats_use_the_nearest_rank_and_latest_timestamp() {
+        let points = (1..=20)
+            .rev()
+            .map(|i| (i as f64, i as f64))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            Stats::of(&points),
+            Some(Stats {
+                min: 1.0,
+                max: 20.0,
+                mean: 10.5,
+                p95: 19.0,
+                latest: 20.0,
+            })
+        );
+        assert_eq!(Stats::of(&[]), None);
+    }
+
+    #[test]
+    fn totals_add_facets_at_each_timestamp() {
+        let facets = BTreeMap::from([
+            ("api".to_owned(), vec![(0.0, 1.0), (60.0, 2.0)]),
+            ("web".to_owned(), vec![(60.0, 3.0), (120.0, 4.0)]),
+        ]);
+        assert_eq!(total(&facets), vec![(0.0, 1.0), (60.0, 5.0), (120.0, 4.0)]);
+    }
+
+    #[test]
+    fn anomalies_stray_from_the_rolling_mean() {
+        let mut points = (0..10)
+            .map(|i| (i as f64, 10.0 + (i % 2) as f64))
+            .collect::<Vec<_>>();
+        points.push((10.0, 50.0));
+        let facets = BTreeMap::from([
+            ("api".to_owned(), points),
+            ("web".to_owned(), vec![(0.0, 1.0); 8]),
+        ]);
+        assert_eq!(
+            anomalies(&facets, 5, 3.0),
+            BTreeMap::from([("api".to_owned(), vec![(10.0, 50.0)])])
+        );
+        // Turned off
+        assert!(anomalies(&facets, 5, 0.0).is_empty());
+        assert!(anomalies(&facets, 1, 3.0).is_empty());
+    }
+
+    #[test]
+    fn pinned_queries_come_first_and_shift_within_their_group() {
+        let mut datasets = Datasets::default();
+        for key in ["a", "b", "c", "d"] {
+            datasets.reserve(key, false);
+        }
+        datasets.toggle_pin("c");
+        assert_eq!(datasets.order, ["c", "a", "b", "d"]);
+
+        datasets.rekey("b", "e".to_owned());
+        datasets.toggle_pin("d");
+        assert_eq!(datasets.order, ["c", "d", "a", "e"]);
+
+        for (i, key) in ["a", "c", "d", "e"].into_iter().enumerate() {
+            datasets.insert(key.to_owned(), dataset());
+            assert_eq!(datasets.len(), i + 1);
+        }
+        // Never across the pinned boundary
+        datasets.shift("a", true);
+        assert_eq!(datasets.order, ["c", "d", "a", "e"]);
+        datasets.shift("a", false);
+        datasets.shift("d", true);
+        assert_eq!(datasets.order, ["d", "c", "e", "a"]);
+    }
+
+    #[test]
+    fn closing_a_tab_hands_its_queries_to_the_one_before() {
+        let mut datasets = Datasets::default();
+        datasets.reserve("a", false);
+        datasets.new_tab("errors".to_owned());
+        datasets.reserve("b", false);
+        datasets.new_tab("latency".to_owned());
+        datasets.reserve("c", false);
+        assert_eq!(datasets.tab("c"), "latency");
+
+        datasets.close_tab();
+        assert_eq!(datasets.current_tab(), 1);
+        assert_eq!(datasets.tab("c"), "errors");
+        datasets.close_tab();
+        assert_eq!(datasets.tabs(), [DEFAULT_TAB]);
+        assert_eq!(datasets.tab("b"), DEFAULT_TAB);
+        assert_eq!(datasets.tab("c"), DEFAULT_TAB);
+    }
+}
@@ -0,0 +1,169 @@
+use chrono::Local;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt::{self, Write},
+    fs::File,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::{
+    field::{Field, Visit},
+    Event, Level, Subscriber,
+};
+use tracing_subscriber::{
+    filter::Targets,
+    layer::{Context, SubscriberExt},
+    util::SubscriberInitExt,
+    Layer,
+};
+
+// Events kept for the diagnostics panel, the log file has the rest
+const RECENT_EVENTS: usize = 200;
+
+#[derive(Clone, Copy, Default)]
+pub struct RequestStats {
+    pub requests: u32,
+    pub failures: u32,
+    pub last_latency: Duration,
+    pub total_latency: Duration,
+}
+
+impl RequestStats {
+    pub fn mean_latency(&self) -> Duration {
+        self.total_latency / self.requests.max(1)
+    }
+}
+
+// Shared between the tracing layer, the refresh tasks and the diagnostics panel
+#[derive(Default)]
+pub struct Diagnostics {
+    events: Mutex<VecDeque<String>>,
+    requests: Mutex<BTreeMap<String, RequestStats>>,
+    // When each outstanding request was sent, by query
+    in_flight: Mutex<BTreeMap<String, Instant>>,
+}
+
+impl Diagnostics {
+    pub fn start_request(&self, query: &str) {
+        self.in_flight
+            .lock()
+            .expect("ERROR: Diagnostics lock poisoned!")
+            .insert(query.to_owned(), Instant::now());
+    }
+
+    // For requests that will never be recorded, as their task was stopped
+    pub fn cancel_request(&self, query: &str) {
+        self.in_flight
+            .lock()
+            .expect("ERROR: Diagnostics lock poisoned!")
+            .remove(query);
+    }
+
+    // How long the outstanding request for the query has taken so far
+    pub fn in_flight(&self, query: &str) -> Option<Duration> {
+        self.in_flight
+            .lock()
+            .expect("ERROR: Diagnostics lock poisoned!")
+            .get(query)
+            .map(Instant::elapsed)
+    }
+
+    pub fn record_request(&self, query: &str, latency: Duration, ok: bool) {
+        self.cancel_request(query);
+        let mut requests = self
+            .requests
+            .lock()
+            .expect("ERROR: Diagnostics lock poisoned!");
+        let stats = requests.entry(query.to_owned()).or_default();
+        stats.requests += 1;
+        stats.failures += u32::from(!ok);
+        stats.last_latency = latency;
+        stats.total_latency += latency;
+    }
+
+    pub fn requests(&self) -> Vec<(String, RequestStats)> {
+        self.requests
+            .lock()
+            .expect("ERROR: Diagnostics lock poisoned!")
+            .iter()
+            .map(|(query, stats)| (query.to_owned(), *stats))
+            .collect()
+    }
+
+    // Newest first
+    pub fn events(&self) -> Vec<String> {
+        self.events
+            .lock()
+            .expect("ERROR: Diagnostics lock poisoned!")
+            .iter()
+            .rev()
+            .cloned()
+            .collect()
+    }
+
+    fn push_event(&self, event: String) {
+        let mut events = self
+            .events
+            .lock()
+            .expect("ERROR: Diagnostics lock poisoned!");
+        if events.len() == RECENT_EVENTS {
+            events.pop_front();
+        }
+        events.push_back(event);
+    }
+}
+
+// Copies each event into the diagnostics panel as a single line
+struct Recent(Arc<Diagnostics>);
+
+impl<S: Subscriber> Layer<S> for Recent {
+    fn on_event(&self, event: &Event<'_>, _: Context<'_, S>) {
+        let mut line = format!(
+            "{} {:<5} ",
+            Local::now().format("%H:%M:%S"),
+            event.metadata().level()
+        );
+        event.record(&mut Fields(&mut line));
+        self.0.push_event(line);
+    }
+}
+
+struct Fields<'a>(&'a mut String);
+
+impl Visit for Fields<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "message" => self.0.push_str(value),
+            name => _ = write!(self.0, " {}={}", name, value),
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        match field.name() {
+            "message" => _ = write!(self.0, "{:?}", value),
+            name => _ = write!(self.0, " {}={:?}", name, value),
+        }
+    }
+}
+
+// Writes debug events from this app to a log file, truncated on each start, and keeps the
+// most recent for the diagnostics panel
+pub fn init(path: &Path) -> Arc<Diagnostics> {
+    let diagnostics = Arc::new(Diagnostics::default());
+    let file = File::create(path).ok().map(|file| {
+        tracing_subscriber::fmt::layer()
+            .with_ansi(false)
+            .with_writer(Mutex::new(file))
+    });
+    _ = tracing_subscriber::registry()
+        .with(
+            Targets::new()
+                .with_target("tui", Level::DEBUG)
+                .with_target("server", Level::DEBUG),
+        )
+        .with(file)
+        .with(Recent(diagnostics.clone()))
+        .try_init();
+    diagnostics
+}
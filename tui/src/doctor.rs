@@ -0,0 +1,190 @@
+use crossterm::terminal;
+use reqwest::Client;
+use server::{preflight::PreflightFailure, NewRelicClient};
+use std::{collections::HashMap, env, fs, path::Path, sync::OnceLock};
+
+use crate::{
+    auth::{find_account, find_api_key},
+    config::{find_config, Config},
+    i18n::Msg,
+    session,
+};
+
+enum Check {
+    Pass(String),
+    Warn(String),
+    Fail(String),
+}
+
+// Prints a pass/fail line per check, returning false if any failed
+pub fn run(
+    app_dir: &Path,
+    endpoint: &'static str,
+    account: &'static OnceLock<i64>,
+    api_key: &'static OnceLock<String>,
+) -> bool {
+    let mut report: Vec<(&str, Check)> = vec![];
+
+    let config = match find_config(app_dir) {
+        Some(config_path) => match Config::read(&config_path) {
+            Ok(config) => {
+                report.push(("Config", Check::Pass(config_path.display().to_string())));
+                config
+            }
+            Err(e) => {
+                report.push(("Config", Check::Fail(e.to_string())));
+                Config::default()
+            }
+        },
+        None => {
+            report.push((
+                "Config",
+                Check::Warn("not found, using defaults".to_owned()),
+            ));
+            Config::default()
+        }
+    };
+
+    let catalog_path = app_dir
+        .join("i18n")
+        .join(format!("{}.yaml", config.language()));
+    if let Ok(yaml) = fs::read_to_string(&catalog_path) {
+        report.push((
+            "Messages",
+            match serde_yaml::from_str::<HashMap<Msg, String>>(&yaml) {
+                Ok(_) => Check::Pass(catalog_path.display().to_string()),
+                Err(e) => Check::Fail(e.to_string()),
+            },
+        ));
+    }
+
+    let session_path = config.session_dir(app_dir).join("session.yaml");
+    report.push((
+        "Session",
+        match fs::read_to_string(&session_path) {
+            Ok(yaml) => match session::parse(&yaml) {
+                Ok(session) => Check::Pass(format!(
+                    "{} queries in {}",
+                    session.map_or(0, |session| session.len()),
+                    session_path.display()
+                )),
+                Err(e) => Check::Fail(e.to_string()),
+            },
+            Err(e) => Check::Fail(format!("{}: {}", session_path.display(), e)),
+        },
+    ));
+
+    let key = find_api_key(&config);
+    report.push((
+        "API key",
+        match &key {
+            Some((_, source)) => Check::Pass(format!("from {}", source)),
+            None => Check::Fail("not set, see the Credentials section of the readme".to_owned()),
+        },
+    ));
+    let id = match find_account(&config) {
+        Some((Ok(id), source)) => {
+            report.push(("Account", Check::Pass(format!("{} from {}", id, source))));
+            Some(id)
+        }
+        Some((Err(e), _)) => {
+            report.push(("Account", Check::Fail(e)));
+            None
+        }
+        None => {
+            report.push(("Account", Check::Fail("not set".to_owned())));
+            None
+        }
+    };
+
+    if let (Some((key, _)), Some(id)) = (key, id) {
+        let mut client = NewRelicClient::builder();
+        client
+            .url(endpoint)
+            .account(account.get_or_init(|| id))
+            .api_key(api_key.get_or_init(|| key))
+            .http_client(Client::builder());
+        let preflight = tokio::runtime::Runtime::new()
+            .map_err(|e| PreflightFailure::Network(e.to_string()))
+            .and_then(|runtime| runtime.block_on(client.preflight()));
+
+        let (network, auth, access) = match preflight {
+            Ok(()) => (
+                Check::Pass(endpoint.to_owned()),
+                Check::Pass("accepted".to_owned()),
+                Check::Pass(format!("account {} visible", id)),
+            ),
+            Err(PreflightFailure::Network(e)) => (
+                Check::Fail(e),
+                Check::Warn("not checked".to_owned()),
+                Check::Warn("not checked".to_owned()),
+            ),
+            Err(PreflightFailure::Auth(e)) => (
+                Check::Pass(endpoint.to_owned()),
+                Check::Fail(e),
+                Check::Warn("not checked".to_owned()),
+            ),
+            Err(PreflightFailure::Account(e)) => (
+                Check::Pass(endpoint.to_owned()),
+                Check::Pass("accepted".to_owned()),
+                Check::Fail(e),
+            ),
+        };
+        report.push(("Network", network));
+        report.push(("Auth", auth));
+        report.push(("Access", access));
+    }
+
+    let truecolor = env::var("COLORTERM")
+        .is_ok_and(|colorterm| colorterm == "truecolor" || colorterm == "24bit");
+    report.push((
+        "Colour",
+        match truecolor {
+            true => Check::Pass("truecolor".to_owned()),
+            false => Check::Warn("no truecolor support advertised in COLORTERM".to_owned()),
+        },
+    ));
+
+    let clipboard = ["pbcopy", "wl-copy", "xclip", "xsel"]
+        .into_iter()
+        .find(|tool| on_path(tool));
+    report.push((
+        "Clipboard",
+        match clipboard {
+            Some(tool) => Check::Pass(tool.to_owned()),
+            None => Check::Warn("no pbcopy, wl-copy, xclip or xsel found".to_owned()),
+        },
+    ));
+
+    report.push((
+        "Terminal",
+        match terminal::size() {
+            Ok((columns, rows)) if columns >= 80 && rows >= 24 => {
+                Check::Pass(format!("{}x{}", columns, rows))
+            }
+            Ok((columns, rows)) => {
+                Check::Warn(format!("{}x{}, at least 80x24 recommended", columns, rows))
+            }
+            Err(e) => Check::Warn(e.to_string()),
+        },
+    ));
+
+    let mut healthy = true;
+    for (name, check) in report {
+        let (status, detail) = match check {
+            Check::Pass(detail) => ("PASS", detail),
+            Check::Warn(detail) => ("WARN", detail),
+            Check::Fail(detail) => {
+                healthy = false;
+                ("FAIL", detail)
+            }
+        };
+        println!("[{}] {:<10} {}", status, name, detail);
+    }
+    healthy
+}
+
+fn on_path(tool: &str) -> bool {
+    env::var_os("PATH")
+        .is_some_and(|paths| env::split_paths(&paths).any(|dir| dir.join(tool).is_file()))
+}
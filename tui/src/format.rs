@@ -0,0 +1,220 @@
+use chrono::{DateTime, FixedOffset, Local, Utc};
+use std::env;
+
+// Decimal and digit-grouping separators used when displaying numbers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NumberFormat {
+    pub decimal: char,
+    pub grouping: Option<char>,
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        NumberFormat {
+            decimal: '.',
+            grouping: Some(','),
+        }
+    }
+}
+
+impl NumberFormat {
+    // Accepts POSIX-style locales such as "de_DE.UTF-8" or plain language codes like "fr"
+    pub fn from_locale(locale: &str) -> Self {
+        let language = locale
+            .split(['_', '-', '.'])
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        let (decimal, grouping) =
+            match language.as_str() {
+                "de" | "es" | "it" | "nl" | "pt" | "da" | "id" | "tr" | "el" | "ro" | "hr"
+                | "sl" => (',', Some('.')),
+                "fr" | "ru" | "pl" | "sv" | "fi" | "nb" | "no" | "cs" | "sk" | "uk" | "hu"
+                | "bg" => (',', Some('\u{202f}')),
+                "c" | "posix" => ('.', None),
+                _ => ('.', Some(',')),
+            };
+        NumberFormat { decimal, grouping }
+    }
+
+    // Follows the usual precedence of LC_ALL, then LC_NUMERIC, then LANG
+    pub fn detect() -> Self {
+        ["LC_ALL", "LC_NUMERIC", "LANG"]
+            .iter()
+            .filter_map(|var| env::var(var).ok())
+            .find(|locale| !locale.is_empty())
+            .map(|locale| NumberFormat::from_locale(&locale))
+            .unwrap_or_default()
+    }
+
+    pub fn format(&self, value: f64, precision: usize) -> String {
+        if !value.is_finite() {
+            return value.to_string();
+        }
+
+        let digits = format!("{:.*}", precision, value.abs());
+        let (integer, fraction) = digits.split_once('.').unwrap_or((&digits, ""));
+
+        let mut formatted = String::new();
+        if value < 0.0 && digits.chars().any(|c| c.is_ascii_digit() && c != '0') {
+            formatted.push('-');
+        }
+        for (i, c) in integer.chars().enumerate() {
+            if i > 0 && (integer.len() - i) % 3 == 0 {
+                if let Some(grouping) = self.grouping {
+                    formatted.push(grouping);
+                }
+            }
+            formatted.push(c);
+        }
+        if !fraction.is_empty() {
+            formatted.push(self.decimal);
+            formatted.push_str(fraction);
+        }
+        formatted
+    }
+}
+
+// Zone that timestamps are displayed in
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Timezone {
+    #[default]
+    Local,
+    Utc,
+    Offset(FixedOffset),
+}
+
+impl Timezone {
+    // Accepts "local", "utc" or an offset from UTC such as "+05:30" or "-08"
+    pub fn parse(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase();
+        match name.as_str() {
+            "local" => return Some(Timezone::Local),
+            "utc" | "z" => return Some(Timezone::Utc),
+            _ => {}
+        }
+        let sign = match name.chars().next()? {
+            '+' => 1,
+            '-' => -1,
+            _ => return None,
+        };
+        let (hours, minutes) = name[1..].split_once(':').unwrap_or((&name[1..], "0"));
+        let hours = hours.parse::<i32>().ok().filter(|hours| *hours <= 14)?;
+        let minutes = minutes
+            .parse::<i32>()
+            .ok()
+            .filter(|minutes| *minutes < 60)?;
+        FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60)).map(Timezone::Offset)
+    }
+
+    pub fn at(&self, seconds: i64) -> Option<DateTime<FixedOffset>> {
+        let time = DateTime::<Utc>::from_timestamp(seconds, 0)?;
+        Some(match self {
+            Timezone::Local => time.with_timezone(&Local).fixed_offset(),
+            Timezone::Utc => time.fixed_offset(),
+            Timezone::Offset(offset) => time.with_timezone(offset),
+        })
+    }
+
+    pub fn now(&self) -> DateTime<FixedOffset> {
+        let now = Utc::now();
+        self.at(now.timestamp())
+            .unwrap_or_else(|| now.fixed_offset())
+    }
+
+    pub fn format(&self, seconds: f64, format: &str) -> String {
+        self.at(seconds as i64)
+            .map(|time| time.format(format).to_string())
+            .unwrap_or_default()
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Timezone::Local => "local".to_owned(),
+            Timezone::Utc => "UTC".to_owned(),
+            Timezone::Offset(offset) => format!("UTC{}", offset),
+        }
+    }
+
+    // Local, then UTC, then the configured offset if there is one
+    pub fn next(self, configured: Timezone) -> Self {
+        match (self, configured) {
+            (Timezone::Local, _) => Timezone::Utc,
+            (Timezone::Utc, Timezone::Offset(_)) => configured,
+            _ => Timezone::Local,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locales_pick_their_separators() {
+        assert_eq!(
+            NumberFormat::from_locale("en_US.UTF-8"),
+            NumberFormat::default()
+        );
+        assert_eq!(
+            NumberFormat::from_locale("de_DE.UTF-8"),
+            NumberFormat {
+                decimal: ',',
+                grouping: Some('.'),
+            }
+        );
+        assert_eq!(
+            NumberFormat::from_locale("fr"),
+            NumberFormat {
+                decimal: ',',
+                grouping: Some('\u{202f}'),
+            }
+        );
+        for locale in ["C", "POSIX", "C.UTF-8"] {
+            assert_eq!(
+                NumberFormat::from_locale(locale),
+                NumberFormat {
+                    decimal: '.',
+                    grouping: None,
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn digits_are_grouped_in_threes() {
+        let format = NumberFormat::default();
+        assert_eq!(format.format(123.0, 0), "123");
+        assert_eq!(format.format(1234.0, 0), "1,234");
+        assert_eq!(format.format(1234567.0, 2), "1,234,567.00");
+        assert_eq!(format.format(-1234567.0, 0), "-1,234,567");
+    }
+
+    #[test]
+    fn separators_follow_the_locale() {
+        assert_eq!(NumberFormat::from_locale("de").format(1234.5, 1), "1.234,5");
+        assert_eq!(
+            NumberFormat::from_locale("fr").format(1234567.25, 2),
+            "1\u{202f}234\u{202f}567,25"
+        );
+        assert_eq!(
+            NumberFormat::from_locale("C").format(1234567.5, 1),
+            "1234567.5"
+        );
+    }
+
+    #[test]
+    fn negatives_that_round_to_zero_lose_their_sign() {
+        let format = NumberFormat::default();
+        assert_eq!(format.format(-0.001, 2), "0.00");
+        assert_eq!(format.format(-0.4, 0), "0");
+        assert_eq!(format.format(-0.005, 3), "-0.005");
+    }
+
+    #[test]
+    fn non_finite_values_are_shown_as_is() {
+        let format = NumberFormat::default();
+        assert_eq!(format.format(f64::NAN, 2), "NaN");
+        assert_eq!(format.format(f64::NEG_INFINITY, 2), "-inf");
+    }
+}
@@ -0,0 +1,551 @@
+use serde::Deserialize;
+use std::{collections::HashMap, env, fs, path::Path};
+use tracing::{debug, warn};
+
+// Every user-facing string, keyed in catalog files by its snake_case name
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Msg {
+    ReloadSession,
+    SaveSession,
+    RenameQuery,
+    ExportDashboard,
+    AddLink,
+    EditNote,
+    EditAxis,
+    EditThresholds,
+    EditVariables,
+    TimeRangeTitle,
+    TimeRangeCurrent,
+    TimeRangePerQuery,
+    TimeRangeCustom,
+    DatePickerTitle,
+    DatePickerHelp,
+    EnterQuery,
+    InvalidQuery,
+    Details,
+    StatsRange,
+    StatsLatest,
+    SnapshotFrom,
+    Anomalies,
+    TooSmall,
+    AnomalyAt,
+    Time,
+    ActiveQueries,
+    Loading,
+    Paused,
+    ReadOnly,
+    Traces,
+    TracesLoading,
+    TraceErrors,
+    Trace,
+    TraceSpans,
+    LoadingSpans,
+    NoSpans,
+    Waterfall,
+    TracesHelp,
+    OpenIssues,
+    OpenIssuesLoading,
+    AlertsHelp,
+    Dashboards,
+    DashboardsLoading,
+    ImportHelp,
+    Entities,
+    EntitiesLoading,
+    BrowserApps,
+    BrowserAppsLoading,
+    MobileApps,
+    MobileAppsLoading,
+    Crashes,
+    CrashesLoading,
+    CrashLatest,
+    Console,
+    ConsoleLoading,
+    ConsoleResponse,
+    Hosts,
+    HostsLoading,
+    Kubernetes,
+    KubernetesLoading,
+    ServiceLevels,
+    ServiceLevelsLoading,
+    Workloads,
+    WorkloadsLoading,
+    WorkloadsHelp,
+    FetchingWorkload,
+    WorkloadEmpty,
+    Templates,
+    NoTemplates,
+    TemplateVariable,
+    SloBudget,
+    SloBurn,
+    Selected,
+    LoadingQuery,
+    QueryFailed,
+    NoData,
+    NoDataWindow,
+    VsPrevious,
+    Acknowledging,
+    Acknowledged,
+    AcknowledgeFailed,
+    FetchingCondition,
+    NoCondition,
+    ConditionFailed,
+    FetchingDashboard,
+    NothingImported,
+    Exporting,
+    Exported,
+    ExportFailed,
+    NothingToExport,
+    PreflightTitle,
+    PreflightNetwork,
+    PreflightAuth,
+    PreflightAccount,
+    PreflightUnchecked,
+    PreflightNetworkHint,
+    PreflightAuthHint,
+    PreflightAccountHint,
+    PreflightHelp,
+    StatusConnecting,
+    StatusOk,
+    StatusError,
+    StatusRefresh,
+    StatusRefreshPaused,
+    StatusFetching,
+    Queued,
+    StatusAccount,
+    StatusTimezone,
+    ThresholdCrossed,
+    ConfigReloaded,
+    ConfigInvalid,
+    HintsDefault,
+    HintsDashboard,
+    HintsScrub,
+    HintsInput,
+    HintsView,
+    HintsConsole,
+    HelpTitle,
+    HelpGraph,
+    HelpZoom,
+    HelpDashboard,
+    HelpInputs,
+    HelpDatePicker,
+    HelpViews,
+    HelpConsole,
+    HelpScroll,
+    DiagnosticsTitle,
+    DiagnosticsRequests,
+    DiagnosticsColumns,
+    DiagnosticsNoRequests,
+    DiagnosticsEvents,
+    DiagnosticsNoEvents,
+    DiagnosticsClose,
+    KeyDiagnostics,
+    ErrorTitle,
+    ErrorDismiss,
+    PaletteTitle,
+    PaletteEmpty,
+    KeyPalette,
+    KeySave,
+    KeyColourBlind,
+    KeyFacet,
+    KeySnapshot,
+    KeyChartStyle,
+    KeyTimezone,
+    KeyAxis,
+    KeyThresholds,
+    KeyNextTab,
+    KeyNewTab,
+    KeyRenameTab,
+    KeyMoveTab,
+    KeyCloseTab,
+    RenameTab,
+    KeyQuit,
+    KeySuspend,
+    KeyEdit,
+    KeyDuplicate,
+    KeySelect,
+    KeyTopBottom,
+    KeyHalfPage,
+    KeyCount,
+    KeyReorder,
+    KeyPin,
+    KeyDelete,
+    KeyRename,
+    KeyNote,
+    KeyLink,
+    KeyOpenLinks,
+    KeyQueryBuilder,
+    KeyTimeRange,
+    KeyDashboard,
+    KeyTraces,
+    KeyEntities,
+    KeyBrowser,
+    KeyMobile,
+    KeyConsole,
+    KeyHosts,
+    KeySort,
+    KeyKubernetes,
+    KeyServiceLevels,
+    KeyWorkloads,
+    KeyTemplates,
+    KeyVariables,
+    KeyParent,
+    KeyConsoleEdit,
+    KeyConsoleRun,
+    KeyScroll,
+    KeyAlerts,
+    KeyImport,
+    KeyExport,
+    KeyLayout,
+    KeyScrubStart,
+    KeyHelp,
+    KeyScrubMove,
+    KeyZoomIn,
+    KeyZoomOut,
+    KeyClearScrub,
+    KeyExpand,
+    KeyConfirm,
+    KeyCancel,
+    KeyCursor,
+    KeyErase,
+    KeyDatePicker,
+    KeyField,
+    KeyAdjust,
+    KeyBound,
+    KeyApply,
+    KeyMove,
+    KeyOpen,
+    KeyRefresh,
+    KeyAcknowledge,
+    KeyBack,
+}
+
+impl Msg {
+    fn english(self) -> &'static str {
+        match self {
+            Msg::ReloadSession => {
+                "A previous session was found. Would you like to reload its queries? y/n"
+            }
+            Msg::SaveSession => "The session has unsaved changes. Would you like to save them? y/n",
+            Msg::RenameQuery => "Rename query",
+            Msg::ExportDashboard => "Export to dashboard named",
+            Msg::AddLink => "Add link",
+            Msg::EditNote => "Edit note",
+            Msg::EditAxis => "Y axis: log, linear, min=<n>, max=<n>, unit=count|ms|s|%|bytes|auto",
+            Msg::EditThresholds => "Thresholds: value:label:colour, separated by commas",
+            Msg::EditVariables => "Variables used as $name in queries: name=value, separated by commas",
+            Msg::TimeRangeTitle => "Time range for all queries",
+            Msg::TimeRangeCurrent => "Current: {} UNTIL {}",
+            Msg::TimeRangePerQuery => "Current: per query",
+            Msg::TimeRangeCustom => "{}  or custom: <since> [UNTIL <until>]",
+            Msg::DatePickerTitle => "Set {} (Tab to switch)",
+            Msg::DatePickerHelp => "h/l: field  j/k: adjust  Enter: apply  Esc: cancel",
+            Msg::EnterQuery => "Enter query: ",
+            Msg::InvalidQuery => "Invalid query: {}",
+            Msg::Details => "Details",
+            Msg::StatsRange => "min {}  max {}  avg {}",
+            Msg::StatsLatest => "p95 {}  latest {}",
+            Msg::SnapshotFrom => "vs snapshot from {}",
+            Msg::Anomalies => "Anomalies",
+            Msg::TooSmall => "Terminal too small: {}x{}, needs at least {}x{}",
+            Msg::AnomalyAt => "{} at {}: {}",
+            Msg::Time => "Time",
+            Msg::ActiveQueries => "Active Queries",
+            Msg::Loading => "loading {}/{}",
+            Msg::Paused => "paused",
+            Msg::ReadOnly => "read-only",
+            Msg::Traces => "Traces",
+            Msg::TracesLoading => "Traces (loading)",
+            Msg::TraceErrors => " ({} errors)",
+            Msg::Trace => "Trace {}",
+            Msg::TraceSpans => "Trace {} ({} spans)",
+            Msg::LoadingSpans => "Loading spans...",
+            Msg::NoSpans => "No spans found",
+            Msg::Waterfall => "Waterfall",
+            Msg::TracesHelp => "Enter to view spans, r to refresh, Esc to go back",
+            Msg::OpenIssues => "Open Issues",
+            Msg::OpenIssuesLoading => "Open Issues (loading)",
+            Msg::AlertsHelp => {
+                "a to acknowledge, Enter to chart the condition, r to refresh, Esc to go back"
+            }
+            Msg::Dashboards => "Dashboards",
+            Msg::DashboardsLoading => "Dashboards (loading)",
+            Msg::ImportHelp => {
+                "Enter to import the dashboard's queries, r to refresh, Esc to go back"
+            }
+            Msg::Entities => "Entities: Enter to add queries, r to refresh, Esc to go back",
+            Msg::EntitiesLoading => "Entities (loading)",
+            Msg::BrowserApps => "Browser apps: Enter to open a tab of page and error queries, r to refresh, Esc to go back",
+            Msg::BrowserAppsLoading => "Browser apps (loading)",
+            Msg::MobileApps => "Mobile apps: Enter to chart crashes and list crash groups, r to refresh, Esc to go back",
+            Msg::MobileAppsLoading => "Mobile apps (loading)",
+            Msg::Crashes => "Crashes in {}: j/k to select, r to refresh, Esc to go back",
+            Msg::CrashesLoading => "Crashes in {} (loading)",
+            Msg::CrashLatest => "Latest crash",
+            Msg::Console => "NerdGraph: e to edit, Enter to run, Esc to go back",
+            Msg::ConsoleLoading => "Response (running)",
+            Msg::ConsoleResponse => "Response",
+            Msg::Hosts => "Hosts: Enter to chart, s to sort, r to refresh, Esc to go back",
+            Msg::HostsLoading => "Hosts (loading)",
+            Msg::Kubernetes => "Kubernetes {}: Enter to open, Backspace to go up, r to refresh, Esc to go back",
+            Msg::KubernetesLoading => "Kubernetes {} (loading)",
+            Msg::ServiceLevels => "Service levels: Enter to chart, r to refresh, Esc to go back",
+            Msg::ServiceLevelsLoading => "Service levels (loading)",
+            Msg::Workloads => "Workloads",
+            Msg::WorkloadsLoading => "Workloads (loading)",
+            Msg::WorkloadsHelp => "Enter to add the golden signals of its applications, r to refresh, Esc to go back",
+            Msg::FetchingWorkload => "Fetching the workload's entities...",
+            Msg::WorkloadEmpty => "{} has no APM applications",
+            Msg::Templates => "Templates: Enter to insert, Esc to go back",
+            Msg::NoTemplates => "No templates yet, add them under templates: in the config",
+            Msg::TemplateVariable => "Value for {} (Up/Down for recent values: {})",
+            Msg::SloBudget => "budget",
+            Msg::SloBurn => "burn",
+            Msg::Selected => "Selected {} to {}",
+            Msg::LoadingQuery => "Loading...",
+            Msg::QueryFailed => "Query failed",
+            Msg::NoData => "No data in range",
+            Msg::NoDataWindow => "SINCE {} UNTIL {}",
+            Msg::VsPrevious => "{} vs previous",
+            Msg::Acknowledging => "Acknowledging {}...",
+            Msg::Acknowledged => "Acknowledged {}",
+            Msg::AcknowledgeFailed => "Could not acknowledge {}: {}",
+            Msg::FetchingCondition => "Fetching condition query...",
+            Msg::NoCondition => "Issue has no NRQL condition",
+            Msg::ConditionFailed => "Could not chart condition: {}",
+            Msg::FetchingDashboard => "Fetching dashboard...",
+            Msg::NothingImported => "Nothing imported from {}, {} queries skipped",
+            Msg::Exporting => "exporting",
+            Msg::Exported => "exported to {}",
+            Msg::ExportFailed => "export failed: {}",
+            Msg::NothingToExport => "nothing to export",
+            Msg::PreflightTitle => "Could not connect to New Relic",
+            Msg::PreflightNetwork => "Network",
+            Msg::PreflightAuth => "API key",
+            Msg::PreflightAccount => "Account access",
+            Msg::PreflightUnchecked => "not checked",
+            Msg::PreflightNetworkHint => {
+                "Check your connection, VPN or proxy settings, and that api.newrelic.com is reachable."
+            }
+            Msg::PreflightAuthHint => {
+                "Use a User key (NRAK-...), not a license or ingest key. Set NR_API_KEY, or remove the stored keychain entry to be prompted again."
+            }
+            Msg::PreflightAccountHint => {
+                "Check NR_ACCOUNT (or account in config.yaml) is the right ID, and that the key's user has access to it."
+            }
+            Msg::PreflightHelp => "r to retry, c to continue anyway, q to quit",
+            Msg::StatusConnecting => "API: waiting",
+            Msg::StatusOk => "API: ok",
+            Msg::StatusError => "API: last request failed",
+            Msg::StatusRefresh => "refresh in {}s",
+            Msg::StatusRefreshPaused => "refresh paused",
+            Msg::StatusFetching => "fetching for {}s",
+            Msg::Queued => "(queued)",
+            Msg::StatusAccount => "account {}",
+            Msg::StatusTimezone => "times in {}",
+            Msg::ThresholdCrossed => "{} crossed {}",
+            Msg::ConfigReloaded => "Config reloaded",
+            Msg::ConfigInvalid => "Config not reloaded: {}",
+            Msg::HintsDefault => {
+                "e edit  j/k select  p pin  x delete  r rename  t time  d dashboard  T/E/A/I views  : commands  ? help  q quit"
+            }
+            Msg::HintsDashboard => "j/k select  Enter expand  d back  L layout  q quit",
+            Msg::HintsScrub => "Shift+arrows scrub  Enter zoom  Backspace zoom out  Esc clear",
+            Msg::HintsInput => "Enter confirm  Esc cancel",
+            Msg::HintsView => "j/k move  Enter select  r refresh  Esc back",
+            Msg::HintsConsole => "e edit  Enter run  j/k scroll  Esc back",
+            Msg::HelpTitle => "Keybindings",
+            Msg::HelpGraph => "Queries",
+            Msg::HelpZoom => "Time window selection",
+            Msg::HelpDashboard => "Dashboard",
+            Msg::HelpInputs => "Text inputs",
+            Msg::HelpDatePicker => "Date picker",
+            Msg::HelpViews => "Traces, entities, browser and mobile apps, hosts, Kubernetes, service levels, workloads, alerts and import",
+            Msg::HelpConsole => "NerdGraph console",
+            Msg::HelpScroll => "j/k scroll, Esc close",
+            Msg::DiagnosticsTitle => "Diagnostics",
+            Msg::DiagnosticsRequests => "Requests by query",
+            Msg::DiagnosticsColumns => "requests  failed      last      mean  query",
+            Msg::DiagnosticsNoRequests => "No requests yet",
+            Msg::DiagnosticsEvents => "Recent events, newest first",
+            Msg::DiagnosticsNoEvents => "No events yet",
+            Msg::DiagnosticsClose => "F12/Esc close",
+            Msg::KeyDiagnostics => "Show requests and recent internal events",
+            Msg::ErrorTitle => "Error",
+            Msg::ErrorDismiss => "Press any key to dismiss",
+            Msg::PaletteTitle => "Commands",
+            Msg::PaletteEmpty => "No matching commands",
+            Msg::KeyPalette => "Search all commands",
+            Msg::KeySave => "Save the session",
+            Msg::KeyColourBlind => "Toggle the colour-blind palette",
+            Msg::KeyFacet => "Cycle the facet summarised in the details panel",
+            Msg::KeySnapshot => "Freeze the selected query as a snapshot to compare against, or drop it",
+            Msg::KeyChartStyle => "Cycle line, scatter, stacked area and stacked bar charts",
+            Msg::KeyTimezone => "Switch times between local, UTC and the configured zone",
+            Msg::KeyAxis => "Set the y axis scale, bounds and unit",
+            Msg::KeyThresholds => "Set threshold lines",
+            Msg::KeyNextTab => "Switch to the next or previous tab",
+            Msg::KeyNewTab => "Open a new tab",
+            Msg::KeyRenameTab => "Rename the current tab",
+            Msg::KeyMoveTab => "Move the selected query to the next tab",
+            Msg::KeyCloseTab => "Close the current tab, keeping its queries",
+            Msg::RenameTab => "Rename tab",
+            Msg::KeyQuit => "Quit",
+            Msg::KeySuspend => "Suspend to the shell, resume with fg",
+            Msg::KeyEdit => "Enter a new query",
+            Msg::KeyDuplicate => "Copy the selected query into the query box, to add a changed copy",
+            Msg::KeySelect => "Select the next or previous query",
+            Msg::KeyTopBottom => "Select the first or last query",
+            Msg::KeyHalfPage => "Move the selection half a page down or up",
+            Msg::KeyCount => "Repeat a motion, e.g. 5j moves down five queries",
+            Msg::KeyReorder => "Move the selected query down or up",
+            Msg::KeyPin => "Pin or unpin the selected query",
+            Msg::KeyDelete => "Delete the selected query",
+            Msg::KeyRename => "Rename the selected query",
+            Msg::KeyNote => "Edit the selected query's note",
+            Msg::KeyLink => "Add a link to the selected query",
+            Msg::KeyOpenLinks => "Open the selected query's links",
+            Msg::KeyQueryBuilder => "Open the selected query in the query builder",
+            Msg::KeyTimeRange => "Change the time range",
+            Msg::KeyDashboard => "Toggle the dashboard",
+            Msg::KeyTraces => "Browse traces",
+            Msg::KeyEntities => "Browse APM applications",
+            Msg::KeyBrowser => "Browse browser (RUM) applications",
+            Msg::KeyMobile => "Browse mobile applications and their crashes",
+            Msg::KeyConsole => "Run raw GraphQL against NerdGraph",
+            Msg::KeyHosts => "Browse infrastructure hosts",
+            Msg::KeySort => "Sort hosts by the next column",
+            Msg::KeyKubernetes => "Explore Kubernetes clusters, namespaces and pods",
+            Msg::KeyServiceLevels => "Show how each service level is tracking its target",
+            Msg::KeyWorkloads => "Browse workloads and their status",
+            Msg::KeyTemplates => "Insert a query from the template library",
+            Msg::KeyVariables => "Edit the session variables, re-running queries that use them",
+            Msg::KeyParent => "Go up a level",
+            Msg::KeyConsoleEdit => "Edit the query",
+            Msg::KeyConsoleRun => "Run the query",
+            Msg::KeyScroll => "Scroll the response",
+            Msg::KeyAlerts => "Browse open alert issues",
+            Msg::KeyImport => "Import queries from a dashboard",
+            Msg::KeyExport => "Export queries to a dashboard",
+            Msg::KeyLayout => "Cycle layout presets",
+            Msg::KeyScrubStart => "Start selecting a time window",
+            Msg::KeyHelp => "Show this help",
+            Msg::KeyScrubMove => "Move the selected window",
+            Msg::KeyZoomIn => "Zoom into the selected window",
+            Msg::KeyZoomOut => "Zoom back out",
+            Msg::KeyClearScrub => "Clear the selection",
+            Msg::KeyExpand => "Expand or shrink the selected panel",
+            Msg::KeyConfirm => "Confirm",
+            Msg::KeyCancel => "Cancel",
+            Msg::KeyCursor => "Move the cursor",
+            Msg::KeyErase => "Delete the previous character",
+            Msg::KeyDatePicker => "Pick a SINCE or UNTIL date",
+            Msg::KeyField => "Select a field",
+            Msg::KeyAdjust => "Increase or decrease the field",
+            Msg::KeyBound => "Switch between SINCE and UNTIL",
+            Msg::KeyApply => "Apply the date to the query",
+            Msg::KeyMove => "Move the selection",
+            Msg::KeyOpen => "Open or add the selected item",
+            Msg::KeyRefresh => "Reload the list",
+            Msg::KeyAcknowledge => "Acknowledge the selected issue",
+            Msg::KeyBack => "Go back",
+        }
+    }
+}
+
+// Translations loaded over the built-in English strings, which fill any gaps
+#[derive(Debug, Default)]
+pub struct Catalog {
+    messages: HashMap<Msg, String>,
+}
+
+impl Catalog {
+    // Reads <dir>/<language>.yaml, e.g. de.yaml, if one exists
+    pub fn load(dir: &Path, language: &str) -> Self {
+        match fs::read_to_string(dir.join(format!("{}.yaml", language))) {
+            Ok(yaml) => Catalog::parse(&yaml, language),
+            Err(e) => {
+                debug!("no {} catalog, using English: {}", language, e);
+                Catalog::default()
+            }
+        }
+    }
+
+    // A catalog that fails to parse is ignored as a whole rather than stopping the app
+    fn parse(yaml: &str, language: &str) -> Self {
+        match serde_yaml::from_str(yaml) {
+            Ok(messages) => Catalog { messages },
+            Err(e) => {
+                warn!("invalid {} catalog, using English: {}", language, e);
+                Catalog::default()
+            }
+        }
+    }
+
+    pub fn get(&self, msg: Msg) -> &str {
+        self.messages
+            .get(&msg)
+            .map(|message| message.as_str())
+            .unwrap_or(msg.english())
+    }
+
+    // Substitutes each {} placeholder in turn
+    pub fn fill(&self, msg: Msg, args: &[&str]) -> String {
+        let mut parts = self.get(msg).split("{}");
+        let mut filled = parts.next().unwrap_or_default().to_owned();
+        for (i, part) in parts.enumerate() {
+            filled.push_str(args.get(i).copied().unwrap_or_default());
+            filled.push_str(part);
+        }
+        filled
+    }
+}
+
+// Language code from LC_ALL, LC_MESSAGES or LANG, e.g. "de" for de_DE.UTF-8
+pub fn detect_language() -> String {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|locale| !locale.is_empty())
+        .and_then(|locale| locale.split(['_', '-', '.']).next().map(str::to_lowercase))
+        .unwrap_or("en".to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translations_override_english() {
+        let catalog = Catalog::parse("query_failed: Abfrage fehlgeschlagen\n", "de");
+        assert_eq!(catalog.get(Msg::QueryFailed), "Abfrage fehlgeschlagen");
+        assert_eq!(catalog.get(Msg::NoData), "No data in range");
+    }
+
+    #[test]
+    fn broken_or_missing_catalogs_fall_back_to_english() {
+        for yaml in [
+            "query_failed: [unclosed",
+            "not_a_message: x\n",
+            "- a list\n",
+        ] {
+            let catalog = Catalog::parse(yaml, "de");
+            assert_eq!(catalog.get(Msg::QueryFailed), "Query failed", "{}", yaml);
+        }
+        let catalog = Catalog::load(Path::new("/nonexistent"), "de");
+        assert_eq!(catalog.get(Msg::QueryFailed), "Query failed");
+    }
+
+    #[test]
+    fn placeholders_are_filled_in_order() {
+        let catalog = Catalog::default();
+        assert_eq!(
+            catalog.fill(Msg::NothingImported, &["Ops", "3"]),
+            "Nothing imported from Ops, 3 queries skipped"
+        );
+        assert_eq!(
+            catalog.fill(Msg::NothingImported, &["Ops"]),
+            "Nothing imported from Ops,  queries skipped"
+        );
+    }
+}
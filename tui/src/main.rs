@@ -1,46 +1,54 @@
+mod action;
 mod app;
+mod auth;
+mod axis;
 mod backend;
+mod config;
+mod dataset;
+mod diagnostics;
+mod doctor;
+mod format;
+mod i18n;
 pub mod parser;
 pub mod query;
+mod script;
+mod session;
+mod template;
+#[cfg(test)]
+mod testing;
+mod threshold;
 mod ui;
 
 use app::App;
 use backend::Backend;
+use config::{find_config, Config, ConfigWatch};
 use crossterm::{
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
 };
+use i18n::Catalog;
 use ratatui::{backend::CrosstermBackend, Terminal};
 use reqwest::Client;
+use script::Script;
 use server::NewRelicClient;
-use ui::PALETTES;
+use session::{is_read_only, Session};
+use tracing::{error, warn};
 
 use std::{
-    collections::BTreeMap,
     env, fs,
     io::{self, stdout},
+    panic,
     path::{Path, PathBuf},
+    process,
     sync::OnceLock,
+    time::Duration,
 };
 
-static THEME: usize = 6;
 const ENDPOINT: &str = "https://api.newrelic.com/graphql";
 static ACCOUNT: OnceLock<i64> = OnceLock::new();
 static API_KEY: OnceLock<String> = OnceLock::new();
 
 fn main() -> io::Result<()> {
-    enable_raw_mode()?;
-    stdout().execute(EnterAlternateScreen)?;
-
-    let account = ACCOUNT.get_or_init(|| {
-        std::env::var("NR_ACCOUNT")
-            .expect("ERROR: No NR_ACCOUNT provided!")
-            .parse::<i64>()
-            .expect("ERROR: Provided NR_ACCOUNT is not valid! (Parse failure)")
-    });
-    let api_key = API_KEY
-        .get_or_init(|| std::env::var("NR_API_KEY").expect("ERROR: No NR_API_KEY provided!"));
-
     let home_dir = match env::var("HOME") {
         Ok(val) => val,
         Err(_) => {
@@ -50,11 +58,76 @@ fn main() -> io::Result<()> {
     };
 
     // Construct the path to Application Support directory
-    let mut session_path = PathBuf::from(home_dir);
-    session_path.push("Library/Application Support/xrelic/session.yaml");
-    let yaml = fs::read_to_string(session_path).expect("ERROR: Could not read session file!");
-    let session: Option<BTreeMap<String, String>> =
-        serde_yaml::from_str(&yaml).expect("ERROR: Could not deserialize session file!");
+    let mut app_dir = PathBuf::from(home_dir);
+    app_dir.push("Library/Application Support/xrelic");
+    if env::args().nth(1).as_deref() == Some("config") {
+        process::exit(config::command(env::args().nth(2).as_deref(), &app_dir));
+    }
+    if env::args().any(|arg| arg == "--doctor") {
+        let healthy = doctor::run(&app_dir, ENDPOINT, &ACCOUNT, &API_KEY);
+        process::exit(if healthy { 0 } else { 1 });
+    }
+    let diagnostics = diagnostics::init(&app_dir.join("debug.log"));
+    let config_path = find_config(&app_dir);
+    let config = Config::load(&app_dir).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        process::exit(1);
+    });
+
+    // Played back in place of the keyboard, for demos and reproducing bugs
+    let script = env::args()
+        .skip_while(|arg| arg != "--script")
+        .nth(1)
+        .map(|path| match Script::load(Path::new(&path)) {
+            Ok(script) => script,
+            Err(e) => {
+                eprintln!("Invalid script: {e}");
+                process::exit(1);
+            }
+        });
+
+    // Resolved before entering the alternate screen, as either may prompt
+    let account = ACCOUNT.get_or_init(|| {
+        auth::account(&config).unwrap_or_else(|e| {
+            eprintln!("{e}");
+            process::exit(1);
+        })
+    });
+    let api_key = API_KEY.get_or_init(|| {
+        auth::api_key(&config).unwrap_or_else(|e| {
+            eprintln!("{e}");
+            process::exit(1);
+        })
+    });
+
+    let text = Catalog::load(&app_dir.join("i18n"), &config.language());
+    let session_path = config.session_dir(&app_dir).join("session.yaml");
+    // No session file yet just means nothing was saved
+    let yaml = match fs::read_to_string(&session_path) {
+        Ok(yaml) => yaml,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => {
+            eprintln!("Could not read {}: {}", session_path.display(), e);
+            process::exit(1);
+        }
+    };
+    let session: Option<Session> = session::parse(&yaml).unwrap_or_else(|e| {
+        eprintln!("{} is not a valid session:\n{}", session_path.display(), e);
+        process::exit(1);
+    });
+    let read_only = env::args().any(|arg| arg == "--read-only") || is_read_only(&yaml);
+
+    // Give the terminal back before a panic is printed, rather than leaving it raw
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        error!("{}", info);
+        _ = disable_raw_mode();
+        _ = stdout().execute(LeaveAlternateScreen);
+        hook(info);
+    }));
+
+    enable_raw_mode()?;
+    stdout().execute(EnterAlternateScreen)?;
 
     let mut client = NewRelicClient::builder();
     client
@@ -65,13 +138,31 @@ fn main() -> io::Result<()> {
 
     let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
     terminal.show_cursor()?;
-    let backend = Backend::new(client);
-    let app = App::new(&PALETTES[THEME], backend, session);
+    let backend =
+        Backend::new(client, Duration::from_secs(config.cache_ttl)).with_diagnostics(diagnostics);
+    let mut app = App::new(
+        config.palette(),
+        backend,
+        config,
+        text,
+        session,
+        session_path,
+        read_only,
+    );
+    app.script = script;
+    app.config_watch = config_path.and_then(|path| {
+        ConfigWatch::new(path)
+            .map_err(|e| warn!("config changes won't be picked up: {}", e))
+            .ok()
+    });
 
-    app.run(&mut terminal).unwrap();
+    let result = match app.check_credentials(&mut terminal) {
+        Ok(true) => app.run(&mut terminal),
+        other => other.map(|_| ()),
+    };
 
     disable_raw_mode()?;
     stdout().execute(LeaveAlternateScreen)?;
 
-    Ok(())
+    result
 }
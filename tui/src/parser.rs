@@ -4,21 +4,28 @@ use std::collections::HashMap;
 use nom::{
     branch::alt,
     bytes::complete::{tag, take_until},
+    combinator::{recognize, rest},
+    sequence::pair,
     IResult,
 };
 
 fn parse_timeseries(input: &str) -> IResult<&str, &str> {
-    alt((tag("TIMESERIES"), tag("TABLE")))(input)
+    recognize(pair(alt((tag("TIMESERIES"), tag("TABLE"))), rest))(input)
 }
 
 fn parse_limit(input: &str) -> IResult<&str, &str> {
     let (remainder, _) = tag("LIMIT")(input)?;
-    alt((take_until("TIMESERIES"), take_until("TABLE")))(remainder)
+    alt((take_until("TIMESERIES"), take_until("TABLE"), rest))(remainder)
+}
+
+fn parse_compare(input: &str) -> IResult<&str, &str> {
+    let (remainder, _) = tag("COMPARE WITH")(input)?;
+    take_until("LIMIT")(remainder)
 }
 
 fn parse_until(input: &str) -> IResult<&str, &str> {
     let (remainder, _) = tag("UNTIL")(input)?;
-    take_until("LIMIT")(remainder)
+    alt((take_until("COMPARE WITH"), take_until("LIMIT")))(remainder)
 }
 
 fn parse_since(input: &str) -> IResult<&str, &str> {
@@ -47,19 +54,34 @@ fn parse_from(input: &str) -> IResult<&str, &str> {
 }
 
 pub fn parse_nrql(input: &str) -> Result<HashMap<String, String>> {
-    let (remainder, from) = parse_from(input).map_err(|_| anyhow!("Parsing Error! : FROM"))?;
-    let (remainder, select) =
-        parse_select(remainder).map_err(|_| anyhow!("Parsing Error!: SELECT"))?;
-    let (remainder, r#where) =
-        parse_where(remainder).map_err(|_| anyhow!("Parsing Error! : WHERE"))?;
+    let (remainder, from) =
+        parse_from(input).map_err(|_| anyhow!("expected FROM <event type> followed by SELECT"))?;
+    let (remainder, select) = parse_select(remainder)
+        .map_err(|_| anyhow!("expected SELECT <expression> followed by WHERE"))?;
+    let (remainder, r#where) = parse_where(remainder)
+        .map_err(|_| anyhow!("expected WHERE <condition> followed by FACET or SINCE"))?;
     let (remainder, facet) = parse_facet(remainder).unwrap_or((remainder, ""));
     let (remainder, since) =
-        parse_since(remainder).map_err(|_| anyhow!("Parsing Error! : SINCE"))?;
-    let (remainder, until) =
-        parse_until(remainder).map_err(|_| anyhow!("Parsing Error! : UNTIL"))?;
+        parse_since(remainder).map_err(|_| anyhow!("expected SINCE <time> followed by UNTIL"))?;
+    let (remainder, until) = parse_until(remainder)
+        .map_err(|_| anyhow!("expected UNTIL <time> followed by COMPARE WITH or LIMIT"))?;
+    let (remainder, compare) = parse_compare(remainder).unwrap_or((remainder, ""));
     let (remainder, limit) =
-        parse_limit(remainder).map_err(|_| anyhow!("Parsing Error! : LIMIT"))?;
-    let (_, mode) = parse_timeseries(remainder).map_err(|_| anyhow!("Parsing error! : MODE"))?;
+        parse_limit(remainder).map_err(|_| anyhow!("expected LIMIT <count>"))?;
+    // Without TIMESERIES the query returns single values
+    let (_, mode) = parse_timeseries(remainder).unwrap_or((remainder, ""));
+
+    let clauses = [
+        ("FROM", from),
+        ("SELECT", select),
+        ("WHERE", r#where),
+        ("SINCE", since),
+        ("UNTIL", until),
+        ("LIMIT", limit),
+    ];
+    if let Some((clause, _)) = clauses.iter().find(|(_, value)| value.trim().is_empty()) {
+        return Err(anyhow!("{} is empty", clause));
+    }
 
     let mut outputs = HashMap::new();
 
@@ -69,6 +91,7 @@ pub fn parse_nrql(input: &str) -> Result<HashMap<String, String>> {
     outputs.insert("FACET".to_owned(), facet.trim().to_owned());
     outputs.insert("SINCE".to_owned(), since.trim().to_owned());
     outputs.insert("UNTIL".to_owned(), until.trim().to_owned());
+    outputs.insert("COMPARE".to_owned(), compare.trim().to_owned());
     outputs.insert("LIMIT".to_owned(), limit.trim().to_owned());
     outputs.insert("MODE".to_owned(), mode.trim().to_owned());
 
@@ -1,6 +1,7 @@
-use std::ops::Add;
+use std::collections::BTreeMap;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use chrono::Utc;
 use serde::Deserialize;
 
 use crate::parser::parse_nrql;
@@ -13,7 +14,24 @@ pub enum QueryType {
 
 pub struct NRQLResult {}
 
-#[derive(Default, Debug, Deserialize, Clone)]
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct TimeRange {
+    pub since: String,
+    pub until: String,
+}
+
+impl TimeRange {
+    pub const PRESETS: [&'static str; 4] = ["30 minutes", "1 hour", "6 hours", "24 hours"];
+
+    pub fn last(duration: &str) -> Self {
+        TimeRange {
+            since: format!("{} ago", duration),
+            until: "now".to_owned(),
+        }
+    }
+}
+
+#[derive(Default, Debug, Deserialize, Clone, PartialEq)]
 pub struct NRQLQuery {
     pub from: String,
     pub select: String,
@@ -21,32 +39,379 @@ pub struct NRQLQuery {
     pub facet: String,
     pub since: String,
     pub until: String,
+    pub compare: String,
     pub limit: String,
     pub mode: String,
+    // Runs against this account rather than the client's
+    pub account: Option<i64>,
 }
 
+// Prefixes a query to target another account, e.g. "ACCOUNT 1234 FROM ..."
+pub const ACCOUNT_PREFIX: &str = "ACCOUNT ";
+
 impl NRQLQuery {
+    pub fn set_since(&mut self, since: impl Into<String>) -> &mut Self {
+        self.since = since.into();
+        self
+    }
+
+    pub fn set_until(&mut self, until: impl Into<String>) -> &mut Self {
+        self.until = until.into();
+        self
+    }
+
+    pub fn set_time_range(&mut self, range: &TimeRange) -> &mut Self {
+        self.set_since(&range.since).set_until(&range.until)
+    }
+
+    // Collapses whitespace outside of quotes, so equivalent queries share a key
+    pub fn canonical(mut self) -> Self {
+        for field in [
+            &mut self.from,
+            &mut self.select,
+            &mut self.r#where,
+            &mut self.facet,
+            &mut self.since,
+            &mut self.until,
+            &mut self.compare,
+            &mut self.limit,
+            &mut self.mode,
+        ] {
+            *field = collapse_whitespace(field);
+        }
+        self
+    }
+
+    // Resolves SINCE/UNTIL to absolute epoch seconds where possible
+    pub fn window(&self) -> Option<(i64, i64)> {
+        let now = Utc::now().timestamp();
+        Some((to_epoch(&self.since, now)?, to_epoch(&self.until, now)?))
+    }
+
+    // The attributes listed in FACET, e.g. ["appName", "host"] for FACET appName, host
+    pub fn facet_attributes(&self) -> Vec<&str> {
+        let mut attributes = vec![];
+        let mut depth = 0;
+        let mut quote = None;
+        let mut start = 0;
+        for (i, c) in self.facet.char_indices() {
+            match quote {
+                Some(q) if c == q => quote = None,
+                Some(_) => {}
+                None if c == '\'' || c == '"' || c == '`' => quote = Some(c),
+                None if c == '(' => depth += 1,
+                None if c == ')' => depth -= 1,
+                None if c == ',' && depth == 0 => {
+                    attributes.push(self.facet[start..i].trim());
+                    start = i + 1;
+                }
+                None => {}
+            }
+        }
+        attributes.push(self.facet[start..].trim());
+        attributes.retain(|attribute| !attribute.is_empty());
+        attributes
+    }
+
+    // Includes the account prefix, so the same NRQL against two accounts keys two datasets
     pub fn to_string(&self) -> Result<String> {
+        match self.account {
+            Some(account) => Ok(format!("{}{} {}", ACCOUNT_PREFIX, account, self.nrql())),
+            None => Ok(self.nrql()),
+        }
+    }
+
+    // The NRQL sent to New Relic
+    pub fn nrql(&self) -> String {
         let mut query = String::new();
         query += format!("FROM {} ", self.from).as_str();
-        query += format!("SELECT {} as value ", self.select).as_str();
+        query += format!("SELECT {} ", self.select).as_str();
         query += format!("WHERE {} ", self.r#where).as_str();
         if !String::is_empty(&self.facet) {
             query += format!("FACET {} ", self.facet).as_str();
         }
         query += format!("SINCE {} ", self.since).as_str();
         query += format!("UNTIL {} ", self.until).as_str();
+        if !String::is_empty(&self.compare) {
+            query += format!("COMPARE WITH {} ", self.compare).as_str();
+        }
         query += format!("LIMIT {} ", self.limit).as_str();
-        query += format!("{}", self.mode).as_str();
+        query += self.mode.as_str();
+
+        query.trim_end().to_owned()
+    }
 
-        Ok(query.to_string())
+    pub fn is_timeseries(&self) -> bool {
+        self.mode.starts_with("TIMESERIES")
+    }
+
+    // The query as sent, with session variables filled in
+    pub fn with_variables(&self, variables: &Variables) -> Self {
+        let mut query = self.clone();
+        for field in [
+            &mut query.from,
+            &mut query.select,
+            &mut query.r#where,
+            &mut query.facet,
+            &mut query.since,
+            &mut query.until,
+            &mut query.compare,
+            &mut query.limit,
+            &mut query.mode,
+        ] {
+            *field = substitute(field, variables);
+        }
+        query
+    }
+}
+
+// Session variables by name, used as $name in queries
+pub type Variables = BTreeMap<String, String>;
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+// Replaces each $name that has a value, anything else is left as written
+pub fn substitute(text: &str, variables: &Variables) -> String {
+    let mut output = String::new();
+    let mut rest = text;
+    while let Some(i) = rest.find('$') {
+        output.push_str(&rest[..i]);
+        let after = &rest[i + 1..];
+        let len = after.find(|c| !is_name_char(c)).unwrap_or(after.len());
+        match variables.get(&after[..len]) {
+            Some(value) if len > 0 => output.push_str(value),
+            _ => output.push_str(&rest[i..i + 1 + len]),
+        }
+        rest = &after[len..];
+    }
+    output.push_str(rest);
+    output
+}
+
+// A quoted NRQL string literal, e.g. for an entity name in a WHERE clause
+pub fn nrql_string(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+// Variables as edited, e.g. "app=checkout, env=prod"
+pub fn parse_variables(input: &str) -> Result<Variables> {
+    let mut variables = Variables::new();
+    for pair in input.split(',').filter(|pair| !pair.trim().is_empty()) {
+        let (name, value) = pair
+            .split_once('=')
+            .ok_or_else(|| anyhow!("expected name=value, got {}", pair.trim()))?;
+        let name = name.trim().trim_start_matches('$');
+        if name.is_empty() || !name.chars().all(is_name_char) {
+            return Err(anyhow!("{} is not a valid variable name", name));
+        }
+        variables.insert(name.to_owned(), value.trim().to_owned());
+    }
+    Ok(variables)
+}
+
+pub fn variables_spec(variables: &Variables) -> String {
+    variables
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn collapse_whitespace(value: &str) -> String {
+    let mut collapsed = String::new();
+    let mut quote = None;
+    for c in value.trim().chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            None if c == '\'' || c == '"' || c == '`' => quote = Some(c),
+            None if c.is_whitespace() => {
+                if !collapsed.ends_with(' ') {
+                    collapsed.push(' ');
+                }
+                continue;
+            }
+            _ => {}
+        }
+        collapsed.push(c);
+    }
+    collapsed
+}
+
+// Clauses in the order the parser expects them
+const CLAUSES: [&str; 7] = [
+    "FACET",
+    "SINCE",
+    "UNTIL",
+    "COMPARE WITH",
+    "LIMIT",
+    "TIMESERIES",
+    "TABLE",
+];
+
+// Rearranges NRQL as written in New Relic (SELECT .. FROM .. in any clause order) into the
+// order the parser expects. A missing SINCE/UNTIL falls back to the given range and TIMESERIES
+// is kept only if it was written. Queries using a clause the parser has no slot for are not
+// reordered, since dropping it would change their results.
+pub fn reorder_nrql(nrql: &str, range: &TimeRange) -> Option<String> {
+    const KEYWORDS: [&str; 12] = [
+        "SELECT",
+        "FROM",
+        "WHERE",
+        "FACET",
+        "SINCE",
+        "UNTIL",
+        "COMPARE WITH",
+        "LIMIT",
+        "TIMESERIES",
+        "ORDER BY",
+        "EXTRAPOLATE",
+        "WITH TIMEZONE",
+    ];
+    const UNSUPPORTED: [&str; 3] = ["ORDER BY", "EXTRAPOLATE", "WITH TIMEZONE"];
+    let nrql = nrql.trim();
+    // ASCII uppercasing keeps byte offsets aligned with the original, and blanking out quoted
+    // text byte for byte keeps keywords inside string literals from being matched
+    let mut upper = String::from(" ");
+    let mut quote = None;
+    for c in nrql.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {
+                upper.extend(std::iter::repeat_n('_', c.len_utf8()));
+                continue;
+            }
+            None if c == '\'' || c == '"' || c == '`' => quote = Some(c),
+            None => {}
+        }
+        upper.push(c.to_ascii_uppercase());
+    }
+    upper.push(' ');
+    let starts = KEYWORDS
+        .iter()
+        .map(|keyword| (*keyword, upper.find(&format!(" {} ", keyword))))
+        .collect::<Vec<_>>();
+    if starts
+        .iter()
+        .any(|(keyword, start)| UNSUPPORTED.contains(keyword) && start.is_some())
+    {
+        return None;
+    }
+    let clause = |keyword: &str| {
+        let start = starts.iter().find(|(k, _)| *k == keyword)?.1?;
+        let end = starts
+            .iter()
+            .filter_map(|(_, i)| *i)
+            .filter(|i| *i > start)
+            .min()
+            .unwrap_or(nrql.len());
+        Some(nrql[usize::min(start + keyword.len(), end)..end].trim())
+    };
+
+    let facet = clause("FACET")
+        .map(|facet| format!("FACET {} ", facet))
+        .unwrap_or_default();
+    let compare = clause("COMPARE WITH")
+        .map(|compare| format!("COMPARE WITH {} ", compare))
+        .unwrap_or_default();
+    let mode = clause("TIMESERIES")
+        .map(|bucket| format!("TIMESERIES {}", bucket))
+        .unwrap_or_default();
+    Some(format!(
+        "FROM {} SELECT {} WHERE {} {}SINCE {} UNTIL {} {}LIMIT {} {}",
+        clause("FROM")?,
+        clause("SELECT")?,
+        clause("WHERE").unwrap_or("true"),
+        facet,
+        clause("SINCE").unwrap_or(&range.since),
+        clause("UNTIL").unwrap_or(&range.until),
+        compare,
+        clause("LIMIT").unwrap_or("MAX"),
+        mode
+    ))
+}
+
+// Replaces a clause's value in raw NRQL text, inserting the clause if it is missing
+pub fn set_clause(query: &str, clause: &str, value: &str) -> String {
+    let later = CLAUSES
+        .iter()
+        .skip_while(|c| **c != clause)
+        .skip(1)
+        .copied()
+        .collect::<Vec<_>>();
+    let find_after = |from: usize| {
+        later
+            .iter()
+            .filter_map(|c| query[from..].find(c).map(|i| from + i))
+            .min()
+    };
+
+    let (start, end) = match query.find(clause) {
+        Some(start) => (
+            start,
+            find_after(start + clause.len()).unwrap_or(query.len()),
+        ),
+        None => {
+            let at = find_after(0).unwrap_or(query.len());
+            (at, at)
+        }
+    };
+
+    format!(
+        "{} {} {} {}",
+        query[..start].trim(),
+        clause,
+        value,
+        query[end..].trim()
+    )
+    .trim()
+    .to_owned()
+}
+
+fn to_epoch(time: &str, now: i64) -> Option<i64> {
+    let time = time.trim().to_lowercase();
+    if time == "now" {
+        return Some(now);
+    }
+    if let Ok(millis) = time.parse::<i64>() {
+        return Some(millis / 1000);
+    }
+
+    let mut parts = time.split_whitespace();
+    let amount = parts.next()?.parse::<i64>().ok()?;
+    let unit = match parts.next()?.trim_end_matches('s') {
+        "second" => 1,
+        "minute" => 60,
+        "hour" => 60 * 60,
+        "day" => 60 * 60 * 24,
+        "week" => 60 * 60 * 24 * 7,
+        _ => return None,
+    };
+    match parts.next() {
+        Some("ago") => Some(now - amount * unit),
+        _ => None,
     }
 }
 
 impl NRQL for &str {
     fn to_nrql(self) -> Result<NRQLQuery> {
-        let parts = parse_nrql(self)?;
-        let mut nrql = NRQLQuery::default();
+        let (account, query) = match self.trim_start().strip_prefix(ACCOUNT_PREFIX) {
+            Some(rest) => {
+                let rest = rest.trim_start();
+                let end = rest.find(' ').unwrap_or(rest.len());
+                let account = rest[..end]
+                    .parse::<i64>()
+                    .map_err(|_| anyhow!("expected an account number after ACCOUNT"))?;
+                (Some(account), rest[end..].trim_start())
+            }
+            None => (None, self),
+        };
+        let parts = parse_nrql(query)?;
+        let mut nrql = NRQLQuery {
+            account,
+            ..NRQLQuery::default()
+        };
         parts.iter().for_each(|(key, value)| match key.as_ref() {
             "FROM" => nrql.from = value.to_owned(),
             "SELECT" => nrql.select = value.to_owned(),
@@ -54,14 +419,287 @@ impl NRQL for &str {
             "FACET" => nrql.facet = value.to_owned(),
             "SINCE" => nrql.since = value.to_owned(),
             "UNTIL" => nrql.until = value.to_owned(),
+            "COMPARE" => nrql.compare = value.to_owned(),
             "LIMIT" => nrql.limit = value.to_owned(),
             "MODE" => nrql.mode = value.to_owned(),
             _ => panic!(),
         });
-        Ok(nrql)
+        Ok(nrql.canonical())
     }
 }
 
 pub trait NRQL {
     fn to_nrql(self) -> Result<NRQLQuery>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    // Mirrors how dataset keys are turned back into queries
+    fn reparse(query: &NRQLQuery) -> NRQLQuery {
+        query.to_string().unwrap().trim().to_nrql().unwrap()
+    }
+
+    fn attribute() -> impl Strategy<Value = String> {
+        "[a-z][a-z0-9_]{0,10}(\\.[a-z]{1,6})?"
+    }
+
+    fn select() -> impl Strategy<Value = String> {
+        prop_oneof![
+            Just("count(*)".to_owned()),
+            attribute().prop_map(|a| format!("average({})", a)),
+            attribute().prop_map(|a| format!("uniqueCount({})", a)),
+            (attribute(), 1..100u32).prop_map(|(a, p)| format!("percentile({}, {})", a, p)),
+            (attribute(), 1..100u32).prop_map(|(a, n)| format!("sum({}) / {}", a, n)),
+        ]
+    }
+
+    fn condition() -> impl Strategy<Value = String> {
+        prop_oneof![
+            (attribute(), "[a-z0-9 ]{0,12}").prop_map(|(a, v)| format!("{} = '{}'", a, v)),
+            (attribute(), 0..10_000u32).prop_map(|(a, n)| format!("{} > {}", a, n)),
+            attribute().prop_map(|a| format!("{} IS NOT NULL", a)),
+        ]
+    }
+
+    fn ago() -> impl Strategy<Value = String> {
+        (1..60u32, prop_oneof!["minutes", "hours", "days"])
+            .prop_map(|(n, unit)| format!("{} {} ago", n, unit))
+    }
+
+    prop_compose! {
+        fn nrql()(
+            from in "[A-Z][A-Za-z]{0,15}",
+            select in select(),
+            conditions in prop::collection::vec(condition(), 1..4),
+            facet in prop::option::of(attribute()),
+            since in prop_oneof![
+                ago(),
+                (1_600_000_000_000u64..1_700_000_000_000).prop_map(|ms| ms.to_string()),
+            ],
+            until in prop_oneof![Just("now".to_owned()), ago()],
+            compare in prop::option::of(ago()),
+            limit in prop_oneof![Just("MAX".to_owned()), (1..2000u32).prop_map(|n| n.to_string())],
+            mode in prop_oneof![
+                Just("TIMESERIES".to_owned()),
+                Just("TIMESERIES AUTO".to_owned()),
+                (1..30u32).prop_map(|n| format!("TIMESERIES {} minutes", n)),
+                Just(String::new()),
+            ],
+        ) -> String {
+            let facet = facet.map(|f| format!("FACET {} ", f)).unwrap_or_default();
+            let compare = compare.map(|c| format!("COMPARE WITH {} ", c)).unwrap_or_default();
+            format!(
+                "FROM {} SELECT {} WHERE {} {}SINCE {} UNTIL {} {}LIMIT {} {}",
+                from, select, conditions.join(" AND "), facet, since, until, compare, limit, mode
+            )
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn round_trip_is_stable(text in nrql()) {
+            let query = text.as_str().to_nrql().unwrap();
+            let reparsed = reparse(&query);
+            prop_assert_eq!(&query, &reparsed);
+            prop_assert_eq!(query.to_string().unwrap(), reparsed.to_string().unwrap());
+        }
+
+        #[test]
+        fn extra_whitespace_is_canonical(text in nrql()) {
+            let spaced = text.replace(' ', "   ");
+            let query = text.as_str().to_nrql().unwrap();
+            let spaced = spaced.as_str().to_nrql().unwrap();
+            // Quoted values keep their spacing, so only compare queries without them
+            if !text.contains('\'') {
+                prop_assert_eq!(query.to_string().unwrap(), spaced.to_string().unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn fixtures_round_trip() {
+        let fixtures = [
+            "FROM Transaction SELECT average(duration) WHERE appName = 'checkout service' FACET host SINCE 30 minutes ago UNTIL now LIMIT MAX TIMESERIES",
+            "FROM Transaction SELECT percentile(duration, 95, 99) WHERE appName = 'api' AND httpResponseCode >= 500 SINCE 1 day ago UNTIL now COMPARE WITH 1 week ago LIMIT 100 TIMESERIES 1 hour",
+            "FROM Log SELECT count(*) WHERE message LIKE '%timeout  after%' SINCE 1709280000000 UNTIL 1709283600000 LIMIT MAX TIMESERIES AUTO",
+            "FROM SystemSample SELECT max(cpuPercent) WHERE hostname IS NOT NULL FACET hostname SINCE '2024-03-01 09:30:00+0100' UNTIL '2024-03-01 10:30:00+0100' LIMIT 10 TIMESERIES",
+            "FROM PageView SELECT uniqueCount(session) WHERE countryCode IN ('GB', 'DE') SINCE 6 hours ago UNTIL 1 hour ago LIMIT MAX TABLE",
+        ];
+        for fixture in fixtures {
+            let query = fixture.to_nrql().unwrap();
+            assert_eq!(query, reparse(&query), "{}", fixture);
+        }
+    }
+
+    #[test]
+    fn quoted_whitespace_is_preserved() {
+        let query = "FROM Log  SELECT count(*)  WHERE message = 'a   b' SINCE 1 hour ago UNTIL now LIMIT MAX TIMESERIES"
+            .to_nrql()
+            .unwrap();
+        assert_eq!(query.from, "Log");
+        assert_eq!(query.r#where, "message = 'a   b'");
+    }
+
+    #[test]
+    fn reorders_new_relic_nrql() {
+        let range = TimeRange::last("30 minutes");
+        let reordered = reorder_nrql(
+            "SELECT count(*) FROM TransactionError WHERE appName = 'api' FACET error.class SINCE 1 day ago TIMESERIES 5 minutes",
+            &range,
+        )
+        .unwrap();
+        let query = reordered.as_str().to_nrql().unwrap();
+        assert_eq!(query.from, "TransactionError");
+        assert_eq!(query.select, "count(*)");
+        assert_eq!(query.r#where, "appName = 'api'");
+        assert_eq!(query.facet, "error.class");
+        assert_eq!(query.since, "1 day ago");
+        assert_eq!(query.until, "now");
+        assert_eq!(query.limit, "MAX");
+        assert_eq!(query.mode, "TIMESERIES 5 minutes");
+
+        let query = reorder_nrql("SELECT count(*) FROM Transaction", &range)
+            .unwrap()
+            .as_str()
+            .to_nrql()
+            .unwrap();
+        assert_eq!(query.r#where, "true");
+        assert_eq!(query.since, "30 minutes ago");
+        assert_eq!(query.mode, "");
+        assert!(!query.is_timeseries());
+    }
+
+    #[test]
+    fn reorder_ignores_keywords_in_string_literals() {
+        let range = TimeRange::last("30 minutes");
+        let query = reorder_nrql(
+            "SELECT count(*) FROM Log WHERE message = 'failed SINCE restart' OR `order by` = 'x' FACET host",
+            &range,
+        )
+        .unwrap()
+        .as_str()
+        .to_nrql()
+        .unwrap();
+        assert_eq!(
+            query.r#where,
+            "message = 'failed SINCE restart' OR `order by` = 'x'"
+        );
+        assert_eq!(query.facet, "host");
+        assert_eq!(query.since, "30 minutes ago");
+    }
+
+    #[test]
+    fn reorder_refuses_clauses_it_would_drop() {
+        let range = TimeRange::last("30 minutes");
+        for nrql in [
+            "SELECT count(*) FROM Transaction FACET name ORDER BY count(*) LIMIT 5",
+            "SELECT count(*) FROM Transaction SINCE 1 day ago EXTRAPOLATE",
+            "SELECT count(*) FROM Transaction SINCE today WITH TIMEZONE 'Europe/Berlin'",
+        ] {
+            assert_eq!(reorder_nrql(nrql, &range), None, "{}", nrql);
+        }
+    }
+
+    #[test]
+    fn set_clause_replaces_and_inserts() {
+        let query = "FROM Transaction SELECT count(*) WHERE true SINCE 1 hour ago UNTIL now LIMIT MAX TIMESERIES";
+        let query = set_clause(query, "UNTIL", "'2024-03-01 10:00:00+0000'");
+        let query = set_clause(&query, "COMPARE WITH", "1 day ago");
+        let query = query.as_str().to_nrql().unwrap();
+        assert_eq!(query.since, "1 hour ago");
+        assert_eq!(query.until, "'2024-03-01 10:00:00+0000'");
+        assert_eq!(query.compare, "1 day ago");
+        assert_eq!(query.limit, "MAX");
+    }
+
+    #[test]
+    fn account_prefix_round_trips() {
+        let query = "ACCOUNT 1234 FROM Transaction SELECT count(*) WHERE true SINCE 1 hour ago UNTIL now LIMIT MAX TIMESERIES"
+            .to_nrql()
+            .unwrap();
+        assert_eq!(query.account, Some(1234));
+        assert!(query.nrql().starts_with("FROM Transaction"));
+        assert_eq!(reparse(&query), query);
+        assert!("ACCOUNT abc FROM Transaction".to_nrql().is_err());
+    }
+
+    #[test]
+    fn select_is_sent_verbatim() {
+        let query = "FROM Transaction SELECT average(duration), percentile(duration, 95) as 'p95' WHERE true SINCE 1 hour ago UNTIL now LIMIT MAX TIMESERIES"
+            .to_nrql()
+            .unwrap();
+        assert!(query
+            .nrql()
+            .contains("SELECT average(duration), percentile(duration, 95) as 'p95' WHERE"));
+        assert!(!query.nrql().contains("as value"));
+        assert_eq!(reparse(&query), query);
+    }
+
+    #[test]
+    fn facet_attributes_split_on_top_level_commas() {
+        let query = "FROM Transaction SELECT count(*) WHERE true FACET appName, buckets(duration, 1, 10), `host, name` SINCE 1 hour ago UNTIL now LIMIT MAX TIMESERIES"
+            .to_nrql()
+            .unwrap();
+        assert_eq!(
+            query.facet_attributes(),
+            ["appName", "buckets(duration, 1, 10)", "`host, name`"]
+        );
+        assert!(NRQLQuery::default().facet_attributes().is_empty());
+    }
+
+    #[test]
+    fn timeseries_is_optional() {
+        let query =
+            "FROM Transaction SELECT count(*) WHERE true SINCE 1 hour ago UNTIL now LIMIT MAX"
+                .to_nrql()
+                .unwrap();
+        assert_eq!(query.limit, "MAX");
+        assert!(!query.is_timeseries());
+        assert_eq!(
+            query.nrql(),
+            "FROM Transaction SELECT count(*) WHERE true SINCE 1 hour ago UNTIL now LIMIT MAX"
+        );
+    }
+
+    #[test]
+    fn errors_name_the_clause_at_fault() {
+        let error = |query: &str| query.to_nrql().unwrap_err().to_string();
+        assert!(error("SELECT count(*) FROM Transaction").contains("FROM"));
+        assert!(error(
+            "FROM Transaction SELECT count(*) WHERE true SINCE 1 hour ago LIMIT MAX TIMESERIES"
+        )
+        .contains("SINCE"));
+        assert_eq!(
+            error("FROM Transaction SELECT  WHERE true SINCE 1 hour ago UNTIL now LIMIT MAX TIMESERIES"),
+            "SELECT is empty"
+        );
+    }
+
+    #[test]
+    fn variables_are_substituted_where_they_have_values() {
+        let query = "FROM Transaction SELECT count(*) WHERE appName = '$app' AND env = '$env_name' AND price = '$5' SINCE 1 hour ago UNTIL now LIMIT MAX TIMESERIES"
+            .to_nrql()
+            .unwrap();
+        let variables = parse_variables("app=checkout, $env_name = prod ,").unwrap();
+        assert_eq!(variables_spec(&variables), "app=checkout, env_name=prod");
+        assert_eq!(
+            query.with_variables(&variables).r#where,
+            "appName = 'checkout' AND env = 'prod' AND price = '$5'"
+        );
+        assert_eq!(substitute("$ and $apps", &variables), "$ and $apps");
+
+        assert!(parse_variables("app").is_err());
+        assert!(parse_variables("my app=checkout").is_err());
+        assert!(parse_variables(" ").unwrap().is_empty());
+    }
+
+    #[test]
+    fn strings_are_quoted_and_escaped() {
+        assert_eq!(nrql_string("checkout"), "'checkout'");
+        assert_eq!(nrql_string("O'Brien's"), "'O\\'Brien\\'s'");
+        assert_eq!(nrql_string("C:\\apps"), "'C:\\\\apps'");
+    }
+}
@@ -0,0 +1,175 @@
+use anyhow::{anyhow, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::{
+    collections::VecDeque,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+// Key presses read from a file and played back in place of the terminal, one step per line:
+//
+//   # comments and blank lines are skipped
+//   wait 500            pause for 500ms before the next key
+//   type FROM Log ...   each character of the rest of the line
+//   key ctrl+t          a single key, optionally with ctrl+, shift+ or alt+
+pub struct Script {
+    steps: VecDeque<(Duration, KeyEvent)>,
+    due: Instant,
+}
+
+impl Script {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Could not read {}: {e}", path.display()))?;
+        Self::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut steps = VecDeque::new();
+        let mut delay = Duration::ZERO;
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim_start();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (command, arg) = line.split_once(' ').unwrap_or((line, ""));
+            match command {
+                "wait" => {
+                    let ms = arg
+                        .trim()
+                        .parse::<u64>()
+                        .map_err(|_| anyhow!("line {}: expected milliseconds", number + 1))?;
+                    delay += Duration::from_millis(ms);
+                }
+                "type" => {
+                    for c in arg.chars() {
+                        steps.push_back((delay, KeyEvent::from(KeyCode::Char(c))));
+                        delay = Duration::ZERO;
+                    }
+                }
+                "key" => {
+                    let key = parse_key(arg.trim())
+                        .ok_or_else(|| anyhow!("line {}: unknown key {arg}", number + 1))?;
+                    steps.push_back((delay, key));
+                    delay = Duration::ZERO;
+                }
+                _ => return Err(anyhow!("line {}: unknown step {command}", number + 1)),
+            }
+        }
+        Ok(Self {
+            steps,
+            due: Instant::now(),
+        })
+    }
+
+    // The next key once its delay has passed since the previous one
+    pub fn next_key(&mut self) -> Option<KeyEvent> {
+        let (delay, _) = self.steps.front()?;
+        if self.due.elapsed() < *delay {
+            return None;
+        }
+        self.due = Instant::now();
+        self.steps.pop_front().map(|(_, key)| key)
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
+
+fn parse_key(spec: &str) -> Option<KeyEvent> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut name = spec;
+    while let Some((modifier, rest)) = name.split_once('+').filter(|(_, rest)| !rest.is_empty()) {
+        modifiers |= match modifier.to_lowercase().as_str() {
+            "ctrl" => KeyModifiers::CONTROL,
+            "shift" => KeyModifiers::SHIFT,
+            "alt" => KeyModifiers::ALT,
+            _ => return None,
+        };
+        name = rest;
+    }
+    let code = match name.to_lowercase().as_str() {
+        "enter" => KeyCode::Enter,
+        "esc" => KeyCode::Esc,
+        "tab" => KeyCode::Tab,
+        "backtab" => KeyCode::BackTab,
+        "backspace" => KeyCode::Backspace,
+        "space" => KeyCode::Char(' '),
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => KeyCode::Char(c),
+                _ => return None,
+            }
+        }
+    };
+    Some(KeyEvent::new(code, modifiers))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        app::Focus,
+        testing::{app, series},
+    };
+
+    #[test]
+    fn parses_steps() {
+        let mut script = Script::parse(
+            "# open the query box\nkey e\ntype a b\n\nkey ctrl+t\nkey shift+Left\nkey Enter\n",
+        )
+        .unwrap();
+        let keys: Vec<_> = std::iter::from_fn(|| script.next_key()).collect();
+        assert_eq!(
+            keys,
+            vec![
+                KeyEvent::from(KeyCode::Char('e')),
+                KeyEvent::from(KeyCode::Char('a')),
+                KeyEvent::from(KeyCode::Char(' ')),
+                KeyEvent::from(KeyCode::Char('b')),
+                KeyEvent::new(KeyCode::Char('t'), KeyModifiers::CONTROL),
+                KeyEvent::new(KeyCode::Left, KeyModifiers::SHIFT),
+                KeyEvent::from(KeyCode::Enter),
+            ]
+        );
+        assert!(script.is_done());
+    }
+
+    #[test]
+    fn holds_keys_until_their_delay() {
+        let mut script = Script::parse("wait 60000\nkey q\n").unwrap();
+        assert_eq!(script.next_key(), None);
+        assert!(!script.is_done());
+    }
+
+    #[test]
+    fn rejects_unknown_steps() {
+        assert!(Script::parse("press q").is_err());
+        assert!(Script::parse("key hyper+q").is_err());
+        assert!(Script::parse("wait soon").is_err());
+    }
+
+    #[test]
+    fn plays_back_against_the_app() {
+        let mut app = app(Ok(series(None, &[(0.0, 1.0)])));
+        let mut script = Script::parse(
+            "key e\ntype FROM Transaction SELECT count(*) WHERE appName = 'api' SINCE 1 hour ago UNTIL now LIMIT MAX TIMESERIES\nkey Enter\nkey N\nkey Backspace\ntype errors\nkey Enter\n",
+        )
+        .unwrap();
+        while let Some(key) = script.next_key() {
+            app.handle_key(key);
+        }
+        assert!(app.focus == Focus::Default);
+        assert_eq!(app.datasets.tabs(), ["main", "errors"]);
+        assert_eq!(app.datasets.current_tab(), 1);
+        assert!(app.datasets.is_empty());
+    }
+}
@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::{axis::YAxis, dataset::ChartStyle, threshold::Threshold};
+
+pub type Session = BTreeMap<String, SessionQuery>;
+
+// A session file starting with this comment opens read-only, e.g. curated team sessions
+pub const READ_ONLY_MARKER: &str = "# read-only";
+
+pub fn is_read_only(yaml: &str) -> bool {
+    yaml.lines()
+        .next()
+        .is_some_and(|line| line.trim() == READ_ONLY_MARKER)
+}
+
+// Sessions used to map each alias straight to its query, and still load
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredQuery {
+    Query(String),
+    Full(SessionQuery),
+}
+
+pub fn parse(yaml: &str) -> serde_yaml::Result<Option<Session>> {
+    let stored: Option<BTreeMap<String, StoredQuery>> = serde_yaml::from_str(yaml)?;
+    Ok(stored.map(|stored| {
+        stored
+            .into_iter()
+            .map(|(alias, stored)| {
+                let query = match stored {
+                    StoredQuery::Query(query) => SessionQuery {
+                        query,
+                        ..Default::default()
+                    },
+                    StoredQuery::Full(query) => query,
+                };
+                (alias, query)
+            })
+            .collect()
+    }))
+}
+
+// Queries used to be saved with " as value" appended to SELECT, which is now sent as written
+pub fn upgrade_query(query: &str) -> String {
+    query.replacen(" as value WHERE ", " WHERE ", 1)
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct SessionQuery {
+    pub query: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub note: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub links: Vec<String>,
+    #[serde(default)]
+    pub position: usize,
+    #[serde(default)]
+    pub pinned: bool,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub tab: String,
+    #[serde(default, skip_serializing_if = "ChartStyle::is_line")]
+    pub style: ChartStyle,
+    #[serde(default, skip_serializing_if = "YAxis::is_default")]
+    pub axis: YAxis,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub thresholds: Vec<Threshold>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_sessions_have_no_queries() {
+        assert!(parse("").unwrap().is_none());
+    }
+
+    #[test]
+    fn sessions_of_plain_queries_still_load() {
+        let session = parse(
+            "errors: FROM TransactionError SELECT count(*) as value WHERE appName = 'api' SINCE 30 minutes ago UNTIL now LIMIT MAX TIMESERIES\n",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            upgrade_query(&session["errors"].query),
+            "FROM TransactionError SELECT count(*) WHERE appName = 'api' SINCE 30 minutes ago UNTIL now LIMIT MAX TIMESERIES"
+        );
+        assert!(session["errors"].note.is_empty());
+
+        let session =
+            parse("errors:\n  query: FROM TransactionError SELECT count(*)\n  pinned: true\n")
+                .unwrap()
+                .unwrap();
+        assert!(session["errors"].pinned);
+        assert!(parse("").unwrap().is_none());
+    }
+}
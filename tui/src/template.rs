@@ -0,0 +1,132 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+// A query kept in the config's library, with {{name}} placeholders filled in when it is
+// inserted, e.g. "... WHERE appName = '{{app}}' SINCE {{range}} ..."
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QueryTemplate {
+    pub name: String,
+    pub query: String,
+}
+
+// Start and end of each placeholder, braces included, and the name inside
+fn placeholders(query: &str) -> Vec<(usize, usize, &str)> {
+    let mut found = vec![];
+    let mut from = 0;
+    while let Some(start) = query[from..].find("{{").map(|i| from + i) {
+        let Some(end) = query[start + 2..].find("}}").map(|i| start + 2 + i) else {
+            break;
+        };
+        found.push((start, end + 2, query[start + 2..end].trim()));
+        from = end + 2;
+    }
+    found
+}
+
+impl QueryTemplate {
+    // Names in the order they first appear, each once
+    pub fn variables(&self) -> Vec<String> {
+        let mut variables: Vec<String> = vec![];
+        for (_, _, name) in placeholders(&self.query) {
+            if !name.is_empty() && !variables.iter().any(|variable| variable == name) {
+                variables.push(name.to_owned());
+            }
+        }
+        variables
+    }
+
+    // Placeholders without a value are left as they are
+    pub fn expand(&self, values: &BTreeMap<String, String>) -> String {
+        let mut query = String::new();
+        let mut from = 0;
+        for (start, end, name) in placeholders(&self.query) {
+            query.push_str(&self.query[from..start]);
+            match values.get(name) {
+                Some(value) => query.push_str(value),
+                None => query.push_str(&self.query[start..end]),
+            }
+            from = end;
+        }
+        query.push_str(&self.query[from..]);
+        query
+    }
+}
+
+// Values typed for each variable, most recent first, kept between runs
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RecentValues(BTreeMap<String, Vec<String>>);
+
+impl RecentValues {
+    const KEEP: usize = 10;
+
+    pub fn load(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|yaml| serde_yaml::from_str(&yaml).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_yaml::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn get(&self, variable: &str) -> &[String] {
+        self.0.get(variable).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    pub fn remember(&mut self, variable: &str, value: &str) {
+        let values = self.0.entry(variable.to_owned()).or_default();
+        values.retain(|recent| recent != value);
+        values.insert(0, value.to_owned());
+        values.truncate(Self::KEEP);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template(query: &str) -> QueryTemplate {
+        QueryTemplate {
+            name: "latency".to_owned(),
+            query: query.to_owned(),
+        }
+    }
+
+    #[test]
+    fn finds_and_expands_variables() {
+        let latency = template(
+            "FROM Transaction SELECT average(duration) WHERE (appName = '{{app}}' OR service = '{{app}}') AND host = '{{ host }}' SINCE {{range}} UNTIL now LIMIT MAX TIMESERIES",
+        );
+        assert_eq!(latency.variables(), vec!["app", "host", "range"]);
+
+        let values = BTreeMap::from([
+            ("app".to_owned(), "api".to_owned()),
+            ("range".to_owned(), "1 hour ago".to_owned()),
+        ]);
+        assert_eq!(
+            latency.expand(&values),
+            "FROM Transaction SELECT average(duration) WHERE (appName = 'api' OR service = 'api') AND host = '{{ host }}' SINCE 1 hour ago UNTIL now LIMIT MAX TIMESERIES"
+        );
+        assert!(template("no {{ closing").variables().is_empty());
+    }
+
+    #[test]
+    fn remembers_recent_values_once_each() {
+        let mut recent = RecentValues::default();
+        recent.remember("app", "api");
+        recent.remember("app", "web");
+        recent.remember("app", "api");
+        assert_eq!(recent.get("app"), ["api", "web"]);
+        for i in 0..20 {
+            recent.remember("env", &i.to_string());
+        }
+        assert_eq!(recent.get("env").len(), RecentValues::KEEP);
+        assert_eq!(recent.get("env")[0], "19");
+        assert!(recent.get("region").is_empty());
+    }
+}
@@ -0,0 +1,134 @@
+// Test harness: an App whose queries are answered by canned NerdGraph results, and helpers to
+// drive it through actions and render it headlessly
+use anyhow::anyhow;
+use ratatui::{backend::TestBackend, style::palette::tailwind, Terminal};
+use serde_json::json;
+use server::{timeseries::TimeseriesResult, NewRelicClient};
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    sync::{mpsc::Receiver, Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{
+    action::Action,
+    app::App,
+    backend::{Backend, BoxFuture, NerdGraph, UIEvent},
+    config::Config,
+    i18n::Catalog,
+};
+
+static ACCOUNT: i64 = 1;
+
+pub struct MockNerdGraph {
+    pub results: Result<Vec<TimeseriesResult>, String>,
+    // NRQL of every request, as sent
+    pub sent: Arc<Mutex<Vec<String>>>,
+}
+
+impl NerdGraph for MockNerdGraph {
+    fn timeseries(
+        &self,
+        _account: Option<i64>,
+        nrql: String,
+    ) -> BoxFuture<'_, anyhow::Result<Vec<TimeseriesResult>>> {
+        self.sent.lock().unwrap().push(nrql);
+        let results = self.results.clone().map_err(|e| anyhow!(e));
+        Box::pin(async move { results })
+    }
+}
+
+// Never answers, so requests stay in flight until their task is stopped
+pub struct StalledNerdGraph;
+
+impl NerdGraph for StalledNerdGraph {
+    fn timeseries(
+        &self,
+        _account: Option<i64>,
+        _nrql: String,
+    ) -> BoxFuture<'_, anyhow::Result<Vec<TimeseriesResult>>> {
+        Box::pin(std::future::pending())
+    }
+}
+
+// One facet's points, as (begin time, value) one minute apart
+pub fn series(facet: Option<&str>, points: &[(f64, f64)]) -> Vec<TimeseriesResult> {
+    points
+        .iter()
+        .map(|(begin, value)| TimeseriesResult {
+            begin_time_seconds: *begin,
+            end_time_seconds: begin + 60.0,
+            facet: facet
+                .map(|facet| vec![facet.to_owned()])
+                .unwrap_or_default(),
+            comparison: None,
+            columns: BTreeMap::from([(String::from("value"), json!(value))]),
+        })
+        .collect()
+}
+
+pub fn backend(nerdgraph: impl NerdGraph + 'static) -> Backend {
+    let mut client = NewRelicClient::builder();
+    client.account(&ACCOUNT);
+    Backend::new(client, Duration::ZERO).with_nerdgraph(Arc::new(nerdgraph))
+}
+
+pub fn app(results: Result<Vec<TimeseriesResult>, String>) -> App {
+    let nerdgraph = MockNerdGraph {
+        results,
+        sent: Arc::default(),
+    };
+    let mut app = App::new(
+        &tailwind::BLUE,
+        backend(nerdgraph),
+        Config::default(),
+        Catalog::default(),
+        None,
+        PathBuf::new(),
+        false,
+    );
+    // Refresh only when asked, so a timer tick can't slip an extra payload into a test
+    app.backend.send(UIEvent::SetRefreshInterval(u64::MAX));
+    app
+}
+
+// Types a query into the query box as a user would, then waits for its first payload
+pub fn add_query(app: &mut App, nrql: &str) {
+    app.handle_action(Action::EditQuery);
+    for c in nrql.chars() {
+        app.handle_action(Action::InsertChar(c));
+    }
+    app.handle_action(Action::Submit);
+    let payload = recv(&app.backend.data_rx);
+    app.handle_payload(payload);
+    app.handle_action(Action::SelectNext);
+}
+
+// The next message on one of the backend's channels
+pub fn recv<T>(rx: &Receiver<T>) -> T {
+    rx.recv_timeout(Duration::from_secs(5))
+        .expect("ERROR: No reply from the mock backend!")
+}
+
+// Aliases of the queries sent but not yet answered
+pub fn pending_aliases(app: &App) -> Vec<&str> {
+    app.pending
+        .values()
+        .map(|(alias, _)| alias.as_str())
+        .collect()
+}
+
+// The rendered screen, one string per row
+pub fn render(app: &mut App, width: u16, height: u16) -> Vec<String> {
+    let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+    terminal.draw(|frame| app.ui(frame)).unwrap();
+    let buffer = terminal.backend().buffer();
+    (0..height)
+        .map(|y| (0..width).map(|x| buffer.get(x, y).symbol()).collect())
+        .collect()
+}
+
+pub fn contains(screen: &[String], text: &str) -> bool {
+    screen.iter().any(|row| row.contains(text))
+}
@@ -0,0 +1,127 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
+
+use crate::{axis::Unit, format::NumberFormat};
+
+// A horizontal line at a value worth watching, typed as "value:label:colour", e.g.
+// "500:slow:yellow". The label and colour are optional, colours are ratatui's names or #rrggbb.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Threshold {
+    pub value: f64,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub label: String,
+    #[serde(default = "default_colour")]
+    pub colour: String,
+}
+
+fn default_colour() -> String {
+    "red".to_owned()
+}
+
+impl Threshold {
+    fn parse(spec: &str) -> Result<Self> {
+        let mut parts = spec.splitn(3, ':').map(str::trim);
+        let value = parts.next().unwrap_or_default();
+        let value = value
+            .parse::<f64>()
+            .map_err(|_| anyhow!("expected a number, not {}", value))?;
+        let label = parts.next().unwrap_or_default().to_owned();
+        let colour = match parts.next() {
+            Some(colour) if !colour.is_empty() => colour.to_lowercase(),
+            _ => default_colour(),
+        };
+        if Color::from_str(&colour).is_err() {
+            return Err(anyhow!("unknown colour {}", colour));
+        }
+        Ok(Threshold {
+            value,
+            label,
+            colour,
+        })
+    }
+
+    // Several thresholds separated by commas, an empty spec clears them
+    pub fn parse_all(spec: &str) -> Result<Vec<Self>> {
+        let mut thresholds = spec
+            .split(',')
+            .filter(|spec| !spec.trim().is_empty())
+            .map(Threshold::parse)
+            .collect::<Result<Vec<_>>>()?;
+        thresholds.sort_by(|a, b| a.value.total_cmp(&b.value));
+        Ok(thresholds)
+    }
+
+    // The thresholds as they would be typed, to edit them again
+    pub fn spec_all(thresholds: &[Self]) -> String {
+        thresholds
+            .iter()
+            .map(|threshold| {
+                let mut spec = threshold.value.to_string();
+                if !threshold.label.is_empty() || threshold.colour != default_colour() {
+                    spec.push_str(&format!(":{}", threshold.label));
+                }
+                if threshold.colour != default_colour() {
+                    spec.push_str(&format!(":{}", threshold.colour));
+                }
+                spec
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    pub fn colour(&self) -> Color {
+        Color::from_str(&self.colour).unwrap_or(Color::Red)
+    }
+
+    // Shown in the chart legend, the value itself when there is no label
+    pub fn name(&self, unit: Unit, numbers: &NumberFormat) -> String {
+        match self.label.is_empty() {
+            true => unit.format(self.value, numbers),
+            false => format!("{} ({})", self.label, unit.format(self.value, numbers)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_thresholds() {
+        let thresholds = Threshold::parse_all("1000:critical, 500 : slow : Yellow").unwrap();
+        assert_eq!(
+            thresholds,
+            vec![
+                Threshold {
+                    value: 500.0,
+                    label: "slow".to_owned(),
+                    colour: "yellow".to_owned(),
+                },
+                Threshold {
+                    value: 1000.0,
+                    label: "critical".to_owned(),
+                    colour: "red".to_owned(),
+                },
+            ]
+        );
+        let spec = Threshold::spec_all(&thresholds);
+        assert_eq!(spec, "500:slow:yellow, 1000:critical");
+        assert_eq!(Threshold::parse_all(&spec).unwrap(), thresholds);
+        assert!(Threshold::parse_all("").unwrap().is_empty());
+        assert!(Threshold::parse_all("lots").is_err());
+        assert!(Threshold::parse_all("5:high:sparkly").is_err());
+    }
+
+    #[test]
+    fn names_fall_back_to_the_value() {
+        let numbers = NumberFormat::default();
+        let threshold = Threshold::parse_all("1500").unwrap().remove(0);
+        assert_eq!(threshold.name(Unit::Milliseconds, &numbers), "1.50 s");
+        let threshold = Threshold::parse_all("90:busy").unwrap().remove(0);
+        assert_eq!(threshold.name(Unit::Percent, &numbers), "busy (90%)");
+        assert_eq!(threshold.colour(), Color::Red);
+    }
+}
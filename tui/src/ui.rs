@@ -1,20 +1,59 @@
-use chrono::{DateTime, Utc};
+use chrono::Utc;
+use server::{preflight::PreflightFailure, trace::Span as TraceSpan};
+use std::{cmp::Ordering, collections::BTreeMap, time::Duration};
 
 use ratatui::{
     prelude::*,
     widgets::{
-        Axis, Block, BorderType, Borders, Chart, Clear, Dataset, GraphType, LegendPosition, List,
-        Padding, Paragraph,
+        Axis, Bar, BarChart, BarGroup, Block, BorderType, Borders, Chart, Clear, Dataset,
+        GraphType, LegendPosition, List, ListItem, ListState, Padding, Paragraph, Tabs, Wrap,
     },
 };
 use style::palette::tailwind;
 use tui_big_text::{BigText, PixelSize};
 
 use crate::{
-    app::{Focus, InputMode, QUERY, RENAME, SESSION_LOAD},
+    app::{
+        parse_key, EntityKind, Focus, InputMode, AXIS, CONSOLE, EXPORT, LINK, MIN_HEIGHT,
+        MIN_WIDTH, NOTE, PALETTE, QUERY, RENAME, SESSION_LOAD, SESSION_SAVE, TAB_NAME,
+        TEMPLATE_VARIABLE, THRESHOLDS, TIME_RANGE, VARIABLES,
+    },
+    axis::Unit,
+    dataset::{total, Chart as ChartData, ChartStyle, Dataset as AppDataset, Stats},
+    format::Timezone,
+    i18n::Msg,
+    query::TimeRange,
     App,
 };
 
+// Points that stray from the rolling mean, and the panel listing them
+const ANOMALY: Color = Color::Magenta;
+
+// How long a threshold crossing stays in the status bar
+const FLASH: Duration = Duration::from_secs(5);
+
+// Beside queries in the list that have a note
+const NOTE_MARKER: &str = "✎";
+
+// Beside queries with a request out, moving on a frame every quarter second
+const SPINNER: [&str; 8] = ["⣾", "⣽", "⣻", "⢿", "⡿", "⣟", "⣯", "⣷"];
+
+// A spinner and the seconds the query's request has been out, e.g. "⣻ 2.5s"
+fn fetching(app: &App, query: &str) -> Option<String> {
+    let elapsed = app.backend.diagnostics.in_flight(query)?;
+    let frame = SPINNER[(elapsed.as_millis() / 250) as usize % SPINNER.len()];
+    Some(format!(
+        "{} {}s",
+        frame,
+        app.numbers.format(elapsed.as_secs_f64(), 1)
+    ))
+}
+
+// Names of PALETTES, as used by theme in the config
+pub const THEMES: [&str; 9] = [
+    "blue", "emerald", "indigo", "red", "amber", "rose", "lime", "fuchsia", "sky",
+];
+
 pub const PALETTES: [tailwind::Palette; 9] = [
     tailwind::BLUE,
     tailwind::EMERALD,
@@ -27,15 +66,14 @@ pub const PALETTES: [tailwind::Palette; 9] = [
     tailwind::SKY,
 ];
 
-pub fn render_loading(app: &mut App, frame: &mut Frame, area: Rect) {}
+pub fn render_loading(_app: &mut App, _frame: &mut Frame, _area: Rect) {}
 
 pub fn render_load_session(app: &mut App, frame: &mut Frame, area: Rect) {
     let area = centered_rect(60, 20, area);
     let vertical = Layout::vertical([Constraint::Length(3), Constraint::Length(3)]);
     let [prompt_area, input_area] = vertical.areas(area);
 
-    let prompt =
-        Text::from("A previous session was found. Would you like to reload its queries? y/n");
+    let prompt = Text::from(app.text.get(Msg::ReloadSession));
     let input = Paragraph::new(app.input_buffer(SESSION_LOAD))
         .style(match app.input_mode {
             InputMode::Normal => Style::default(),
@@ -52,102 +90,168 @@ pub fn render_load_session(app: &mut App, frame: &mut Frame, area: Rect) {
     frame.render_widget(input, input_area);
 }
 
+pub fn render_save_session(app: &mut App, frame: &mut Frame, area: Rect) {
+    let area = centered_rect(60, 20, area);
+    let vertical = Layout::vertical([Constraint::Length(3), Constraint::Length(3)]);
+    let [prompt_area, input_area] = vertical.areas(area);
+
+    let prompt = Text::from(app.text.get(Msg::SaveSession));
+    let input = Paragraph::new(app.input_buffer(SESSION_SAVE))
+        .style(match app.input_mode {
+            InputMode::Normal => Style::default(),
+            InputMode::Input => Style::default().fg(app.theme.focus_fg),
+        })
+        .block(
+            Block::default()
+                .padding(Padding::zero())
+                .borders(Borders::BOTTOM)
+                .border_type(BorderType::Rounded),
+        );
+    frame.render_widget(Clear, area);
+    frame.render_widget(prompt, prompt_area);
+    frame.render_widget(input, input_area);
+}
+
 pub fn render_dashboard(app: &mut App, frame: &mut Frame, area: Rect) {
-    let n_graphs = &app.datasets.len();
-    let areas = match *n_graphs {
-        0 => vec![],
-        1 => vec![area],
-        2 => {
-            let layout = Layout::vertical([Constraint::Percentage(50), Constraint::Percentage(50)]);
-            let [first, second] = layout.areas(area);
-            vec![first, second]
-        }
-        3 => {
-            let vertical =
-                Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]);
-            let [top, bottom] = vertical.areas(area);
-            let horizontal =
-                Layout::vertical([Constraint::Percentage(50), Constraint::Percentage(50)]);
-            let [first, second] = horizontal.areas(bottom);
-            vec![top, first, second]
-        }
-        _ => panic!(),
-    };
+    if app.expanded {
+        render_graph(app, frame, area);
+        return;
+    }
 
+    let areas = grid(app.datasets.len(), area);
     (0..areas.len()).for_each(|i| {
         render_ith_graph(app, frame, areas[i], i);
     });
 }
 
+// Lays out n cells in a near-square grid, with any shortfall in the last row
+fn grid(n: usize, area: Rect) -> Vec<Rect> {
+    if n == 0 {
+        return vec![];
+    }
+    let cols = (n as f64).sqrt().ceil() as usize;
+    let rows = n.div_ceil(cols);
+
+    let row_areas = Layout::vertical(vec![Constraint::Ratio(1, rows as u32); rows]).split(area);
+    row_areas
+        .iter()
+        .enumerate()
+        .flat_map(|(row, row_area)| {
+            let cells = usize::min(cols, n - row * cols);
+            Layout::horizontal(vec![Constraint::Ratio(1, cells as u32); cells])
+                .split(*row_area)
+                .to_vec()
+        })
+        .collect()
+}
+
 pub fn render_ith_graph(app: &mut App, frame: &mut Frame, area: Rect, i: usize) {
-    let datasets = app.datasets.iter().nth(i).map(|(_, data)| {
-        data.facets
-            .iter()
-            .map(|(facet, points)| {
-                Dataset::default()
-                    .name(facet.to_owned())
-                    .data(&points[..])
-                    .marker(Marker::Braille)
-                    .graph_type(GraphType::Line)
-                    .style(match facet.as_str() {
-                        ".NET" => Style::default().fg(app.theme.net_fg),
-                        "Elasticsearch" => Style::default().fg(app.theme.elastic_fg),
-                        "Web external" => Style::default().fg(app.theme.webex_fg),
-                        "value" => Style::default().fg(app.theme.value_fg),
-                        _ => Style::default(),
-                    })
-            })
-            .collect::<Vec<_>>()
-    });
+    if let Some(key) = app.datasets.keys().nth(i).cloned() {
+        if render_placeholder(app, frame, area, &key, app.list_state.selected() == Some(i)) {
+            return;
+        }
+    }
+    if app.config.screen_reader {
+        if let Some((query, data)) = app.datasets.iter().nth(i) {
+            let title = data.query_alias.as_ref().unwrap_or(query).to_owned();
+            let selected = app.list_state.selected() == Some(i);
+            render_summary(app, frame, area, data, title, selected);
+            return;
+        }
+    }
+    if let Some(key) = app.datasets.keys().nth(i).cloned() {
+        let selected = app.list_state.selected() == Some(i);
+        if render_billboard(app, frame, area, &key, selected)
+            || render_breakdown(app, frame, area, &key, selected)
+            || render_histogram(app, frame, area, &key, selected)
+        {
+            return;
+        }
+    }
+
+    let key = app.datasets.keys().nth(i).cloned();
+    if let Some(key) = key {
+        app.datasets.refresh_chart(&key, area);
+    }
+    let datasets = app
+        .datasets
+        .keys()
+        .nth(i)
+        .and_then(|key| app.datasets.chart(key))
+        .map(|chart| {
+            (
+                facet_datasets(app, chart),
+                chart.y_bounds,
+                y_labels(app, chart),
+                threshold_marks(app, chart),
+            )
+        });
 
     match datasets {
-        Some(datasets) => {
-            let (_, dataset) = app
+        Some((datasets, (min_y, max_y), y_labels, thresholds)) => {
+            let (query, dataset) = app
                 .datasets
                 .iter()
                 .nth(i)
                 .expect("ERROR: Could not index bounds!");
+            let title = dataset.query_alias.as_ref().unwrap_or(query).to_owned();
+            let border_fg = match app.list_state.selected() {
+                Some(selected) if selected == i => app.theme.focus_fg,
+                _ => app.theme.chart_fg,
+            };
 
-            let bounds = dataset.bounds;
             let selection = &dataset.selection;
 
-            let (min_x, mut min_y) = bounds.mins;
-            let (_, mut max_y) = bounds.maxes;
-            let mut half_y = (max_y - min_y) / 2_f64;
-
-            min_y = f64::round(min_y);
-            max_y = f64::round(max_y);
-            half_y = f64::round(half_y);
+            let (min_x, _) = dataset.bounds.mins;
 
             // Create the X axis and define its properties
+            let max_x = Utc::now().timestamp() as f64;
+            let ticks = x_ticks(min_x, max_x, area.width);
             let x_axis = Axis::default()
-                .title("Time".red())
+                .title(app.text.get(Msg::Time).red())
                 .style(Style::default().fg(app.theme.chart_fg))
-                .bounds([min_x, Utc::now().timestamp() as f64])
-                .labels(vec![
-                    DateTime::from_timestamp(min_x as i64, 0)
-                        .unwrap()
-                        .time()
-                        .to_string()
-                        .fg(app.theme.chart_fg)
-                        .bold(),
-                    DateTime::from_timestamp(Utc::now().timestamp(), 0)
-                        .unwrap()
-                        .to_string()
-                        .fg(app.theme.chart_fg)
-                        .bold(),
-                ]);
+                .bounds([min_x, max_x])
+                .labels(
+                    ticks
+                        .iter()
+                        .map(|x| {
+                            time_label(app.timezone, *x, max_x - min_x)
+                                .fg(app.theme.chart_fg)
+                                .bold()
+                        })
+                        .collect(),
+                );
+
+            // Dotted verticals at each tick between the ends, drawn beneath the series
+            let grid = match app.config.gridlines {
+                true => gridlines(&ticks, min_y, max_y),
+                false => vec![],
+            };
+            let lines = threshold_lines(&thresholds, min_x, max_x);
+            let datasets: Vec<Dataset> = grid
+                .iter()
+                .map(|line| {
+                    Dataset::default()
+                        .data(line)
+                        .marker(Marker::Dot)
+                        .graph_type(GraphType::Line)
+                        .style(Style::default().fg(app.theme.chart_fg).dim())
+                })
+                .chain(threshold_datasets(&thresholds, &lines))
+                .chain(datasets)
+                .collect();
 
             // Create the Y axis and define its properties
             let y_axis = Axis::default()
                 .title(selection.clone().fg(app.theme.chart_fg))
                 .style(Style::default().fg(app.theme.chart_fg))
                 .bounds([min_y, max_y])
-                .labels(vec![
-                    min_y.to_string().fg(app.theme.chart_fg).bold(),
-                    half_y.to_string().fg(app.theme.chart_fg).bold(),
-                    max_y.to_string().fg(app.theme.chart_fg).bold(),
-                ]);
+                .labels(
+                    y_labels
+                        .into_iter()
+                        .map(|label| label.fg(app.theme.chart_fg).bold())
+                        .collect(),
+                );
 
             let legend_position = match &datasets.len() {
                 1 => None,
@@ -157,9 +261,15 @@ pub fn render_ith_graph(app: &mut App, frame: &mut Frame, area: Rect, i: usize)
             // Create the chart and link all the parts together
             let chart = Chart::new(datasets)
                 .block(
-                    Block::default(), /*.borders(Borders::ALL).title("Chart")*/
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .border_type(BorderType::Rounded)
+                        .border_style(Style::default().fg(border_fg))
+                        .title(title),
                 )
                 .legend_position(legend_position)
+                // Column names such as percentile.duration.95 outgrow the default quarter
+                .hidden_legend_constraints((Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)))
                 .x_axis(x_axis)
                 .y_axis(y_axis);
             frame.render_widget(chart, area);
@@ -183,10 +293,14 @@ pub fn render_rename_dialog(app: &mut App, frame: &mut Frame, area: Rect) {
     let vertical = Layout::vertical([Constraint::Length(3), Constraint::Length(3)]);
     let [prompt_area, input_area] = vertical.areas(area);
 
-    let prompt = Text::from("Rename query");
-    let input = Paragraph::new(app.input_buffer(RENAME))
+    let (prompt, input) = match app.focus {
+        Focus::TabName => (app.text.get(Msg::RenameTab), app.input_buffer(TAB_NAME)),
+        _ => (app.text.get(Msg::RenameQuery), app.input_buffer(RENAME)),
+    };
+    let prompt = Text::from(prompt);
+    let input = Paragraph::new(input)
         .style(match app.focus {
-            Focus::Rename => Style::default().fg(app.theme.focus_fg),
+            Focus::Rename | Focus::TabName => Style::default().fg(app.theme.focus_fg),
             _ => Style::default(),
         })
         .block(
@@ -200,21 +314,427 @@ pub fn render_rename_dialog(app: &mut App, frame: &mut Frame, area: Rect) {
     frame.render_widget(input, input_area);
 }
 
+pub fn render_export_dialog(app: &mut App, frame: &mut Frame, area: Rect) {
+    let area = centered_rect(60, 20, area);
+    let vertical = Layout::vertical([Constraint::Length(3), Constraint::Length(3)]);
+    let [prompt_area, input_area] = vertical.areas(area);
+
+    let prompt = Text::from(app.text.get(Msg::ExportDashboard));
+    let input = Paragraph::new(app.input_buffer(EXPORT))
+        .style(Style::default().fg(app.theme.focus_fg))
+        .block(
+            Block::default()
+                .padding(Padding::zero())
+                .borders(Borders::BOTTOM),
+        );
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(prompt, prompt_area);
+    frame.render_widget(input, input_area);
+}
+
+pub fn render_note_dialog(app: &mut App, frame: &mut Frame, area: Rect) {
+    let area = centered_rect(60, 20, area);
+    let vertical = Layout::vertical([Constraint::Length(3), Constraint::Length(3)]);
+    let [prompt_area, input_area] = vertical.areas(area);
+
+    let (prompt, input) = match app.focus {
+        Focus::Link => (
+            app.text.get(Msg::AddLink).to_owned(),
+            app.input_buffer(LINK),
+        ),
+        Focus::Axis => (
+            app.text.get(Msg::EditAxis).to_owned(),
+            app.input_buffer(AXIS),
+        ),
+        Focus::Thresholds => (
+            app.text.get(Msg::EditThresholds).to_owned(),
+            app.input_buffer(THRESHOLDS),
+        ),
+        Focus::Variables => (
+            app.text.get(Msg::EditVariables).to_owned(),
+            app.input_buffer(VARIABLES),
+        ),
+        Focus::TemplateVariable => {
+            let variable = app.template_variable().unwrap_or_default();
+            let recent = app.recent_values.get(&variable).len().to_string();
+            (
+                app.text.fill(Msg::TemplateVariable, &[&variable, &recent]),
+                app.input_buffer(TEMPLATE_VARIABLE),
+            )
+        }
+        _ => (
+            app.text.get(Msg::EditNote).to_owned(),
+            app.input_buffer(NOTE),
+        ),
+    };
+    let input = Paragraph::new(input)
+        .style(Style::default().fg(app.theme.focus_fg))
+        .block(
+            Block::default()
+                .padding(Padding::zero())
+                .borders(Borders::BOTTOM),
+        );
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(Text::from(prompt), prompt_area);
+    frame.render_widget(input, input_area);
+}
+
+pub fn render_time_range_dialog(app: &mut App, frame: &mut Frame, area: Rect) {
+    let area = centered_rect(60, 30, area);
+    let vertical = Layout::vertical([Constraint::Length(4), Constraint::Length(3)]);
+    let [prompt_area, input_area] = vertical.areas(area);
+
+    let current = match &app.time_range {
+        Some(range) => app
+            .text
+            .fill(Msg::TimeRangeCurrent, &[&range.since, &range.until]),
+        None => app.text.get(Msg::TimeRangePerQuery).to_owned(),
+    };
+    let presets = TimeRange::PRESETS
+        .iter()
+        .enumerate()
+        .map(|(i, preset)| format!("{}) {}", i + 1, preset))
+        .collect::<Vec<_>>()
+        .join("  ");
+    let prompt = Text::from(vec![
+        Line::from(app.text.get(Msg::TimeRangeTitle)),
+        Line::from(current),
+        Line::from(app.text.fill(Msg::TimeRangeCustom, &[&presets])),
+    ]);
+    let input = Paragraph::new(app.input_buffer(TIME_RANGE))
+        .style(Style::default().fg(app.theme.focus_fg))
+        .block(
+            Block::default()
+                .padding(Padding::zero())
+                .borders(Borders::BOTTOM),
+        );
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(prompt, prompt_area);
+    frame.render_widget(input, input_area);
+}
+
+pub fn render_date_picker(app: &mut App, frame: &mut Frame, area: Rect) {
+    let Some(picker) = &app.date_picker else {
+        return;
+    };
+    let area = centered_rect(60, 30, area);
+    let vertical = Layout::vertical([Constraint::Length(2), Constraint::Length(3)]);
+    let [prompt_area, picker_area] = vertical.areas(area);
+
+    let clause = if picker.until { "UNTIL" } else { "SINCE" };
+    let prompt = Text::from(vec![
+        Line::from(app.text.fill(Msg::DatePickerTitle, &[clause])),
+        Line::from(app.text.get(Msg::DatePickerHelp)),
+    ]);
+
+    let fields = ["%Y", "%m", "%d", "%H", "%M"];
+    let separators = ["-", "-", " ", ":", ":00 %z"];
+    let spans = fields
+        .iter()
+        .zip(separators)
+        .enumerate()
+        .flat_map(|(i, (field, separator))| {
+            let value = picker.time.format(field).to_string();
+            let value = match i == picker.field {
+                true => value.fg(app.theme.focus_fg).reversed(),
+                false => value.fg(app.theme.focus_fg),
+            };
+            [value, picker.time.format(separator).to_string().into()]
+        })
+        .collect::<Vec<_>>();
+    let input = Paragraph::new(Line::from(spans)).block(
+        Block::default()
+            .padding(Padding::zero())
+            .borders(Borders::BOTTOM),
+    );
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(prompt, prompt_area);
+    frame.render_widget(input, picker_area);
+}
+
+pub fn render_details(app: &mut App, frame: &mut Frame, area: Rect) {
+    let mut lines = vec![];
+    if let Some(data) = app.datasets.get(&app.selected_query) {
+        if let Some(notice) = &data.notice {
+            lines.push(Line::from(notice.to_owned().fg(app.theme.webex_fg)));
+        }
+        let facet = app
+            .stats_facet
+            .as_ref()
+            .filter(|facet| data.facets.contains_key(*facet));
+        let points = match facet {
+            Some(facet) => data.facets[facet].clone(),
+            None => total(&data.facets),
+        };
+        if let Some(stats) = Stats::of(&points) {
+            let unit = data.axis.unit.unwrap_or(Unit::infer(&data.selection));
+            let value = |value: f64| unit.format(value, &app.numbers);
+            if let Some(facet) = facet {
+                lines.push(Line::from(facet.to_owned().bold()));
+            }
+            lines.push(Line::from(app.text.fill(
+                Msg::StatsRange,
+                &[&value(stats.min), &value(stats.max), &value(stats.mean)],
+            )));
+            lines.push(Line::from(app.text.fill(
+                Msg::StatsLatest,
+                &[&value(stats.p95), &value(stats.latest)],
+            )));
+            let width = area.width.saturating_sub(2) as usize;
+            if width >= TREND_WIDTH {
+                lines.push(Line::from(sparkline(&points, width).fg(app.theme.value_fg)));
+            }
+        }
+        if !data.note.is_empty() {
+            lines.push(Line::from(
+                format!("{} {}", NOTE_MARKER, data.note).italic(),
+            ));
+        }
+        data.comparison.iter().for_each(|(facet, previous)| {
+            if let Some(current) = data.facets.get(facet) {
+                lines.push(Line::from(format!(
+                    "{}: {} vs previous",
+                    facet,
+                    change(app, current, previous)
+                )));
+            }
+        });
+        if let Some(snapshot) = &data.snapshot {
+            lines.push(Line::from(
+                app.text
+                    .fill(
+                        Msg::SnapshotFrom,
+                        &[&app.timezone.format(snapshot.taken, "%H:%M")],
+                    )
+                    .fg(app.theme.chart_fg),
+            ));
+            snapshot.facets.iter().for_each(|(facet, frozen)| {
+                if let Some(current) = data.facets.get(facet) {
+                    lines.push(Line::from(format!(
+                        "{}: {}",
+                        facet,
+                        change(app, current, frozen)
+                    )));
+                }
+            });
+        }
+        data.links.iter().enumerate().for_each(|(i, link)| {
+            lines.push(Line::from(
+                format!("[{}] {}", i + 1, link).fg(app.theme.value_fg),
+            ));
+        });
+    }
+
+    let details = Paragraph::new(lines).wrap(Wrap { trim: true }).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .title(app.text.get(Msg::Details)),
+    );
+    frame.render_widget(details, area);
+}
+
+// The latest anomaly of each facet across the tab's queries
+pub fn anomaly_lines(app: &App) -> Vec<Line<'static>> {
+    app.datasets
+        .iter()
+        .flat_map(|(query, data)| {
+            let name = data.query_alias.as_deref().unwrap_or(query);
+            let unit = data.axis.unit.unwrap_or(Unit::infer(&data.selection));
+            data.anomalies.iter().filter_map(move |(facet, points)| {
+                let (x, y) = points.last()?;
+                let name = match data.facets.len() {
+                    1 => name.to_owned(),
+                    _ => format!("{} {}", name, facet),
+                };
+                Some(Line::from(app.text.fill(
+                    Msg::AnomalyAt,
+                    &[
+                        &unit.format(*y, &app.numbers),
+                        &app.timezone.format(*x, "%H:%M"),
+                        &name,
+                    ],
+                )))
+            })
+        })
+        .collect()
+}
+
+pub fn render_anomalies(app: &mut App, frame: &mut Frame, area: Rect) {
+    let anomalies = Paragraph::new(anomaly_lines(app)).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(ANOMALY))
+            .title(app.text.get(Msg::Anomalies)),
+    );
+    frame.render_widget(anomalies, area);
+}
+
+// One block per column, each as high as the mean of the points falling in it
+fn sparkline(points: &[(f64, f64)], width: usize) -> String {
+    const LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    if points.is_empty() || width == 0 {
+        return String::new();
+    }
+    let columns = points
+        .chunks(points.len().div_ceil(width))
+        .map(mean)
+        .collect::<Vec<_>>();
+    let (low, high) = columns.iter().fold((f64::MAX, f64::MIN), |(low, high), y| {
+        (low.min(*y), high.max(*y))
+    });
+    columns
+        .iter()
+        .map(|y| match high > low {
+            true => LEVELS[((y - low) / (high - low) * 7.0).round() as usize],
+            false => LEVELS[0],
+        })
+        .collect()
+}
+
+// Graphs shorter than this are drawn as a sparkline per facet, as a chart would be unreadable
+const SPARKLINE_HEIGHT: u16 = 12;
+// Details narrower than this leave out the trend, too few columns to show one
+const TREND_WIDTH: usize = 20;
+
+fn render_sparklines(app: &App, frame: &mut Frame, area: Rect, data: &AppDataset) {
+    let width = area.width.saturating_sub(2) as usize;
+    let lines = data
+        .facets
+        .iter()
+        .map(|(facet, points)| {
+            let latest = points
+                .last()
+                .map(|(_, y)| app.numbers.format(*y, 2))
+                .unwrap_or_default();
+            let label = format!("{} {} ", facet, latest);
+            let spark = sparkline(points, width.saturating_sub(label.chars().count()));
+            Line::from(vec![
+                label.fg(app.theme.value_fg),
+                spark.fg(app.theme.focus_fg),
+            ])
+        })
+        .collect::<Vec<_>>();
+    let title = data
+        .query_alias
+        .clone()
+        .unwrap_or(app.selected_query.to_owned());
+    let sparklines = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .title(title),
+    );
+    frame.render_widget(sparklines, area);
+}
+
+// A bar filled to a fraction between 0 and 1
+fn gauge(fraction: f64, width: usize) -> String {
+    let filled = ((fraction.clamp(0.0, 1.0) * width as f64).round() as usize).min(width);
+    format!("{}{}", "█".repeat(filled), "░".repeat(width - filled))
+}
+
+// Percentage change in the mean, e.g. "+12.5%"
+fn change(app: &App, current: &[(f64, f64)], previous: &[(f64, f64)]) -> String {
+    let (current, previous) = (mean(current), mean(previous));
+    let delta = if previous == 0.0 {
+        0.0
+    } else {
+        (current - previous) / previous * 100.0
+    };
+    let sign = if delta >= 0.0 { "+" } else { "" };
+    format!("{}{}%", sign, app.numbers.format(delta, 1))
+}
+
+fn mean(points: &[(f64, f64)]) -> f64 {
+    match points.len() {
+        0 => 0.0,
+        n => points.iter().map(|(_, y)| y).sum::<f64>() / n as f64,
+    }
+}
+
 pub fn render_query_list(app: &mut App, frame: &mut Frame, area: Rect) {
     let items = app
         .datasets
         .iter()
-        .map(|(query, data)| match &data.query_alias {
-            Some(alias) => alias.to_owned(),
-            None => query.to_owned(),
+        .map(|(query, data)| {
+            let name = match &data.query_alias {
+                Some(alias) => alias.to_owned(),
+                None => query.to_owned(),
+            };
+            // The note itself is in the details pane
+            let name = match data.note.is_empty() {
+                true => name,
+                false => format!("{} {}", NOTE_MARKER, name),
+            };
+            let name = match app.datasets.is_pinned(query) {
+                true => format!("* {}", name),
+                false => name,
+            };
+            // Slow queries show how long they've taken, rather than looking like they have no data
+            let name = match fetching(app, query) {
+                Some(fetching) => format!("{} {}", fetching, name),
+                None => name,
+            };
+            // Red while the latest value is over a threshold
+            match data.breached() {
+                Some(_) => ListItem::new(name).style(Style::new().red()),
+                None => ListItem::new(name),
+            }
         })
         .collect::<Vec<_>>();
+    // Queries without a first result yet, which can't be selected until they have one
+    let waiting = app.pending.iter().map(|(query, (alias, _))| {
+        let name = match alias.is_empty() {
+            true => query.to_owned(),
+            false => alias.to_owned(),
+        };
+        let line = match fetching(app, query) {
+            Some(fetching) => format!("{} {}", fetching, name),
+            None => format!("{} {}", name, app.text.get(Msg::Queued)),
+        };
+        ListItem::new(line).style(Style::new().fg(app.theme.chart_fg))
+    });
+    let items = items.into_iter().chain(waiting).collect::<Vec<_>>();
+    let mut flags = vec![];
+    if app.load_total > 0 {
+        let loaded = app.load_total - app.loading.len();
+        flags.push(app.text.fill(
+            Msg::Loading,
+            &[&loaded.to_string(), &app.load_total.to_string()],
+        ));
+    }
+    if app.idle {
+        flags.push(app.text.get(Msg::Paused).to_owned());
+    }
+    if app.read_only {
+        flags.push(app.text.get(Msg::ReadOnly).to_owned());
+    }
+    if let Some(message) = &app.export_message {
+        flags.push(message.to_owned());
+    }
+    if let Some(preset) = app.layout.and_then(|i| app.config.layouts.get(i)) {
+        flags.push(preset.name.to_owned());
+    }
+    let title = match flags.is_empty() {
+        true => app.text.get(Msg::ActiveQueries).to_owned(),
+        false => format!(
+            "{} ({})",
+            app.text.get(Msg::ActiveQueries),
+            flags.join(", ")
+        ),
+    };
     let list = List::new(items)
         .block(
             Block::default()
                 .borders(Borders::ALL)
                 .border_type(BorderType::Rounded)
-                .title("Active Queries"),
+                .title(title),
         )
         .highlight_style(
             Style::new()
@@ -224,107 +744,891 @@ pub fn render_query_list(app: &mut App, frame: &mut Frame, area: Rect) {
         .highlight_symbol(">>")
         .repeat_highlight_symbol(true);
 
+    app.list_height = area.height.saturating_sub(2);
     frame.render_stateful_widget(list, area, &mut app.list_state);
 }
 
-pub fn render_query_box(app: &mut App, frame: &mut Frame, area: Rect) {
-    let input = Paragraph::new(app.inputs[QUERY as usize].buffer.as_str())
-        .style(match app.focus {
-            Focus::QueryInput => Style::default().fg(app.theme.focus_fg),
-            _ => Style::default(),
+pub fn render_traces(app: &mut App, frame: &mut Frame, area: Rect) {
+    let [list_area, waterfall_area] =
+        Layout::horizontal([Constraint::Percentage(30), Constraint::Min(20)]).areas(area);
+
+    let items = app
+        .traces
+        .summaries
+        .iter()
+        .map(|summary| {
+            let name = summary
+                .root_entity_name
+                .as_deref()
+                .or(summary.root_span_name.as_deref())
+                .unwrap_or(&summary.trace_id);
+            let errors = match summary.error_count.unwrap_or(0) {
+                0 => String::new(),
+                n => app.text.fill(Msg::TraceErrors, &[&n.to_string()]),
+            };
+            format!(
+                "{} {} ms{}",
+                name,
+                app.numbers.format(summary.duration_ms.unwrap_or(0.0), 0),
+                errors
+            )
         })
+        .collect::<Vec<_>>();
+    let title = match app.traces.loading && app.traces.spans.is_none() {
+        true => app.text.get(Msg::TracesLoading),
+        false => app.text.get(Msg::Traces),
+    };
+    let list = List::new(items)
         .block(
             Block::default()
                 .borders(Borders::ALL)
                 .border_type(BorderType::Rounded)
-                .title("Enter query: "),
-        );
-    frame.render_widget(input, area);
+                .title(title),
+        )
+        .highlight_style(
+            Style::new()
+                .add_modifier(Modifier::REVERSED)
+                .fg(app.theme.chart_fg),
+        )
+        .highlight_symbol(">>");
+    app.view_height = list_area.height.saturating_sub(2);
+    frame.render_stateful_widget(list, list_area, &mut app.traces.list_state);
+
+    let (title, lines) = match &app.traces.spans {
+        Some((trace_id, spans)) if spans.is_empty() => (
+            app.text.fill(Msg::Trace, &[trace_id]),
+            vec![Line::from(match app.traces.loading {
+                true => app.text.get(Msg::LoadingSpans),
+                false => app.text.get(Msg::NoSpans),
+            })],
+        ),
+        Some((trace_id, spans)) => (
+            app.text
+                .fill(Msg::TraceSpans, &[trace_id, &spans.len().to_string()]),
+            waterfall(app, spans, waterfall_area.width.saturating_sub(2)),
+        ),
+        None => (
+            app.text.get(Msg::Waterfall).to_owned(),
+            vec![Line::from(app.text.get(Msg::TracesHelp))],
+        ),
+    };
+    let waterfall = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .title(title),
+    );
+    frame.render_widget(waterfall, waterfall_area);
 }
 
-pub fn render_graph(app: &mut App, frame: &mut Frame, area: Rect) {
-    let datasets = app.datasets.get(&app.selected_query).map(|data| {
-        data.facets
-            .iter()
-            .map(|(facet, points)| {
-                Dataset::default()
-                    .name(facet.to_owned())
-                    .data(&points[..])
-                    .marker(Marker::Braille)
-                    .graph_type(GraphType::Line)
-                    .style(match facet.as_str() {
-                        ".NET" => Style::default().fg(app.theme.net_fg),
-                        "Elasticsearch" => Style::default().fg(app.theme.elastic_fg),
-                        "Web external" => Style::default().fg(app.theme.webex_fg),
-                        "value" => Style::default().fg(app.theme.value_fg),
-                        _ => Style::default(),
-                    })
-            })
-            .collect::<Vec<_>>()
-    });
+pub fn render_alerts(app: &mut App, frame: &mut Frame, area: Rect) {
+    let [list_area, status_area] =
+        Layout::vertical([Constraint::Min(1), Constraint::Length(3)]).areas(area);
 
-    match datasets {
-        Some(datasets) => {
-            let dataset = app
-                .datasets
-                .get(&app.selected_query)
-                .expect("ERROR: No bounds found for selected query");
-            let bounds = dataset.bounds;
-            let selection = &dataset.selection;
+    let items = app
+        .alerts
+        .issues
+        .iter()
+        .map(|issue| {
+            let colour = match issue.priority.as_str() {
+                "CRITICAL" => Color::Red,
+                "HIGH" => Color::LightRed,
+                "MEDIUM" => Color::Yellow,
+                _ => app.theme.value_fg,
+            };
+            let acknowledged = match issue.acknowledged_at {
+                Some(_) => " (acknowledged)",
+                None => "",
+            };
+            Line::from(vec![
+                format!("{:<9}", issue.priority).fg(colour).bold(),
+                Span::raw(format!(
+                    "{} [{}] {}{}",
+                    issue.title.join(", "),
+                    issue.entity_names.join(", "),
+                    issue.condition_name.join(", "),
+                    acknowledged
+                )),
+            ])
+        })
+        .collect::<Vec<_>>();
+    let title = match app.alerts.loading {
+        true => app.text.get(Msg::OpenIssuesLoading),
+        false => app.text.get(Msg::OpenIssues),
+    };
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .title(title),
+        )
+        .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+        .highlight_symbol(">> ");
+    app.view_height = list_area.height.saturating_sub(2);
+    frame.render_stateful_widget(list, list_area, &mut app.alerts.list_state);
 
-            let (min_x, mut min_y) = bounds.mins;
-            let (_, mut max_y) = bounds.maxes;
-            let mut half_y = (max_y - min_y) / 2_f64;
+    let status = app
+        .alerts
+        .message
+        .clone()
+        .unwrap_or(app.text.get(Msg::AlertsHelp).to_owned());
+    let status = Paragraph::new(status).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded),
+    );
+    frame.render_widget(status, status_area);
+}
 
-            min_y = f64::round(min_y);
-            max_y = f64::round(max_y);
-            half_y = f64::round(half_y);
+pub fn render_workloads(app: &mut App, frame: &mut Frame, area: Rect) {
+    let [list_area, status_area] =
+        Layout::vertical([Constraint::Min(1), Constraint::Length(3)]).areas(area);
 
-            // Create the X axis and define its properties
-            let x_axis = Axis::default()
-                .title("Time".fg(app.theme.chart_fg))
-                .style(Style::default().fg(app.theme.chart_fg))
-                .bounds([min_x, Utc::now().timestamp() as f64])
-                .labels(vec![
-                    DateTime::from_timestamp(min_x as i64, 0)
-                        .unwrap()
-                        .time()
-                        .to_string()
-                        .fg(app.theme.chart_fg)
-                        .bold(),
-                    DateTime::from_timestamp(Utc::now().timestamp(), 0)
-                        .unwrap()
-                        .to_string()
-                        .fg(app.theme.chart_fg)
-                        .bold(),
-                ]);
+    let items = app
+        .workloads
+        .workloads
+        .iter()
+        .map(|workload| {
+            let status = workload
+                .workload_status
+                .as_ref()
+                .map_or("UNKNOWN", |status| status.status_value.as_str());
+            let colour = match status {
+                "DISRUPTED" => Color::Red,
+                "DEGRADED" => Color::Yellow,
+                "OPERATIONAL" => Color::Green,
+                _ => app.theme.value_fg,
+            };
+            Line::from(vec![
+                format!("{:<12}", status).fg(colour).bold(),
+                Span::raw(workload.name.to_owned()),
+            ])
+        })
+        .collect::<Vec<_>>();
+    let title = match app.workloads.loading {
+        true => app.text.get(Msg::WorkloadsLoading),
+        false => app.text.get(Msg::Workloads),
+    };
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .title(title),
+        )
+        .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+        .highlight_symbol(">> ");
+    app.view_height = list_area.height.saturating_sub(2);
+    frame.render_stateful_widget(list, list_area, &mut app.workloads.list_state);
 
-            // Create the Y axis and define its properties
-            let y_axis = Axis::default()
-                .title(selection.clone().fg(app.theme.chart_fg))
-                .style(Style::default().fg(app.theme.chart_fg))
-                .bounds([min_y, max_y])
-                .labels(vec![
-                    min_y.to_string().fg(app.theme.chart_fg).bold(),
-                    half_y.to_string().fg(app.theme.chart_fg).bold(),
-                    max_y.to_string().fg(app.theme.chart_fg).bold(),
-                ]);
+    let status = app
+        .workloads
+        .message
+        .clone()
+        .unwrap_or(app.text.get(Msg::WorkloadsHelp).to_owned());
+    let status = Paragraph::new(status).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded),
+    );
+    frame.render_widget(status, status_area);
+}
 
-            let legend_position = match &datasets.len() {
-                1 => None,
-                _ => Some(LegendPosition::TopRight),
-            };
+pub fn render_templates(app: &mut App, frame: &mut Frame, area: Rect) {
+    let items = app
+        .config
+        .templates
+        .iter()
+        .map(|template| {
+            Line::from(vec![
+                format!("{:<24}", template.name)
+                    .fg(app.theme.focus_fg)
+                    .bold(),
+                Span::raw(template.query.to_owned()),
+            ])
+        })
+        .collect::<Vec<_>>();
+    let title = match items.is_empty() {
+        true => app.text.get(Msg::NoTemplates),
+        false => app.text.get(Msg::Templates),
+    };
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .title(title),
+        )
+        .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+        .highlight_symbol(">> ");
+    app.view_height = area.height.saturating_sub(2);
+    frame.render_stateful_widget(list, area, &mut app.templates.list_state);
+}
 
-            // Create the chart and link all the parts together
-            let chart = Chart::new(datasets)
-                .block(
-                    Block::default()
+pub fn render_import(app: &mut App, frame: &mut Frame, area: Rect) {
+    let [list_area, status_area] =
+        Layout::vertical([Constraint::Min(1), Constraint::Length(3)]).areas(area);
+
+    let items = app
+        .import
+        .dashboards
+        .iter()
+        .map(|dashboard| dashboard.name.to_owned())
+        .collect::<Vec<_>>();
+    let title = match app.import.loading {
+        true => app.text.get(Msg::DashboardsLoading),
+        false => app.text.get(Msg::Dashboards),
+    };
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .title(title),
+        )
+        .highlight_style(
+            Style::new()
+                .add_modifier(Modifier::REVERSED)
+                .fg(app.theme.chart_fg),
+        )
+        .highlight_symbol(">> ");
+    app.view_height = list_area.height.saturating_sub(2);
+    frame.render_stateful_widget(list, list_area, &mut app.import.list_state);
+
+    let status = app
+        .import
+        .message
+        .clone()
+        .unwrap_or(app.text.get(Msg::ImportHelp).to_owned());
+    let status = Paragraph::new(status).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded),
+    );
+    frame.render_widget(status, status_area);
+}
+
+pub fn render_console(app: &mut App, frame: &mut Frame, area: Rect) {
+    let [input_area, response_area] =
+        Layout::vertical([Constraint::Length(3), Constraint::Min(1)]).areas(area);
+    let input_fg = match app.input_mode {
+        InputMode::Input => app.theme.focus_fg,
+        InputMode::Normal => app.theme.chart_fg,
+    };
+    let input = Paragraph::new(app.input_buffer(CONSOLE)).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(input_fg))
+            .title(app.text.get(Msg::Console)),
+    );
+    frame.render_widget(input, input_area);
+
+    let title = match app.console.loading {
+        true => app.text.get(Msg::ConsoleLoading),
+        false => app.text.get(Msg::ConsoleResponse),
+    };
+    let response = Paragraph::new(app.console.response.as_str())
+        .scroll((app.console.scroll, 0))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .title(title),
+        );
+    frame.render_widget(response, response_area);
+}
+
+pub fn render_entities(app: &mut App, frame: &mut Frame, area: Rect) {
+    let metric = |value: Option<f64>, precision: usize| {
+        value
+            .map(|value| app.numbers.format(value, precision))
+            .unwrap_or("-".to_owned())
+    };
+    let name_width = app
+        .entities
+        .entities
+        .iter()
+        .map(|entity| entity.name.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let mut items = vec![format!(
+        "   {:<width$}  {:>10}  {:>8}  {:>6}  {:>8}",
+        "Name",
+        "rpm",
+        "errors %",
+        "apdex",
+        "ms",
+        width = name_width
+    )];
+    items.extend(app.entities.entities.iter().map(|entity| {
+        // Only APM apps have a summary to show
+        if app.entities.kind != EntityKind::Apm {
+            return entity.name.clone();
+        }
+        let summary = entity.apm_summary.clone().unwrap_or_default();
+        format!(
+            "{:<width$}  {:>10}  {:>8}  {:>6}  {:>8}",
+            entity.name,
+            metric(summary.throughput, 1),
+            metric(summary.error_rate.map(|rate| rate * 100.0), 2),
+            metric(summary.apdex_score, 2),
+            metric(summary.response_time_average.map(|s| s * 1000.0), 1),
+            width = name_width
+        )
+    }));
+
+    if app.entities.kind != EntityKind::Apm {
+        items[0] = "   Name".to_owned();
+    }
+    let title = match (app.entities.kind, app.entities.loading) {
+        (EntityKind::Apm, true) => app.text.get(Msg::EntitiesLoading),
+        (EntityKind::Apm, false) => app.text.get(Msg::Entities),
+        (EntityKind::Browser, true) => app.text.get(Msg::BrowserAppsLoading),
+        (EntityKind::Browser, false) => app.text.get(Msg::BrowserApps),
+        (EntityKind::Mobile, true) => app.text.get(Msg::MobileAppsLoading),
+        (EntityKind::Mobile, false) => app.text.get(Msg::MobileApps),
+    };
+    let [header_area, list_area] =
+        Layout::vertical([Constraint::Length(3), Constraint::Min(1)]).areas(area);
+    let header = Paragraph::new(items.remove(0)).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .title(title),
+    );
+    frame.render_widget(header, header_area);
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded),
+        )
+        .highlight_style(
+            Style::new()
+                .add_modifier(Modifier::REVERSED)
+                .fg(app.theme.chart_fg),
+        )
+        .highlight_symbol(">> ");
+    app.view_height = list_area.height.saturating_sub(2);
+    frame.render_stateful_widget(list, list_area, &mut app.entities.list_state);
+}
+
+pub fn render_hosts(app: &mut App, frame: &mut Frame, area: Rect) {
+    const SPARK_WIDTH: usize = 12;
+    let latest = |series: &[(f64, f64)]| match series.last() {
+        Some((_, y)) => format!("{:>5}%", app.numbers.format(*y, 1)),
+        None => format!("{:>6}", "-"),
+    };
+    let name_width = app
+        .hosts
+        .hosts
+        .iter()
+        .map(|host| host.name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(4);
+
+    // The sorted column is marked with an arrow
+    let columns = ["Name", "cpu", "memory", "disk"]
+        .iter()
+        .enumerate()
+        .map(|(i, column)| match i == app.hosts.sort {
+            true => format!("{} ▼", column),
+            false => column.to_string(),
+        })
+        .collect::<Vec<_>>();
+    let mut items = vec![format!(
+        "   {:<width$}  {:<spark$}  {:<spark$}  {:<spark$}",
+        columns[0],
+        columns[1],
+        columns[2],
+        columns[3],
+        width = name_width,
+        spark = SPARK_WIDTH + 7
+    )];
+    items.extend(app.hosts.hosts.iter().map(|host| {
+        let cells = host
+            .metrics()
+            .iter()
+            .map(|series| {
+                format!(
+                    "{:<spark$} {}",
+                    sparkline(series, SPARK_WIDTH),
+                    latest(series),
+                    spark = SPARK_WIDTH
+                )
+            })
+            .collect::<Vec<_>>();
+        format!(
+            "{:<width$}  {}",
+            host.name,
+            cells.join("  "),
+            width = name_width
+        )
+    }));
+
+    let title = match app.hosts.loading {
+        true => app.text.get(Msg::HostsLoading),
+        false => app.text.get(Msg::Hosts),
+    };
+    let [header_area, list_area] =
+        Layout::vertical([Constraint::Length(3), Constraint::Min(1)]).areas(area);
+    let header = Paragraph::new(items.remove(0)).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .title(title),
+    );
+    frame.render_widget(header, header_area);
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded),
+        )
+        .highlight_style(
+            Style::new()
+                .add_modifier(Modifier::REVERSED)
+                .fg(app.theme.chart_fg),
+        )
+        .highlight_symbol(">> ");
+    app.view_height = list_area.height.saturating_sub(2);
+    frame.render_stateful_widget(list, list_area, &mut app.hosts.list_state);
+}
+
+// One line per SLI: attainment against target, the error budget left as a gauge and the
+// burn rate over the last day
+pub fn render_slos(app: &mut App, frame: &mut Frame, area: Rect) {
+    let name_width = app
+        .slos
+        .slos
+        .iter()
+        .map(|slo| slo.name.chars().count())
+        .max()
+        .unwrap_or(0);
+    let items = app
+        .slos
+        .slos
+        .iter()
+        .map(|slo| {
+            let percent = |value: f64| Unit::Percent.format(value, &app.numbers);
+            let attainment = slo.attainment.map_or("-".to_owned(), percent);
+            let budget = slo.budget();
+            let remaining = budget.map_or("-".to_owned(), |budget| {
+                format!("{}%", app.numbers.format(budget, 0))
+            });
+            let burn = slo.burn.last().map_or("-".to_owned(), |(_, burn)| {
+                format!("{}x", app.numbers.format(*burn, 1))
+            });
+            let line = format!(
+                "{:<width$}  {:>7} / {:<7}  {} {} {:>5}  {} {:<12} {:>6}",
+                slo.name,
+                attainment,
+                percent(slo.target),
+                app.text.get(Msg::SloBudget),
+                gauge(budget.unwrap_or(0.0) / 100.0, 10),
+                remaining,
+                app.text.get(Msg::SloBurn),
+                sparkline(&slo.burn, 12),
+                burn,
+                width = name_width
+            );
+            // Red once the budget is spent
+            match budget {
+                Some(budget) if budget < 0.0 => ListItem::new(line).style(Style::new().red()),
+                _ => ListItem::new(line),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let title = match app.slos.loading {
+        true => app.text.get(Msg::ServiceLevelsLoading),
+        false => app.text.get(Msg::ServiceLevels),
+    };
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .title(title),
+        )
+        .highlight_style(
+            Style::new()
+                .add_modifier(Modifier::REVERSED)
+                .fg(app.theme.chart_fg),
+        )
+        .highlight_symbol(">> ");
+    app.view_height = area.height.saturating_sub(2);
+    frame.render_stateful_widget(list, area, &mut app.slos.list_state);
+}
+
+// Crash groups above, where and how the selected group last crashed below
+pub fn render_crashes(app: &mut App, frame: &mut Frame, area: Rect) {
+    let items = app
+        .crashes
+        .groups
+        .iter()
+        .map(|group| {
+            format!(
+                "{:>7} crashes  {:>6} users  {} at {}",
+                app.numbers.format(group.crashes, 0),
+                app.numbers.format(group.users, 0),
+                group.exception,
+                group.location
+            )
+        })
+        .collect::<Vec<_>>();
+    let details = app
+        .crashes
+        .list_state
+        .selected()
+        .and_then(|i| app.crashes.groups.get(i))
+        .map(|group| {
+            group
+                .details
+                .iter()
+                .map(|(name, value)| format!("{}: {}", name, value))
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .unwrap_or_default();
+
+    let title = match app.crashes.loading {
+        true => app.text.fill(Msg::CrashesLoading, &[&app.crashes.app]),
+        false => app.text.fill(Msg::Crashes, &[&app.crashes.app]),
+    };
+    let [list_area, details_area] =
+        Layout::vertical([Constraint::Min(3), Constraint::Length(8)]).areas(area);
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .title(title),
+        )
+        .highlight_style(
+            Style::new()
+                .add_modifier(Modifier::REVERSED)
+                .fg(app.theme.chart_fg),
+        )
+        .highlight_symbol(">> ");
+    app.view_height = list_area.height.saturating_sub(2);
+    frame.render_stateful_widget(list, list_area, &mut app.crashes.list_state);
+
+    let details = Paragraph::new(details).wrap(Wrap { trim: false }).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .title(app.text.get(Msg::CrashLatest)),
+    );
+    frame.render_widget(details, details_area);
+}
+
+pub fn render_kube(app: &mut App, frame: &mut Frame, area: Rect) {
+    // Columns are whatever the query for this level selected, by name
+    let columns: Vec<String> = app
+        .kube
+        .rows
+        .first()
+        .map(|row| row.values.iter().map(|(name, _)| name.clone()).collect())
+        .unwrap_or_default();
+    let name_width = app
+        .kube
+        .rows
+        .iter()
+        .map(|row| row.name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(4);
+    let cell_width = columns.iter().map(String::len).max().unwrap_or(0).max(10);
+
+    let mut header = format!("   {:<width$}", "Name", width = name_width);
+    for column in &columns {
+        header.push_str(&format!("  {:>width$}", column, width = cell_width));
+    }
+    let items = app
+        .kube
+        .rows
+        .iter()
+        .map(|row| {
+            let mut item = format!("{:<width$}", row.name, width = name_width);
+            for column in &columns {
+                let value = row
+                    .values
+                    .iter()
+                    .find(|(name, _)| name == column)
+                    .map(|(_, value)| Unit::infer(column).format(*value, &app.numbers))
+                    .unwrap_or("-".to_owned());
+                item.push_str(&format!("  {:>width$}", value, width = cell_width));
+            }
+            item
+        })
+        .collect::<Vec<_>>();
+
+    let path = match app.kube.path.is_empty() {
+        true => "clusters".to_owned(),
+        false => app.kube.path.join(" / "),
+    };
+    let title = match app.kube.loading {
+        true => app.text.fill(Msg::KubernetesLoading, &[&path]),
+        false => app.text.fill(Msg::Kubernetes, &[&path]),
+    };
+    let [header_area, list_area] =
+        Layout::vertical([Constraint::Length(3), Constraint::Min(1)]).areas(area);
+    let header = Paragraph::new(header).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .title(title),
+    );
+    frame.render_widget(header, header_area);
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded),
+        )
+        .highlight_style(
+            Style::new()
+                .add_modifier(Modifier::REVERSED)
+                .fg(app.theme.chart_fg),
+        )
+        .highlight_symbol(">> ");
+    app.view_height = list_area.height.saturating_sub(2);
+    frame.render_stateful_widget(list, list_area, &mut app.kube.list_state);
+}
+
+// One row per span: indented name, then a bar placed by start time and scaled by duration
+fn waterfall(app: &App, spans: &[TraceSpan], width: u16) -> Vec<Line<'static>> {
+    const LABEL_WIDTH: usize = 40;
+    let bar_width = usize::max((width as usize).saturating_sub(LABEL_WIDTH + 12), 1);
+
+    let duration = |span: &TraceSpan| span.duration_ms.unwrap_or(0.0);
+    let start = spans
+        .iter()
+        .map(|span| span.timestamp)
+        .fold(f64::MAX, f64::min);
+    let end = spans
+        .iter()
+        .map(|span| span.timestamp + duration(span))
+        .fold(f64::MIN, f64::max);
+    let total = f64::max(end - start, 1.0);
+
+    let parents = spans
+        .iter()
+        .map(|span| (span.id.as_str(), span.parent_id.as_deref()))
+        .collect::<BTreeMap<_, _>>();
+    let depth = |span: &TraceSpan| {
+        let mut depth = 0;
+        let mut parent = span.parent_id.as_deref();
+        // Bounded, in case of cycles in malformed data
+        while let Some(id) = parent {
+            if depth >= spans.len() || !parents.contains_key(id) {
+                break;
+            }
+            depth += 1;
+            parent = parents[id];
+        }
+        depth
+    };
+
+    let mut ordered = spans.iter().collect::<Vec<_>>();
+    ordered.sort_by(|a, b| a.timestamp.total_cmp(&b.timestamp));
+    ordered
+        .into_iter()
+        .map(|span| {
+            let label = format!(
+                "{}{}: {}",
+                "  ".repeat(depth(span)),
+                span.service_name.as_deref().unwrap_or("unknown"),
+                span.name.as_deref().unwrap_or(&span.id)
+            );
+            let label = label.chars().take(LABEL_WIDTH - 1).collect::<String>();
+
+            let offset = ((span.timestamp - start) / total * bar_width as f64) as usize;
+            let offset = usize::min(offset, bar_width - 1);
+            let length = (duration(span) / total * bar_width as f64).round() as usize;
+            let length = length.clamp(1, bar_width - offset);
+
+            let (style, marker) = match span.is_error() {
+                true => (Style::default().fg(Color::Red), " !"),
+                false => (Style::default().fg(app.theme.value_fg), ""),
+            };
+            Line::from(vec![
+                Span::raw(format!("{:<width$}", label, width = LABEL_WIDTH)),
+                Span::raw(" ".repeat(offset)),
+                Span::styled("█".repeat(length), style),
+                Span::raw(format!(" {} ms", app.numbers.format(duration(span), 1))),
+                Span::styled(marker, style),
+            ])
+        })
+        .collect()
+}
+
+pub fn render_query_box(app: &mut App, frame: &mut Frame, area: Rect) {
+    let mut block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(app.text.get(Msg::EnterQuery));
+    if let Some(error) = &app.query_error {
+        block = block
+            .border_style(Style::default().red())
+            .title_bottom(app.text.fill(Msg::InvalidQuery, &[error.as_str()]).red());
+    }
+    let input = Paragraph::new(app.inputs[QUERY as usize].buffer.as_str())
+        .style(match app.focus {
+            Focus::QueryInput => Style::default().fg(app.theme.focus_fg),
+            _ => Style::default(),
+        })
+        .block(block);
+    frame.render_widget(input, area);
+}
+
+pub fn render_graph(app: &mut App, frame: &mut Frame, area: Rect) {
+    let key = app.selected_query.to_owned();
+    if !key.is_empty() && render_placeholder(app, frame, area, &key, true) {
+        return;
+    }
+
+    if app.config.screen_reader {
+        if let Some(data) = app.datasets.get(&app.selected_query) {
+            let title = data
+                .query_alias
+                .as_ref()
+                .unwrap_or(&app.selected_query)
+                .to_owned();
+            render_summary(app, frame, area, data, title, true);
+            return;
+        }
+    }
+    if render_billboard(app, frame, area, &key, true)
+        || render_breakdown(app, frame, area, &key, true)
+        || render_histogram(app, frame, area, &key, true)
+    {
+        return;
+    }
+    if area.height < SPARKLINE_HEIGHT {
+        if let Some(data) = app
+            .datasets
+            .get(&key)
+            .filter(|data| !data.facets.is_empty())
+        {
+            render_sparklines(app, frame, area, data);
+            return;
+        }
+    }
+
+    // Vertical markers at either edge of the scrub selection
+    let scrub_lines =
+        app.scrub
+            .zip(app.datasets.get(&app.selected_query))
+            .map(|((start, end), data)| {
+                let (min_y, max_y) = (data.bounds.mins.1, data.bounds.maxes.1);
+                [
+                    [(start, min_y), (start, max_y)],
+                    [(end, min_y), (end, max_y)],
+                ]
+            });
+
+    app.datasets.refresh_chart(&key, area);
+    let datasets = app.datasets.chart(&key).map(|chart| {
+        let mut datasets = facet_datasets(app, chart);
+        let (y_bounds, y_labels) = (chart.y_bounds, y_labels(app, chart));
+        let thresholds = threshold_marks(app, chart);
+        if let Some(lines) = &scrub_lines {
+            datasets.extend(lines.iter().map(|line| {
+                Dataset::default()
+                    .data(line)
+                    .marker(Marker::Braille)
+                    .graph_type(GraphType::Line)
+                    .style(Style::default().fg(app.theme.focus_fg))
+            }));
+        }
+        (datasets, y_bounds, y_labels, thresholds)
+    });
+
+    match datasets {
+        Some((datasets, (min_y, max_y), y_labels, thresholds)) => {
+            let dataset = app
+                .datasets
+                .get(&app.selected_query)
+                .expect("ERROR: No bounds found for selected query");
+            let selection = &dataset.selection;
+
+            let (min_x, _) = dataset.bounds.mins;
+
+            // Create the X axis and define its properties
+            let max_x = Utc::now().timestamp() as f64;
+            let ticks = x_ticks(min_x, max_x, area.width);
+            let x_axis = Axis::default()
+                .title(app.text.get(Msg::Time).fg(app.theme.chart_fg))
+                .style(Style::default().fg(app.theme.chart_fg))
+                .bounds([min_x, max_x])
+                .labels(
+                    ticks
+                        .iter()
+                        .map(|x| {
+                            time_label(app.timezone, *x, max_x - min_x)
+                                .fg(app.theme.chart_fg)
+                                .bold()
+                        })
+                        .collect(),
+                );
+
+            // Dotted verticals at each tick between the ends, drawn beneath the series
+            let grid = match app.config.gridlines {
+                true => gridlines(&ticks, min_y, max_y),
+                false => vec![],
+            };
+            let lines = threshold_lines(&thresholds, min_x, max_x);
+            let datasets: Vec<Dataset> = grid
+                .iter()
+                .map(|line| {
+                    Dataset::default()
+                        .data(line)
+                        .marker(Marker::Dot)
+                        .graph_type(GraphType::Line)
+                        .style(Style::default().fg(app.theme.chart_fg).dim())
+                })
+                .chain(threshold_datasets(&thresholds, &lines))
+                .chain(datasets)
+                .collect();
+
+            // Create the Y axis and define its properties
+            let y_axis = Axis::default()
+                .title(selection.clone().fg(app.theme.chart_fg))
+                .style(Style::default().fg(app.theme.chart_fg))
+                .bounds([min_y, max_y])
+                .labels(
+                    y_labels
+                        .into_iter()
+                        .map(|label| label.fg(app.theme.chart_fg).bold())
+                        .collect(),
+                );
+
+            let legend_position = match dataset.facets.len() == 1 && thresholds.is_empty() {
+                true => None,
+                false => Some(LegendPosition::TopRight),
+            };
+
+            // Create the chart and link all the parts together
+            let chart = Chart::new(datasets)
+                .block(
+                    Block::default()
                         .borders(Borders::ALL)
                         .border_style(Style::default().fg(app.theme.chart_fg))
                         .border_type(BorderType::Thick)
                         .border_type(BorderType::Rounded),
                 )
                 .legend_position(legend_position)
+                // Column names such as percentile.duration.95 outgrow the default quarter
+                .hidden_legend_constraints((Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)))
                 .x_axis(x_axis)
                 .y_axis(y_axis);
             frame.render_widget(chart, area);
@@ -344,18 +1648,1738 @@ pub fn render_graph(app: &mut App, frame: &mut Frame, area: Rect) {
     // frame.render_widget(chart, frame.size());
 }
 
-pub fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
-    let popup_layout = Layout::vertical([
-        Constraint::Percentage((100 - percent_y) / 2),
-        Constraint::Percentage(percent_y),
-        Constraint::Percentage((100 - percent_y) / 2),
+// Stands in for a chart that is still loading, failed, or ran but returned no rows, so none
+// of them look like an empty chart. Returns false when there is a chart to draw.
+fn render_placeholder(app: &App, frame: &mut Frame, area: Rect, key: &str, selected: bool) -> bool {
+    let lines = match app.datasets.get(key) {
+        None => vec![Line::from(app.text.get(Msg::LoadingQuery))],
+        Some(data) => match &data.error {
+            Some(error) => vec![
+                Line::from(app.text.get(Msg::QueryFailed).red().bold()),
+                Line::from(error.to_owned()),
+            ],
+            None if data.facets.values().all(|points| points.is_empty())
+                && data.histogram.is_empty()
+                && data.scalars.is_empty()
+                && data.facet_totals.is_empty()
+                && data.comparison.values().all(|points| points.is_empty()) =>
+            {
+                let mut lines = vec![Line::from(app.text.get(Msg::NoData).bold())];
+                if let Ok(query) = parse_key(key) {
+                    lines.push(Line::from(
+                        app.text
+                            .fill(Msg::NoDataWindow, &[&query.since, &query.until]),
+                    ));
+                }
+                lines
+            }
+            None => return false,
+        },
+    };
+
+    let title = app
+        .datasets
+        .get(key)
+        .and_then(|data| data.query_alias.to_owned())
+        .unwrap_or(key.to_owned());
+    let border_fg = match selected {
+        true => app.theme.focus_fg,
+        false => app.theme.chart_fg,
+    };
+    let [_, middle, _] = Layout::vertical([
+        Constraint::Fill(1),
+        Constraint::Length(lines.len() as u16),
+        Constraint::Fill(1),
     ])
-    .split(r);
+    .areas(area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(border_fg))
+        .title(title);
 
-    Layout::horizontal([
-        Constraint::Percentage((100 - percent_x) / 2),
-        Constraint::Percentage(percent_x),
-        Constraint::Percentage((100 - percent_x) / 2),
+    frame.render_widget(block, area);
+    frame.render_widget(
+        Paragraph::new(lines)
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true }),
+        middle.inner(&Margin::new(1, 0)),
+    );
+    true
+}
+
+// Draws each single value large, side by side, returning false for any other dataset
+fn render_billboard(app: &App, frame: &mut Frame, area: Rect, key: &str, selected: bool) -> bool {
+    let Some(data) = app
+        .datasets
+        .get(key)
+        .filter(|data| !data.scalars.is_empty())
+    else {
+        return false;
+    };
+
+    let title = data.query_alias.to_owned().unwrap_or(key.to_owned());
+    let border_fg = match selected {
+        true => app.theme.focus_fg,
+        false => app.theme.chart_fg,
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(border_fg))
+        .title(title);
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let cells = Layout::horizontal(vec![
+        Constraint::Ratio(1, data.scalars.len() as u32);
+        data.scalars.len()
     ])
-    .split(popup_layout[1])[1]
+    .split(inner);
+    for (scalar, cell) in data.scalars.iter().zip(cells.iter()) {
+        let precision = match scalar.value.fract() == 0.0 {
+            true => 0,
+            false => 2,
+        };
+        let value = app.numbers.format(scalar.value, precision);
+        let mut lines = vec![Line::from(scalar.name.to_owned().fg(app.theme.chart_fg))];
+        if let Some(previous) = scalar.previous.filter(|previous| *previous != 0.0) {
+            let change = (scalar.value - previous) / previous.abs() * 100.0;
+            let delta = format!(
+                "{} {}%",
+                if change < 0.0 { "▼" } else { "▲" },
+                app.numbers.format(change.abs(), 1)
+            );
+            let delta = app.text.fill(Msg::VsPrevious, &[delta.as_str()]);
+            lines.push(Line::from(match change < 0.0 {
+                true => delta.red(),
+                false => delta.green(),
+            }));
+        }
+
+        // Block digits are 8 cells wide, and 8 or 4 tall depending on the room available
+        let width = value.chars().count() as u16 * 8;
+        let size = match cell.height.saturating_sub(lines.len() as u16) {
+            height if height >= 8 && cell.width >= width => Some((PixelSize::Full, 8)),
+            height if height >= 4 && cell.width >= width => Some((PixelSize::HalfHeight, 4)),
+            _ => None,
+        };
+        let height = size.map_or(1, |(_, height)| height);
+        let [_, value_area, label_area, _] = Layout::vertical([
+            Constraint::Fill(1),
+            Constraint::Length(height),
+            Constraint::Length(lines.len() as u16),
+            Constraint::Fill(1),
+        ])
+        .areas(*cell);
+        match size {
+            Some((pixel_size, _)) => {
+                let [_, value_area, _] = Layout::horizontal([
+                    Constraint::Fill(1),
+                    Constraint::Length(width),
+                    Constraint::Fill(1),
+                ])
+                .areas(value_area);
+                let big = BigText::builder()
+                    .pixel_size(pixel_size)
+                    .style(Style::default().fg(app.theme.value_fg))
+                    .lines(vec![value.into()])
+                    .build()
+                    .expect("ERROR: Could not build billboard!");
+                frame.render_widget(big, value_area);
+            }
+            None => frame.render_widget(
+                Paragraph::new(value.fg(app.theme.value_fg).bold()).alignment(Alignment::Center),
+                value_area,
+            ),
+        }
+        frame.render_widget(
+            Paragraph::new(lines).alignment(Alignment::Center),
+            label_area,
+        );
+    }
+    true
+}
+
+// Draws facet totals as horizontal bars labelled with their share, returning false for any
+// other dataset
+fn render_breakdown(app: &App, frame: &mut Frame, area: Rect, key: &str, selected: bool) -> bool {
+    let Some(data) = app
+        .datasets
+        .get(key)
+        .filter(|data| !data.facet_totals.is_empty())
+    else {
+        return false;
+    };
+
+    let title = data.query_alias.to_owned().unwrap_or(key.to_owned());
+    let border_fg = match selected {
+        true => app.theme.focus_fg,
+        false => app.theme.chart_fg,
+    };
+    let total: f64 = data.facet_totals.iter().map(|total| total.value).sum();
+    let bars: Vec<Bar> = data
+        .facet_totals
+        .iter()
+        .enumerate()
+        .map(|(i, facet)| {
+            let mut text = app.numbers.format(facet.value, 2);
+            if total > 0.0 {
+                text += &format!(" ({}%)", app.numbers.format(facet.value / total * 100.0, 1));
+            }
+            // Bars only take whole numbers, so keep two decimal places of the value
+            Bar::default()
+                .label(Line::from(facet.name.to_owned()))
+                .value((facet.value.max(0.0) * 100.0).round() as u64)
+                .text_value(text)
+                .style(facet_style(app, &facet.name, i, data.facet_totals.len() == 1).0)
+                .value_style(Style::default().fg(app.theme.chart_fg))
+        })
+        .collect();
+    let chart = BarChart::default()
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .border_style(Style::default().fg(border_fg))
+                .title(title),
+        )
+        .direction(Direction::Horizontal)
+        .data(BarGroup::default().bars(&bars))
+        .bar_width(1)
+        .bar_gap(0)
+        .label_style(Style::default().fg(app.theme.chart_fg));
+    frame.render_widget(chart, area);
+    true
+}
+
+// Draws histogram() results as bars, returning false for any other dataset
+fn render_histogram(app: &App, frame: &mut Frame, area: Rect, key: &str, selected: bool) -> bool {
+    let Some(data) = app
+        .datasets
+        .get(key)
+        .filter(|data| !data.histogram.is_empty())
+    else {
+        return false;
+    };
+
+    let title = data.query_alias.to_owned().unwrap_or(key.to_owned());
+    let border_fg = match selected {
+        true => app.theme.focus_fg,
+        false => app.theme.chart_fg,
+    };
+    let bars: Vec<(&str, u64)> = data
+        .histogram
+        .iter()
+        .map(|(label, count)| (label.as_str(), *count))
+        .collect();
+    // Spread the bars across the chart, leaving a gap of one between each
+    let width = (area.width.saturating_sub(2) / bars.len() as u16).saturating_sub(1);
+    let chart = BarChart::default()
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .border_style(Style::default().fg(border_fg))
+                .title(title),
+        )
+        .data(&bars)
+        .bar_width(width.max(1))
+        .bar_gap(1)
+        .bar_style(Style::default().fg(app.theme.value_fg))
+        .label_style(Style::default().fg(app.theme.chart_fg))
+        .value_style(Style::default().fg(app.theme.chart_fg).reversed());
+    frame.render_widget(chart, area);
+    true
+}
+
+pub fn render_tabs(app: &App, frame: &mut Frame, area: Rect) {
+    let tabs = Tabs::new(app.datasets.tabs().iter().map(String::as_str))
+        .select(app.datasets.current_tab())
+        .style(Style::default().fg(app.theme.chart_fg))
+        .highlight_style(Style::default().fg(app.theme.focus_fg).bold());
+    frame.render_widget(tabs, area);
+}
+
+pub fn render_help(app: &App, frame: &mut Frame, area: Rect) {
+    let area = centered_rect(60, 80, area);
+    let keymap = app.keymap();
+    let width = keymap
+        .iter()
+        .flat_map(|(_, keys)| keys.iter().map(|(key, _)| key.len()))
+        .max()
+        .unwrap_or_default();
+
+    let mut lines = vec![];
+    for (group, keys) in keymap {
+        lines.push(Line::from(
+            app.text.get(group).bold().fg(app.theme.focus_fg),
+        ));
+        for (key, description) in keys {
+            lines.push(Line::from(vec![
+                format!("  {:<width$}  ", key).fg(app.theme.value_fg),
+                app.text.get(description).into(),
+            ]));
+        }
+        lines.push(Line::default());
+    }
+
+    let help = Paragraph::new(lines)
+        .scroll((app.help.unwrap_or_default(), 0))
+        .block(
+            Block::default()
+                .title(app.text.get(Msg::HelpTitle))
+                .title_bottom(app.text.get(Msg::HelpScroll))
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .border_style(Style::default().fg(app.theme.focus_fg)),
+        );
+    frame.render_widget(Clear, area);
+    frame.render_widget(help, area);
+}
+
+pub fn render_diagnostics(app: &App, frame: &mut Frame, area: Rect) {
+    let area = centered_rect(80, 80, area);
+    let heading = |msg| Line::from(app.text.get(msg).bold().fg(app.theme.focus_fg));
+    let millis = |latency: std::time::Duration| format!("{}ms", latency.as_millis());
+
+    let mut lines = vec![heading(Msg::DiagnosticsRequests)];
+    let requests = app.backend.diagnostics.requests();
+    match requests.is_empty() {
+        true => lines.push(Line::from(format!(
+            "  {}",
+            app.text.get(Msg::DiagnosticsNoRequests)
+        ))),
+        false => {
+            lines.push(Line::from(
+                format!("  {}", app.text.get(Msg::DiagnosticsColumns)).fg(app.theme.chart_fg),
+            ));
+            for (query, stats) in requests {
+                let failures = format!("{:>6}", stats.failures);
+                lines.push(Line::from(vec![
+                    format!("  {:>8}  ", stats.requests).fg(app.theme.value_fg),
+                    match stats.failures {
+                        0 => failures.fg(app.theme.value_fg),
+                        _ => failures.red(),
+                    },
+                    format!(
+                        "  {:>8}  {:>8}  ",
+                        millis(stats.last_latency),
+                        millis(stats.mean_latency())
+                    )
+                    .fg(app.theme.value_fg),
+                    query.into(),
+                ]));
+            }
+        }
+    }
+    lines.push(Line::default());
+
+    lines.push(heading(Msg::DiagnosticsEvents));
+    let events = app.backend.diagnostics.events();
+    if events.is_empty() {
+        lines.push(Line::from(format!(
+            "  {}",
+            app.text.get(Msg::DiagnosticsNoEvents)
+        )));
+    }
+    for event in events {
+        lines.push(Line::from(format!("  {}", event)));
+    }
+
+    let diagnostics = Paragraph::new(lines).block(
+        Block::default()
+            .title(app.text.get(Msg::DiagnosticsTitle))
+            .title_bottom(app.text.get(Msg::DiagnosticsClose))
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(app.theme.focus_fg)),
+    );
+    frame.render_widget(Clear, area);
+    frame.render_widget(diagnostics, area);
+}
+
+pub fn render_too_small(app: &App, frame: &mut Frame, area: Rect) {
+    let message = app.text.fill(
+        Msg::TooSmall,
+        &[
+            &area.width.to_string(),
+            &area.height.to_string(),
+            &MIN_WIDTH.to_string(),
+            &MIN_HEIGHT.to_string(),
+        ],
+    );
+    let [_, middle, _] = Layout::vertical([
+        Constraint::Min(0),
+        Constraint::Length(area.height.min(3)),
+        Constraint::Min(0),
+    ])
+    .areas(area);
+    let message = Paragraph::new(message)
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true })
+        .style(Style::default().fg(app.theme.focus_fg));
+    frame.render_widget(message, middle);
+}
+
+pub fn render_error(app: &App, frame: &mut Frame, area: Rect) {
+    let area = centered_rect(50, 20, area);
+    let error = Paragraph::new(app.error.clone().unwrap_or_default())
+        .wrap(Wrap { trim: true })
+        .block(
+            Block::default()
+                .title(app.text.get(Msg::ErrorTitle).red().bold())
+                .title_bottom(app.text.get(Msg::ErrorDismiss))
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .border_style(Style::default().red()),
+        );
+    frame.render_widget(Clear, area);
+    frame.render_widget(error, area);
+}
+
+pub fn render_palette(app: &App, frame: &mut Frame, area: Rect) {
+    let area = centered_rect(50, 50, area);
+    let block = Block::default()
+        .title(app.text.get(Msg::PaletteTitle))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(app.theme.focus_fg));
+    let [input_area, list_area] =
+        Layout::vertical([Constraint::Length(2), Constraint::Min(1)]).areas(block.inner(area));
+
+    let input = Paragraph::new(format!(":{}", app.input_buffer(PALETTE)))
+        .style(Style::default().fg(app.theme.focus_fg))
+        .block(Block::default().borders(Borders::BOTTOM));
+    let matches = app.palette_matches();
+    let items = match matches.is_empty() {
+        true => vec![app.text.get(Msg::PaletteEmpty)],
+        false => matches.iter().map(|(msg, _)| app.text.get(*msg)).collect(),
+    };
+    let list = List::new(items).highlight_style(
+        Style::default()
+            .fg(app.theme.focus_fg)
+            .add_modifier(Modifier::REVERSED),
+    );
+    let mut state =
+        ListState::default().with_selected((!matches.is_empty()).then_some(app.palette));
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(block, area);
+    frame.render_widget(input, input_area);
+    frame.render_stateful_widget(list, list_area, &mut state);
+}
+
+pub fn render_status_bar(app: &App, frame: &mut Frame, area: Rect) {
+    let api = match app.api_ok {
+        None => app.text.get(Msg::StatusConnecting).fg(app.theme.chart_fg),
+        Some(true) => app.text.get(Msg::StatusOk).green(),
+        Some(false) => app.text.get(Msg::StatusError).red(),
+    };
+    let in_flight = app.backend.diagnostics.in_flight(&app.selected_query);
+    let refresh = match (in_flight, app.next_refresh()) {
+        (Some(elapsed), _) => app.text.fill(
+            Msg::StatusFetching,
+            &[&app.numbers.format(elapsed.as_secs_f64(), 1)],
+        ),
+        (None, Some(seconds)) => app.text.fill(Msg::StatusRefresh, &[&seconds.to_string()]),
+        (None, None) if app.idle => app.text.get(Msg::StatusRefreshPaused).to_owned(),
+        (None, None) => String::new(),
+    };
+    let account = app.text.fill(Msg::StatusAccount, &[&app.active_account()]);
+    let timezone = app.text.fill(Msg::StatusTimezone, &[&app.timezone.name()]);
+    let hints = match app.focus {
+        _ if app.error.is_some() => Msg::ErrorDismiss,
+        _ if app.help.is_some() => Msg::HelpScroll,
+        _ if app.show_diagnostics => Msg::DiagnosticsClose,
+        _ if matches!(app.input_mode, InputMode::Input) => Msg::HintsInput,
+        Focus::Dashboard => Msg::HintsDashboard,
+        Focus::Traces
+        | Focus::Entities
+        | Focus::Alerts
+        | Focus::Import
+        | Focus::Hosts
+        | Focus::Kubernetes
+        | Focus::Crashes
+        | Focus::ServiceLevels
+        | Focus::Workloads
+        | Focus::Templates => Msg::HintsView,
+        Focus::Console => Msg::HintsConsole,
+        _ if app.scrub.is_some() => Msg::HintsScrub,
+        _ => Msg::HintsDefault,
+    };
+
+    let separator = " | ".fg(app.theme.chart_fg);
+    let mut spans = vec![" ".into(), api, separator.clone()];
+    if let Some((message, colour, at)) = &app.flash {
+        if at.elapsed() < FLASH {
+            spans.extend([
+                message.to_owned().fg(*colour).bold().reversed(),
+                separator.clone(),
+            ]);
+        }
+    }
+    if !refresh.is_empty() {
+        spans.extend([
+            Span::styled(refresh, Style::default().fg(app.theme.value_fg)),
+            separator.clone(),
+        ]);
+    }
+    if !app.variables.is_empty() {
+        let variables = app
+            .variables
+            .iter()
+            .map(|(name, value)| format!("${}={}", name, value))
+            .collect::<Vec<_>>()
+            .join(" ");
+        spans.extend([
+            Span::styled(variables, Style::default().fg(app.theme.value_fg)),
+            separator.clone(),
+        ]);
+    }
+    spans.extend([
+        Span::styled(account, Style::default().fg(app.theme.value_fg)),
+        separator.clone(),
+        Span::styled(timezone, Style::default().fg(app.theme.value_fg)),
+        separator,
+        app.text.get(hints).fg(app.theme.chart_fg),
+    ]);
+    frame.render_widget(Paragraph::new(Line::from(spans)), area);
+}
+
+pub fn render_preflight(app: &App, frame: &mut Frame, area: Rect) {
+    let Some(failure) = &app.preflight else {
+        return;
+    };
+    let stage = match failure {
+        PreflightFailure::Network(_) => 0,
+        PreflightFailure::Auth(_) => 1,
+        PreflightFailure::Account(_) => 2,
+    };
+    let (message, hint) = match failure {
+        PreflightFailure::Network(message) => (message, Msg::PreflightNetworkHint),
+        PreflightFailure::Auth(message) => (message, Msg::PreflightAuthHint),
+        PreflightFailure::Account(message) => (message, Msg::PreflightAccountHint),
+    };
+
+    let mut lines = vec![
+        Line::from(app.text.get(Msg::PreflightTitle).bold()),
+        Line::from(""),
+    ];
+    let checks = [
+        Msg::PreflightNetwork,
+        Msg::PreflightAuth,
+        Msg::PreflightAccount,
+    ];
+    for (i, check) in checks.into_iter().enumerate() {
+        let status = match i.cmp(&stage) {
+            Ordering::Less => "ok".green(),
+            Ordering::Equal => message.to_owned().red(),
+            Ordering::Greater => app.text.get(Msg::PreflightUnchecked).dark_gray(),
+        };
+        lines.push(Line::from(vec![
+            format!("{:<16}", app.text.get(check)).bold(),
+            status,
+        ]));
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from(app.text.get(hint)));
+    lines.push(Line::from(""));
+    lines.push(Line::from(
+        app.text.get(Msg::PreflightHelp).fg(app.theme.value_fg),
+    ));
+
+    let paragraph = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(Color::Red)),
+    );
+    frame.render_widget(Clear, area);
+    frame.render_widget(paragraph, centered_rect(70, 50, area));
+}
+
+// Plain text in place of Braille charts, so screen readers have something to read
+fn render_summary(
+    app: &App,
+    frame: &mut Frame,
+    area: Rect,
+    data: &AppDataset,
+    title: String,
+    selected: bool,
+) {
+    let time = |seconds: f64| app.timezone.format(seconds, "%H:%M:%S");
+
+    let mut lines = vec![Line::from(format!(
+        "{} from {} to {}",
+        data.selection,
+        time(data.bounds.mins.0),
+        time(data.bounds.maxes.0)
+    ))];
+    if let Some((start, end)) = app.scrub.filter(|_| selected) {
+        lines.push(Line::from(
+            app.text.fill(Msg::Selected, &[&time(start), &time(end)]),
+        ));
+    }
+    for (facet, points) in &data.facets {
+        let Some((_, latest)) = points.last() else {
+            lines.push(Line::from(format!("{}: no data", facet)));
+            continue;
+        };
+        let min = points.iter().map(|(_, y)| *y).fold(f64::MAX, f64::min);
+        let max = points.iter().map(|(_, y)| *y).fold(f64::MIN, f64::max);
+        lines.push(Line::from(format!(
+            "{}: latest {}, min {}, max {}, mean {} over {} points",
+            facet,
+            app.numbers.format(*latest, 2),
+            app.numbers.format(min, 2),
+            app.numbers.format(max, 2),
+            app.numbers.format(mean(points), 2),
+            points.len()
+        )));
+    }
+    for facet in &data.facet_totals {
+        lines.push(Line::from(format!(
+            "{}: {}",
+            facet.name,
+            app.numbers.format(facet.value, 2)
+        )));
+    }
+    for scalar in &data.scalars {
+        lines.push(Line::from(format!(
+            "{}: {}",
+            scalar.name,
+            app.numbers.format(scalar.value, 2)
+        )));
+    }
+    for (bucket, count) in &data.histogram {
+        lines.push(Line::from(format!("{}: {}", bucket, count)));
+    }
+
+    let border_fg = match selected {
+        true => app.theme.focus_fg,
+        false => app.theme.chart_fg,
+    };
+    let summary = Paragraph::new(lines)
+        .style(Style::default().fg(app.theme.chart_fg))
+        .wrap(Wrap { trim: true })
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .border_style(Style::default().fg(border_fg))
+                .title(title),
+        );
+    frame.render_widget(summary, area);
+}
+
+// Okabe-Ito palette, distinguishable under the common colour vision deficiencies
+const COLOUR_BLIND_PALETTE: [Color; 7] = [
+    Color::Rgb(230, 159, 0),
+    Color::Rgb(86, 180, 233),
+    Color::Rgb(0, 158, 115),
+    Color::Rgb(240, 228, 66),
+    Color::Rgb(0, 114, 178),
+    Color::Rgb(213, 94, 0),
+    Color::Rgb(204, 121, 167),
+];
+
+const COLOUR_BLIND_MARKERS: [Marker; 4] = [
+    Marker::Braille,
+    Marker::Dot,
+    Marker::Block,
+    Marker::HalfBlock,
+];
+
+// A lone series takes the value colour, whatever its column is called
+fn facet_style(app: &App, facet: &str, i: usize, single: bool) -> (Style, Marker) {
+    if app.config.colour_blind {
+        // Markers vary too, so series never rely on colour alone
+        return (
+            Style::default().fg(COLOUR_BLIND_PALETTE[i % COLOUR_BLIND_PALETTE.len()]),
+            COLOUR_BLIND_MARKERS[i % COLOUR_BLIND_MARKERS.len()],
+        );
+    }
+    let style = match facet {
+        ".NET" => Style::default().fg(app.theme.net_fg),
+        "Elasticsearch" => Style::default().fg(app.theme.elastic_fg),
+        "Web external" => Style::default().fg(app.theme.webex_fg),
+        _ if single => Style::default().fg(app.theme.value_fg),
+        _ => Style::default(),
+    };
+    (style, Marker::Braille)
+}
+
+// Evenly spaced from the start of the window to now, as many as fit the chart's width
+fn x_ticks(min_x: f64, max_x: f64, width: u16) -> Vec<f64> {
+    let span = max_x - min_x;
+    let label_width = match span > 60.0 * 60.0 * 24.0 {
+        true => 12,
+        false => 5,
+    };
+    let count = (width as usize / (label_width + 8)).clamp(2, 6);
+    (0..count)
+        .map(|i| min_x + span * i as f64 / (count - 1) as f64)
+        .collect()
+}
+
+// Time of day, with the date once the window spans more than a day
+fn time_label(timezone: Timezone, seconds: f64, span: f64) -> String {
+    let format = match span > 60.0 * 60.0 * 24.0 {
+        true => "%b %d %H:%M",
+        false => "%H:%M",
+    };
+    timezone.format(seconds, format)
+}
+
+fn gridlines(ticks: &[f64], min_y: f64, max_y: f64) -> Vec<[(f64, f64); 2]> {
+    let inner = ticks.len().saturating_sub(1);
+    ticks
+        .iter()
+        .take(inner)
+        .skip(1)
+        .map(|x| [(*x, min_y), (*x, max_y)])
+        .collect()
+}
+
+// Bottom, middle and top of the y axis, in the dataset's unit
+fn y_labels(app: &App, chart: &ChartData) -> Vec<String> {
+    let (min, max) = chart.y_bounds;
+    [min, (min + max) / 2.0, max]
+        .iter()
+        .map(|y| chart.unit.format(chart.axis.unscale(*y), &app.numbers))
+        .collect()
+}
+
+fn facet_datasets<'a>(app: &App, data: &'a ChartData) -> Vec<Dataset<'a>> {
+    data.facets
+        .iter()
+        .enumerate()
+        .map(|(i, (facet, points))| {
+            let (mut style, marker) = facet_style(app, facet, i, data.facets.len() == 1);
+            // Stacked bands need telling apart even where facets have no colour of their own
+            if data.style.is_stacked() && style.fg.is_none() {
+                style = style.fg(PALETTES[i % PALETTES.len()].c400);
+            }
+            let graph_type = match data.style {
+                ChartStyle::Line => GraphType::Line,
+                _ => GraphType::Scatter,
+            };
+            Dataset::default()
+                .name(facet.to_owned())
+                .data(&points[..])
+                .marker(marker)
+                .graph_type(graph_type)
+                .style(style)
+        })
+        .chain(comparison_datasets(
+            &data.comparison,
+            "previous",
+            app.theme.chart_fg,
+        ))
+        .chain(comparison_datasets(
+            &data.snapshot,
+            "snapshot",
+            app.theme.value_fg,
+        ))
+        .chain(data.anomalies.values().map(|points| {
+            Dataset::default()
+                .data(&points[..])
+                .marker(Marker::Block)
+                .graph_type(GraphType::Scatter)
+                .style(Style::default().fg(ANOMALY))
+        }))
+        .collect()
+}
+
+// Previous-period series from COMPARE WITH or a snapshot, drawn dotted behind the current period
+fn comparison_datasets<'a>(
+    comparison: &'a BTreeMap<String, Vec<(f64, f64)>>,
+    label: &'a str,
+    colour: Color,
+) -> impl Iterator<Item = Dataset<'a>> {
+    comparison.iter().map(move |(facet, points)| {
+        Dataset::default()
+            .name(format!("{} ({})", facet, label))
+            .data(&points[..])
+            .marker(Marker::Dot)
+            .graph_type(GraphType::Scatter)
+            .style(Style::default().fg(colour))
+    })
+}
+
+// Where each threshold is plotted, its legend name and colour
+fn threshold_marks(app: &App, chart: &ChartData) -> Vec<(f64, String, Color)> {
+    chart
+        .thresholds
+        .iter()
+        .map(|(y, threshold)| {
+            (
+                *y,
+                threshold.name(chart.unit, &app.numbers),
+                threshold.colour(),
+            )
+        })
+        .collect()
+}
+
+fn threshold_lines(marks: &[(f64, String, Color)], min_x: f64, max_x: f64) -> Vec<[(f64, f64); 2]> {
+    marks
+        .iter()
+        .map(|(y, _, _)| [(min_x, *y), (max_x, *y)])
+        .collect()
+}
+
+fn threshold_datasets<'a>(
+    marks: &[(f64, String, Color)],
+    lines: &'a [[(f64, f64); 2]],
+) -> Vec<Dataset<'a>> {
+    marks
+        .iter()
+        .zip(lines)
+        .map(|((_, name, colour), line)| {
+            Dataset::default()
+                .name(name.to_owned())
+                .data(line)
+                .marker(Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::default().fg(*colour))
+        })
+        .collect()
+}
+
+pub fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
+    let popup_layout = Layout::vertical([
+        Constraint::Percentage((100 - percent_y) / 2),
+        Constraint::Percentage(percent_y),
+        Constraint::Percentage((100 - percent_y) / 2),
+    ])
+    .split(r);
+
+    Layout::horizontal([
+        Constraint::Percentage((100 - percent_x) / 2),
+        Constraint::Percentage(percent_x),
+        Constraint::Percentage((100 - percent_x) / 2),
+    ])
+    .split(popup_layout[1])[1]
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Utc};
+    use crossterm::event::{KeyCode, KeyEvent};
+    use serde_json::json;
+    use server::timeseries::TimeseriesResult;
+
+    use crate::{
+        action::Action,
+        app::Focus,
+        backend::{FacetTotal, Scalar, Slo},
+        dataset::ChartStyle,
+        format::Timezone,
+        testing::{add_query, app, contains, recv, render, series},
+    };
+
+    const QUERY: &str = "FROM Transaction SELECT count(*) WHERE appName = 'api' SINCE 30 minutes ago UNTIL now LIMIT MAX TIMESERIES";
+
+    #[test]
+    fn empty_app_shows_list_and_status_bar() {
+        let mut app = app(Ok(vec![]));
+        let screen = render(&mut app, 120, 40);
+        assert!(contains(&screen, "Active Queries"));
+        assert!(contains(&screen, "API: waiting"));
+        assert!(contains(&screen, "account 1"));
+    }
+
+    #[test]
+    fn results_render_as_a_chart() {
+        let now = Utc::now().timestamp() as f64;
+        let mut results = series(Some("web"), &[(now - 600.0, 12.0), (now - 540.0, 42.0)]);
+        results.extend(series(
+            Some("worker"),
+            &[(now - 600.0, 3.0), (now - 540.0, 7.0)],
+        ));
+        let mut app = app(Ok(results));
+        add_query(&mut app, QUERY);
+
+        let screen = render(&mut app, 120, 40);
+        assert!(contains(&screen, "API: ok"));
+        assert!(contains(&screen, "count(*)"));
+        assert!(contains(&screen, "worker"));
+        assert!(!contains(&screen, "No data in range"));
+    }
+
+    #[test]
+    fn each_selected_aggregate_is_its_own_series() {
+        let now = Utc::now().timestamp() as f64;
+        let row = |begin: f64, average: f64, p95: f64| -> TimeseriesResult {
+            serde_json::from_value(json!({
+                "beginTimeSeconds": begin,
+                "endTimeSeconds": begin + 60.0,
+                "average.duration": average,
+                "percentile.duration": { "95": p95 },
+            }))
+            .unwrap()
+        };
+        let mut app = app(Ok(vec![
+            row(now - 600.0, 0.2, 0.9),
+            row(now - 540.0, 0.3, 1.4),
+        ]));
+        add_query(
+            &mut app,
+            "FROM Transaction SELECT average(duration), percentile(duration, 95) WHERE true SINCE 30 minutes ago UNTIL now LIMIT MAX TIMESERIES",
+        );
+
+        let screen = render(&mut app, 120, 40);
+        assert!(contains(&screen, "average.duration"));
+        assert!(contains(&screen, "percentile.duration.95"));
+    }
+
+    #[test]
+    fn each_percentile_is_its_own_series() {
+        let now = Utc::now().timestamp() as f64;
+        let row = |begin: f64, p95: f64, p99: f64| -> TimeseriesResult {
+            serde_json::from_value(json!({
+                "beginTimeSeconds": begin,
+                "endTimeSeconds": begin + 60.0,
+                "percentile.duration": { "95": p95, "99": p99 },
+            }))
+            .unwrap()
+        };
+        let mut app = app(Ok(vec![
+            row(now - 600.0, 0.9, 2.0),
+            row(now - 540.0, 1.4, 3.1),
+        ]));
+        add_query(
+            &mut app,
+            "FROM Transaction SELECT percentile(duration, 95, 99) WHERE true SINCE 30 minutes ago UNTIL now LIMIT MAX TIMESERIES",
+        );
+
+        let screen = render(&mut app, 120, 40);
+        assert!(contains(&screen, "percentile.duration.95"));
+        assert!(contains(&screen, "percentile.duration.99"));
+    }
+
+    #[test]
+    fn queries_without_timeseries_are_billboards() {
+        let row = |comparison: &str, count: f64| -> TimeseriesResult {
+            serde_json::from_value(json!({ "comparison": comparison, "count": count })).unwrap()
+        };
+        let mut app = app(Ok(vec![row("current", 125.0), row("previous", 100.0)]));
+        add_query(
+            &mut app,
+            "SELECT count(*) FROM Transaction SINCE 1 hour ago COMPARE WITH 1 day ago",
+        );
+
+        let dataset = app.datasets.iter().next().unwrap().1;
+        assert_eq!(
+            dataset.scalars,
+            [Scalar {
+                name: "count".to_owned(),
+                value: 125.0,
+                previous: Some(100.0),
+            }]
+        );
+        let screen = render(&mut app, 120, 40);
+        assert!(contains(&screen, "count"));
+        assert!(contains(&screen, "▲ 25.0% vs previous"));
+    }
+
+    #[test]
+    fn facets_without_timeseries_are_a_breakdown() {
+        let row = |name: &str, count: f64| -> TimeseriesResult {
+            serde_json::from_value(json!({ "facet": name, "name": name, "count": count })).unwrap()
+        };
+        let mut app = app(Ok(vec![row("checkout", 300.0), row("search", 100.0)]));
+        add_query(
+            &mut app,
+            "FROM Transaction SELECT count(*) WHERE true FACET name SINCE 1 hour ago UNTIL now LIMIT 10",
+        );
+
+        let dataset = app.datasets.iter().next().unwrap().1;
+        assert_eq!(
+            dataset.facet_totals,
+            [
+                FacetTotal {
+                    name: "checkout".to_owned(),
+                    value: 300.0,
+                },
+                FacetTotal {
+                    name: "search".to_owned(),
+                    value: 100.0,
+                },
+            ]
+        );
+        let screen = render(&mut app, 120, 40);
+        assert!(contains(&screen, "checkout"));
+        assert!(contains(&screen, "300.00 (75.0%)"));
+        assert!(contains(&screen, "100.00 (25.0%)"));
+    }
+
+    #[test]
+    fn chart_styles_cycle_and_stack_facets() {
+        let now = Utc::now().timestamp() as f64;
+        let points = |value: f64| [(now - 600.0, value), (now - 540.0, value)];
+        let mut results = series(Some("200"), &points(3.0));
+        results.extend(series(Some("500"), &points(1.0)));
+        let mut app = app(Ok(results));
+        add_query(
+            &mut app,
+            "FROM Transaction SELECT count(*) WHERE true FACET httpResponseCode SINCE 30 minutes ago UNTIL now LIMIT MAX TIMESERIES",
+        );
+        let key = app.selected_query.to_owned();
+
+        app.handle_action(Action::NextChartStyle);
+        app.handle_action(Action::NextChartStyle);
+        assert_eq!(
+            app.datasets.get(&key).unwrap().style,
+            ChartStyle::StackedArea
+        );
+        render(&mut app, 120, 40);
+        let chart = app.datasets.chart(&key).unwrap();
+        assert_eq!(chart.y_bounds, (0.0, 4.0));
+        // 500 is stacked on top of 200
+        assert!(chart.facets["200"].iter().all(|(_, y)| *y <= 3.0));
+        assert!(chart.facets["500"].iter().all(|(_, y)| *y >= 3.0));
+        assert!(chart.facets["500"].iter().any(|(_, y)| *y == 4.0));
+
+        app.handle_action(Action::NextChartStyle);
+        app.handle_action(Action::NextChartStyle);
+        assert_eq!(app.datasets.get(&key).unwrap().style, ChartStyle::Line);
+        render(&mut app, 120, 40);
+        assert_eq!(app.datasets.chart(&key).unwrap().y_bounds, (1.0, 3.0));
+    }
+
+    #[test]
+    fn y_axis_can_be_logarithmic_with_units() {
+        let now = Utc::now().timestamp() as f64;
+        let mut app = app(Ok(series(
+            None,
+            &[(now - 600.0, 10.0), (now - 540.0, 1000.0)],
+        )));
+        add_query(&mut app, QUERY);
+        let key = app.selected_query.to_owned();
+
+        app.handle_action(Action::EditAxis);
+        for c in "log unit=furlongs".chars() {
+            app.handle_action(Action::InsertChar(c));
+        }
+        app.handle_action(Action::Submit);
+        assert!(app.focus == Focus::Axis);
+        assert!(app.error.is_some());
+
+        app.error = None;
+        for _ in "furlongs".chars() {
+            app.handle_action(Action::DeleteChar);
+        }
+        for c in "ms".chars() {
+            app.handle_action(Action::InsertChar(c));
+        }
+        app.handle_action(Action::Submit);
+        assert!(app.focus == Focus::Default);
+        assert_eq!(app.datasets.get(&key).unwrap().axis.spec(), "log unit=ms");
+
+        let screen = render(&mut app, 120, 40);
+        assert_eq!(app.datasets.chart(&key).unwrap().y_bounds, (1.0, 3.0));
+        assert!(contains(&screen, "10 ms"));
+    }
+
+    #[test]
+    fn thresholds_are_drawn_and_flag_breaches() {
+        let now = Utc::now().timestamp() as f64;
+        let mut app = app(Ok(series(
+            None,
+            &[(now - 600.0, 10.0), (now - 540.0, 40.0)],
+        )));
+        add_query(&mut app, QUERY);
+        let key = app.selected_query.to_owned();
+
+        app.handle_action(Action::EditThresholds);
+        for c in "100:critical, 30:busy:yellow".chars() {
+            app.handle_action(Action::InsertChar(c));
+        }
+        app.handle_action(Action::Submit);
+        assert!(app.focus == Focus::Default);
+
+        let data = app.datasets.get(&key).unwrap();
+        assert_eq!(data.breached().unwrap().label, "busy");
+        let screen = render(&mut app, 120, 40);
+        assert!(contains(&screen, "busy (30)"));
+        assert!(contains(&screen, "critical (100)"));
+        assert_eq!(app.datasets.chart(&key).unwrap().y_bounds, (10.0, 100.0));
+
+        // The box opens with the current thresholds, so append one that isn't a number
+        app.handle_action(Action::EditThresholds);
+        for c in ", x".chars() {
+            app.handle_action(Action::InsertChar(c));
+        }
+        app.handle_action(Action::Submit);
+        assert!(app.focus == Focus::Thresholds);
+        assert!(app.error.is_some());
+    }
+
+    #[test]
+    fn crossing_a_threshold_notifies_once_per_cooldown() {
+        let now = Utc::now().timestamp() as f64;
+        let mut app = app(Ok(series(None, &[(now - 600.0, 50.0)])));
+        app.config.notify = true;
+        add_query(&mut app, QUERY);
+        let key = app.selected_query.to_owned();
+        assert!(app.flash.is_none());
+
+        app.datasets.get_mut(&key).unwrap().thresholds =
+            crate::threshold::Threshold::parse_all("40:busy").unwrap();
+        app.notify_crossing(&key);
+        assert!(app.bell);
+        let screen = render(&mut app, 300, 40);
+        assert!(contains(&screen, "crossed busy (40)"));
+
+        app.bell = false;
+        app.notify_crossing(&key);
+        assert!(!app.bell);
+    }
+
+    #[test]
+    fn spikes_are_marked_as_anomalies() {
+        let now = Utc::now().timestamp() as f64;
+        let mut points = (0..12)
+            .map(|i| (now - 1800.0 + i as f64 * 60.0, 10.0 + (i % 2) as f64))
+            .collect::<Vec<_>>();
+        points.push((now - 1080.0, 100.0));
+        let mut app = app(Ok(series(None, &points)));
+        add_query(&mut app, QUERY);
+        let key = app.selected_query.to_owned();
+
+        let anomalies = &app.datasets.get(&key).unwrap().anomalies;
+        assert_eq!(
+            anomalies.values().flatten().collect::<Vec<_>>(),
+            vec![&(now - 1020.0, 100.0)]
+        );
+        let screen = render(&mut app, 200, 40);
+        assert!(contains(&screen, "Anomalies"));
+        assert!(contains(&screen, "100 at "));
+
+        // Marked by the refresh task, which fetches again when the settings change
+        let config = crate::config::Config {
+            anomaly_sigmas: 0.0,
+            ..Default::default()
+        };
+        app.apply_config(Ok(config));
+        let payload = recv(&app.backend.data_rx);
+        app.handle_payload(payload);
+        assert!(app.datasets.get(&key).unwrap().anomalies.is_empty());
+    }
+
+    #[test]
+    fn details_summarise_the_dataset_or_a_facet() {
+        let now = Utc::now().timestamp() as f64;
+        let points = (1..=20)
+            .map(|i| (now - 1800.0 + i as f64 * 60.0, i as f64))
+            .collect::<Vec<_>>();
+        let mut results = series(Some("api"), &points);
+        results.extend(series(Some("web"), &[(now - 1800.0, 10.5)]));
+        let mut app = app(Ok(results));
+        add_query(&mut app, QUERY);
+
+        let screen = render(&mut app, 240, 40);
+        assert!(contains(&screen, "min 1  max 20  avg 10.5"));
+        assert!(contains(&screen, "p95 19  latest 20"));
+        assert!(contains(&screen, "▁"));
+        assert!(contains(&screen, "█"));
+
+        app.handle_action(Action::NextFacet);
+        app.handle_action(Action::NextFacet);
+        assert_eq!(app.stats_facet.as_deref(), Some("web"));
+        let screen = render(&mut app, 240, 40);
+        assert!(contains(&screen, "min 10.5  max 10.5  avg 10.5"));
+
+        app.handle_action(Action::NextFacet);
+        assert_eq!(app.stats_facet, None);
+    }
+
+    #[test]
+    fn snapshots_are_ghosted_and_diffed() {
+        let now = Utc::now().timestamp() as f64;
+        let mut app = app(Ok(series(
+            Some("api"),
+            &[(now - 600.0, 10.0), (now - 540.0, 20.0)],
+        )));
+        add_query(&mut app, QUERY);
+        let key = app.selected_query.to_owned();
+
+        app.handle_action(Action::ToggleSnapshot);
+        for points in app.datasets.get_mut(&key).unwrap().facets.values_mut() {
+            points.iter_mut().for_each(|(_, y)| *y *= 2.0);
+        }
+        let screen = render(&mut app, 240, 40);
+        assert!(contains(&screen, "vs snapshot from"));
+        assert!(contains(&screen, "api: +100.0%"));
+        assert_eq!(app.datasets.chart(&key).unwrap().snapshot.len(), 1);
+
+        app.handle_action(Action::ToggleSnapshot);
+        let screen = render(&mut app, 240, 40);
+        assert!(!contains(&screen, "vs snapshot from"));
+        assert!(app.datasets.chart(&key).unwrap().snapshot.is_empty());
+    }
+
+    #[test]
+    fn console_runs_graphql_and_scrolls_the_response() {
+        let mut app = app(Ok(vec![]));
+        app.handle_action(Action::Console);
+        assert!(app.focus == Focus::Console);
+
+        app.handle_key(KeyEvent::from(KeyCode::Char('e')));
+        for c in "{ actor { user { email } } }".chars() {
+            app.handle_key(KeyEvent::from(KeyCode::Char(c)));
+        }
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+        assert!(app.console.loading);
+        assert!(app.focus == Focus::Console);
+
+        let response = json!({ "data": { "actor": { "user": { "email": "me@example.com" } } } });
+        app.handle_console(Ok(serde_json::to_string_pretty(&response).unwrap()));
+        let screen = render(&mut app, 120, 40);
+        assert!(contains(&screen, "{ actor { user { email } } }"));
+        assert!(contains(&screen, "\"email\": \"me@example.com\""));
+
+        app.handle_key(KeyEvent::from(KeyCode::Char('j')));
+        assert_eq!(app.console.scroll, 1);
+        app.handle_key(KeyEvent::from(KeyCode::Esc));
+        assert!(app.focus == Focus::Default);
+    }
+
+    #[test]
+    fn hosts_show_sparklines_and_sort_by_usage() {
+        let row = |host: &str, end: f64, cpu: f64, memory: f64| {
+            serde_json::from_value::<TimeseriesResult>(json!({
+                "beginTimeSeconds": end - 60.0,
+                "endTimeSeconds": end,
+                "facet": host,
+                "cpu": cpu,
+                "memory": memory,
+                "disk": 40.0,
+            }))
+            .unwrap()
+        };
+        let mut app = app(Ok(vec![
+            row("alpha", 60.0, 10.0, 80.0),
+            row("alpha", 120.0, 20.0, 85.0),
+            row("beta", 60.0, 50.0, 30.0),
+            row("beta", 120.0, 90.0, 35.0),
+        ]));
+        app.handle_action(Action::Hosts);
+        let hosts = recv(&app.backend.host_rx);
+        app.handle_hosts(hosts);
+
+        let screen = render(&mut app, 160, 30);
+        assert!(contains(&screen, "Name ▼"));
+        assert!(contains(&screen, "alpha  ▁█"));
+        assert!(contains(&screen, "90.0%"));
+
+        app.handle_key(KeyEvent::from(KeyCode::Char('s')));
+        let screen = render(&mut app, 160, 30);
+        assert!(contains(&screen, "cpu ▼"));
+    }
+
+    #[test]
+    fn kubernetes_drills_from_clusters_to_pods() {
+        let row = |name: &str| {
+            serde_json::from_value::<TimeseriesResult>(json!({
+                "facet": name,
+                "restarts": 4,
+                "memory": 2_621_440,
+            }))
+            .unwrap()
+        };
+        let mut app = app(Ok(vec![row("prod"), row("staging")]));
+        let reply = |app: &mut crate::App| {
+            let (path, rows) = recv(&app.backend.kube_rx);
+            app.handle_kube(path, rows);
+        };
+        app.handle_action(Action::Kubernetes);
+        reply(&mut app);
+        let screen = render(&mut app, 160, 30);
+        assert!(contains(&screen, "Kubernetes clusters"));
+        assert!(contains(&screen, "restarts"));
+        assert!(contains(&screen, "2.50 MiB"));
+
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+        reply(&mut app);
+        app.handle_key(KeyEvent::from(KeyCode::Char('j')));
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+        reply(&mut app);
+        let screen = render(&mut app, 160, 30);
+        assert!(contains(&screen, "Kubernetes prod / staging"));
+    }
+
+    #[test]
+    fn browser_apps_are_listed_without_apm_columns() {
+        let mut app = app(Ok(vec![]));
+        app.handle_action(Action::Browser);
+        app.handle_entities(vec![server::entity::Entity {
+            guid: "guid".to_owned(),
+            name: "shop".to_owned(),
+            apm_summary: None,
+        }]);
+        let screen = render(&mut app, 160, 30);
+        assert!(contains(&screen, "Browser apps: Enter"));
+        assert!(!contains(&screen, "apdex"));
+    }
+
+    #[test]
+    fn crash_groups_show_counts_and_attributes() {
+        let group = serde_json::from_value::<TimeseriesResult>(json!({
+            "facet": ["NullPointerException", "CartActivity.java:42"],
+            "crashes": 12,
+            "users": 5,
+            "message": "cart was null",
+            "version": "2.3.1",
+            "line": 42,
+        }))
+        .unwrap();
+        let mut app = app(Ok(vec![group]));
+        app.handle_action(Action::Mobile);
+        app.handle_entities(vec![server::entity::Entity {
+            guid: "guid".to_owned(),
+            name: "shop-android".to_owned(),
+            apm_summary: None,
+        }]);
+        let screen = render(&mut app, 160, 30);
+        assert!(contains(&screen, "Mobile apps: Enter"));
+
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+        let groups = recv(&app.backend.crash_rx);
+        app.handle_crashes(groups);
+        let screen = render(&mut app, 160, 30);
+        assert!(contains(&screen, "Crashes in shop-android"));
+        assert!(contains(
+            &screen,
+            "12 crashes       5 users  NullPointerException at CartActivity.java:42"
+        ));
+        assert!(contains(&screen, "message: cart was null"));
+        assert!(contains(&screen, "line: 42"));
+    }
+
+    #[test]
+    fn service_levels_show_budget_and_burn() {
+        let mut app = app(Ok(vec![]));
+        app.handle_action(Action::ServiceLevels);
+        assert!(app.focus == Focus::ServiceLevels);
+        app.handle_slos(vec![
+            Slo {
+                guid: "a".to_owned(),
+                name: "checkout latency".to_owned(),
+                target: 99.0,
+                days: 7,
+                attainment: Some(99.6),
+                burn: vec![(0.0, 0.5), (3600.0, 1.0)],
+            },
+            Slo {
+                guid: "b".to_owned(),
+                name: "search errors".to_owned(),
+                target: 99.9,
+                days: 7,
+                attainment: Some(99.5),
+                burn: vec![],
+            },
+        ]);
+        assert_eq!(app.slos.slos[1].budget().map(f64::round), Some(-400.0));
+        let screen = render(&mut app, 160, 30);
+        assert!(contains(&screen, "99.6% / 99%"));
+        assert!(contains(&screen, "budget ██████░░░░   60%"));
+        assert!(contains(&screen, "burn ▁█"));
+        assert!(contains(&screen, "-400%"));
+
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+        assert!(app.focus == Focus::Default);
+        let (_, attainment) = app
+            .pending
+            .values()
+            .find(|(alias, _)| alias == "checkout latency attainment")
+            .unwrap();
+        assert_eq!(attainment.thresholds[0].value, 99.0);
+        assert!(app
+            .pending
+            .values()
+            .any(|(alias, _)| alias == "checkout latency burn rate"));
+    }
+
+    #[test]
+    fn workloads_show_status_and_missing_applications() {
+        use crate::backend::WorkloadPayload;
+        use server::workload::{Member, Workload, WorkloadStatus};
+
+        let member = |name: &str, domain: &str| Member {
+            guid: name.to_owned(),
+            name: name.to_owned(),
+            domain: domain.to_owned(),
+            entity_type: "APPLICATION".to_owned(),
+        };
+        let mut app = app(Ok(vec![]));
+        app.handle_action(Action::Workloads);
+        app.handle_workload(WorkloadPayload::Workloads(vec![Workload {
+            guid: "w".to_owned(),
+            name: "checkout".to_owned(),
+            workload_status: Some(WorkloadStatus {
+                status_value: "DEGRADED".to_owned(),
+            }),
+        }]));
+        let screen = render(&mut app, 120, 30);
+        assert!(contains(&screen, "DEGRADED    checkout"));
+
+        app.handle_workload(WorkloadPayload::Members(
+            "checkout".to_owned(),
+            Ok(vec![member("web", "BROWSER")]),
+        ));
+        let screen = render(&mut app, 120, 30);
+        assert!(contains(&screen, "checkout has no APM applications"));
+    }
+
+    #[test]
+    fn templates_ask_for_each_variable_with_recent_values() {
+        use crate::{app::TEMPLATE_VARIABLE, template::QueryTemplate};
+
+        let mut app = app(Ok(vec![]));
+        app.config.templates = vec![QueryTemplate {
+            name: "latency".to_owned(),
+            query: "FROM Transaction SELECT average(duration) WHERE appName = '{{app}}' SINCE {{since}} UNTIL now LIMIT MAX TIMESERIES".to_owned(),
+        }];
+        app.handle_key(KeyEvent::from(KeyCode::Char('Y')));
+        let screen = render(&mut app, 120, 30);
+        assert!(contains(&screen, "latency"));
+
+        let fill = |app: &mut crate::App, values: [&str; 2]| {
+            app.handle_key(KeyEvent::from(KeyCode::Enter));
+            for value in values {
+                assert!(app.focus == Focus::TemplateVariable);
+                app.inputs[TEMPLATE_VARIABLE as usize].buffer.clear();
+                app.inputs[TEMPLATE_VARIABLE as usize].cursor_position = 0;
+                for c in value.chars() {
+                    app.handle_action(Action::InsertChar(c));
+                }
+                app.handle_action(Action::Submit);
+            }
+        };
+        fill(&mut app, ["api", "1 hour ago"]);
+        assert!(app.focus == Focus::QueryInput);
+        assert_eq!(
+            app.input_buffer(crate::app::QUERY),
+            "FROM Transaction SELECT average(duration) WHERE appName = 'api' SINCE 1 hour ago UNTIL now LIMIT MAX TIMESERIES"
+        );
+
+        app.handle_action(Action::Cancel);
+        app.handle_action(Action::Templates);
+        fill(&mut app, ["web", "1 day ago"]);
+        app.handle_action(Action::Cancel);
+
+        // The last value comes first and older ones are a key away
+        app.handle_action(Action::Templates);
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+        let screen = render(&mut app, 120, 30);
+        assert!(contains(&screen, "Value for app"));
+        assert_eq!(app.input_buffer(TEMPLATE_VARIABLE), "web");
+        app.handle_key(KeyEvent::from(KeyCode::Up));
+        assert_eq!(app.input_buffer(TEMPLATE_VARIABLE), "api");
+        app.handle_key(KeyEvent::from(KeyCode::Down));
+        assert_eq!(app.input_buffer(TEMPLATE_VARIABLE), "web");
+    }
+
+    #[test]
+    fn notes_are_marked_in_the_list_and_shown_in_the_details() {
+        let mut app = app(Ok(series(None, &[(Utc::now().timestamp() as f64, 1.0)])));
+        add_query(&mut app, QUERY);
+        let screen = render(&mut app, 200, 40);
+        assert!(!contains(&screen, "✎"));
+
+        app.handle_key(KeyEvent::from(KeyCode::Char('n')));
+        for c in "normal is under 200".chars() {
+            app.handle_action(Action::InsertChar(c));
+        }
+        app.handle_action(Action::Submit);
+        assert!(app.dirty);
+        let screen = render(&mut app, 200, 40);
+        assert!(contains(&screen, "✎ FROM Transaction"));
+        assert!(contains(&screen, "✎ normal is under 200"));
+    }
+
+    #[test]
+    fn variables_re_issue_the_queries_using_them() {
+        use crate::testing::MockNerdGraph;
+        use std::{
+            sync::{Arc, Mutex},
+            time::{Duration, Instant},
+        };
+
+        let mut app = app(Ok(vec![]));
+        let sent = Arc::new(Mutex::new(vec![]));
+        app.backend.nerdgraph = Arc::new(MockNerdGraph {
+            results: Ok(vec![]),
+            sent: sent.clone(),
+        });
+        add_query(&mut app, "FROM Transaction SELECT count(*) WHERE appName = '$app' SINCE 30 minutes ago UNTIL now LIMIT MAX TIMESERIES");
+
+        app.handle_key(KeyEvent::from(KeyCode::Char('V')));
+        assert!(app.focus == Focus::Variables);
+        for c in "app checkout".chars() {
+            app.handle_action(Action::InsertChar(c));
+        }
+        app.handle_action(Action::Submit);
+        assert!(app.error.is_some());
+
+        app.error = None;
+        app.inputs[crate::app::VARIABLES as usize].buffer = "app=checkout".to_owned();
+        app.handle_action(Action::Submit);
+        assert!(app.focus == Focus::Default);
+        assert_eq!(app.variables["app"], "checkout");
+        // Kept as written, so switching again re-keys nothing
+        assert!(app.selected_query.contains("'$app'"));
+        let screen = render(&mut app, 120, 30);
+        assert!(contains(&screen, "$app=checkout"));
+
+        let started = Instant::now();
+        while !sent
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|nrql| nrql.contains("appName = 'checkout'"))
+        {
+            assert!(started.elapsed() < Duration::from_secs(5));
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn slow_queries_show_a_spinner_and_how_long_they_have_taken() {
+        let mut app = app(Ok(series(None, &[(Utc::now().timestamp() as f64, 1.0)])));
+        add_query(&mut app, QUERY);
+        let key = app.selected_query.to_owned();
+        let screen = render(&mut app, 200, 40);
+        assert!(!contains(&screen, "fetching for"));
+        assert!(!app.fetching());
+
+        app.backend.diagnostics.start_request(&key);
+        app.pending.insert(
+            "FROM Transaction SELECT count(*)".to_owned(),
+            ("waiting".to_owned(), Default::default()),
+        );
+        assert!(app.fetching());
+        let screen = render(&mut app, 200, 40);
+        assert!(contains(&screen, "0.0s FROM Transaction"));
+        assert!(contains(&screen, "fetching for 0.0s"));
+        assert!(contains(&screen, "waiting (queued)"));
+    }
+
+    #[test]
+    fn small_terminals_drop_panels_then_charts() {
+        let now = Utc::now().timestamp() as f64;
+        let mut app = app(Ok(series(
+            None,
+            &[(now - 180.0, 1.0), (now - 120.0, 5.0), (now - 60.0, 9.0)],
+        )));
+        add_query(&mut app, QUERY);
+
+        let screen = render(&mut app, 120, 30);
+        assert!(contains(&screen, "Active Queries"));
+        assert!(!contains(&screen, "█"));
+
+        let screen = render(&mut app, 80, 30);
+        assert!(!contains(&screen, "Active Queries"));
+        assert!(contains(&screen, "Time"));
+
+        let screen = render(&mut app, 120, 14);
+        assert!(contains(&screen, "▁"));
+        assert!(contains(&screen, "█"));
+
+        let screen = render(&mut app, 30, 8);
+        assert!(contains(&screen, "Terminal too small: 30x8"));
+    }
+
+    #[test]
+    fn x_axis_is_labelled_in_local_time() {
+        let now = Utc::now().timestamp() as f64;
+        let mut app = app(Ok(series(
+            None,
+            &[(now - 7200.0, 1.0), (now - 3600.0, 2.0)],
+        )));
+        add_query(&mut app, QUERY);
+
+        let screen = render(&mut app, 120, 40);
+        let start = DateTime::from_timestamp(now as i64 - 7140, 0).unwrap();
+        let start = start
+            .with_timezone(&chrono::Local)
+            .format("%H:%M")
+            .to_string();
+        assert!(contains(&screen, &start));
+
+        let week = super::time_label(Timezone::Local, now, 7.0 * 24.0 * 60.0 * 60.0);
+        let today = chrono::Local::now().format("%b %d").to_string();
+        assert!(week.starts_with(&today));
+    }
+
+    #[test]
+    fn timestamps_follow_the_chosen_timezone() {
+        let now = Utc::now().timestamp() as f64;
+        let mut app = app(Ok(series(
+            None,
+            &[(now - 7200.0, 1.0), (now - 3600.0, 2.0)],
+        )));
+        add_query(&mut app, QUERY);
+
+        app.handle_action(Action::NextTimezone);
+        assert!(app.timezone == Timezone::Utc);
+        let screen = render(&mut app, 120, 40);
+        let start = DateTime::from_timestamp(now as i64 - 7140, 0).unwrap();
+        assert!(contains(&screen, &start.format("%H:%M").to_string()));
+        assert!(contains(&screen, "times in UTC"));
+
+        let offset = Timezone::parse("+05:30").unwrap();
+        assert_eq!(offset.name(), "UTC+05:30");
+        assert_eq!(offset.format(0.0, "%Y-%m-%d %H:%M"), "1970-01-01 05:30");
+        assert!(Timezone::parse("Mars/Olympus").is_none());
+        assert!(Timezone::Utc.next(offset) == offset);
+        assert!(Timezone::Utc.next(Timezone::Local) == Timezone::Local);
+    }
+
+    #[test]
+    fn histograms_are_drawn_as_bars() {
+        let now = Utc::now().timestamp() as f64;
+        let row = |begin: f64, buckets: [u64; 4]| -> TimeseriesResult {
+            serde_json::from_value(json!({
+                "beginTimeSeconds": begin,
+                "endTimeSeconds": begin + 60.0,
+                "histogram.duration": buckets,
+            }))
+            .unwrap()
+        };
+        let mut app = app(Ok(vec![
+            row(now - 600.0, [3, 10, 4, 1]),
+            row(now - 540.0, [2, 7, 5, 0]),
+        ]));
+        add_query(
+            &mut app,
+            "FROM Transaction SELECT histogram(duration, 2, 4) WHERE true SINCE 30 minutes ago UNTIL now LIMIT MAX TIMESERIES",
+        );
+
+        let dataset = app.datasets.iter().next().unwrap().1;
+        assert_eq!(
+            dataset.histogram,
+            [
+                ("0".to_owned(), 5),
+                ("0.5".to_owned(), 17),
+                ("1".to_owned(), 9),
+                ("1.5".to_owned(), 1)
+            ]
+        );
+        let screen = render(&mut app, 120, 40);
+        assert!(contains(&screen, "0.5"));
+        assert!(contains(&screen, "17"));
+        assert!(!contains(&screen, app.text.get(crate::i18n::Msg::NoData)));
+    }
+
+    #[test]
+    fn compound_facets_are_labelled_by_every_attribute() {
+        let now = Utc::now().timestamp() as f64;
+        let row = |begin: f64, host: &str, count: f64| -> TimeseriesResult {
+            serde_json::from_value(json!({
+                "beginTimeSeconds": begin,
+                "endTimeSeconds": begin + 60.0,
+                "facet": ["api", host],
+                "appName": "api",
+                "host": host,
+                "count": count,
+            }))
+            .unwrap()
+        };
+        let mut app = app(Ok(vec![
+            row(now - 600.0, "web-1", 4.0),
+            row(now - 600.0, "web-2", 6.0),
+            row(now - 540.0, "web-1", 5.0),
+            row(now - 540.0, "web-2", 7.0),
+        ]));
+        add_query(
+            &mut app,
+            "FROM Transaction SELECT count(*) WHERE true FACET appName, host SINCE 30 minutes ago UNTIL now LIMIT MAX TIMESERIES",
+        );
+
+        let screen = render(&mut app, 120, 40);
+        assert!(contains(&screen, "api, web-1"));
+        assert!(contains(&screen, "api, web-2"));
+    }
+
+    #[test]
+    fn failed_query_shows_the_error() {
+        let mut app = app(Err("NRQL Syntax Error: unexpected token".to_owned()));
+        add_query(&mut app, QUERY);
+
+        let screen = render(&mut app, 120, 40);
+        assert!(contains(&screen, "Query failed"));
+        assert!(contains(&screen, "NRQL Syntax Error: unexpected token"));
+        assert!(contains(&screen, "API: last request failed"));
+    }
+
+    #[test]
+    fn empty_result_shows_no_data() {
+        let mut app = app(Ok(vec![]));
+        add_query(&mut app, QUERY);
+
+        let screen = render(&mut app, 120, 40);
+        assert!(contains(&screen, "No data in range"));
+    }
+
+    #[test]
+    fn diagnostics_panel_lists_requests() {
+        let mut app = app(Err("NRQL Syntax Error: unexpected token".to_owned()));
+        add_query(&mut app, QUERY);
+        app.handle_action(Action::Diagnostics);
+
+        let screen = render(&mut app, 160, 40);
+        assert!(contains(&screen, "Requests by query"));
+        assert!(contains(&screen, "FROM Transaction SELECT count(*)"));
+        assert!(screen.iter().any(|row| row.contains("       1       1")));
+    }
+
+    #[test]
+    fn failed_save_shows_an_error_until_a_key_is_pressed() {
+        let mut app = app(Ok(vec![]));
+        app.handle_action(Action::SaveSession);
+
+        let screen = render(&mut app, 120, 40);
+        assert!(contains(&screen, "Could not write"));
+        assert!(contains(&screen, "Press any key to dismiss"));
+
+        app.handle_key(KeyEvent::from(KeyCode::Char('j')));
+        let screen = render(&mut app, 120, 40);
+        assert!(!contains(&screen, "Could not write"));
+    }
+
+    #[test]
+    fn malformed_query_stays_open_with_an_error() {
+        let mut app = app(Ok(vec![]));
+        app.handle_action(Action::EditQuery);
+        for c in "FROM Transaction SELECT count(*) WHERE true SINCE 1 hour ago LIMIT MAX TIMESERIES"
+            .chars()
+        {
+            app.handle_action(Action::InsertChar(c));
+        }
+        app.handle_action(Action::Submit);
+
+        assert!(app.focus == Focus::QueryInput);
+        assert!(app.datasets.is_empty());
+        let screen = render(&mut app, 120, 40);
+        assert!(contains(&screen, "Invalid query: expected SINCE"));
+
+        app.handle_action(Action::Cancel);
+        let screen = render(&mut app, 120, 40);
+        assert!(!contains(&screen, "Invalid query"));
+    }
+
+    #[test]
+    fn help_overlay_lists_keybindings() {
+        let mut app = app(Ok(vec![]));
+        app.handle_action(Action::Help);
+
+        let screen = render(&mut app, 120, 40);
+        assert!(contains(&screen, "Keybindings"));
+        assert!(contains(&screen, "Enter a new query"));
+    }
 }